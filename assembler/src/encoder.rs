@@ -0,0 +1,217 @@
+use crate::error::ErrorKind;
+use crate::parser::{evaluate, Operand, ParseError};
+use chip8_core::emulator::cpu::opcode::Opcode;
+use std::collections::HashMap;
+
+enum Argument<'a> {
+    Register(u8),
+    I,
+    // [I]
+    IndirectI,
+    Dt,
+    St,
+    K,
+    F,
+    Hf,
+    B,
+    R,
+    // LONG <expression>, the 16 bit address of the XO-CHIP long load
+    Long(Operand),
+    Value(&'a Operand),
+}
+
+fn argument(operand: &Operand) -> Argument<'_> {
+    let text = operand.text.to_ascii_uppercase();
+
+    if text.len() == 2 && text.starts_with('V') {
+        if let Ok(x) = u8::from_str_radix(&text[1..], 16) {
+            return Argument::Register(x);
+        }
+    }
+
+    if let Some(address) = text.strip_prefix("LONG") {
+        if address.starts_with(char::is_whitespace) {
+            let offset = operand.text.len() - address.trim_start().len();
+            return Argument::Long(Operand {
+                text: operand.text[offset..].to_string(),
+                column: operand.column + offset,
+            });
+        }
+    }
+
+    match text.replace(' ', "").as_str() {
+        "I" => Argument::I,
+        "[I]" => Argument::IndirectI,
+        "DT" => Argument::Dt,
+        "ST" => Argument::St,
+        "K" => Argument::K,
+        "F" => Argument::F,
+        "HF" => Argument::Hf,
+        "B" => Argument::B,
+        "R" => Argument::R,
+        _ => Argument::Value(operand),
+    }
+}
+
+/// Bytes taken by an instruction, known before the symbols are.
+pub fn size(mnemonic: &str, operands: &[Operand]) -> u16 {
+    match operands.get(1).map(argument) {
+        Some(Argument::Long(_)) if mnemonic == "LD" => 4,
+        _ => 2,
+    }
+}
+
+/// Machine code of an instruction, `column` is the one of its mnemonic.
+pub fn encode(
+    mnemonic: &str,
+    column: usize,
+    operands: &[Operand],
+    symbols: &HashMap<String, i64>,
+) -> Result<Vec<u8>, ParseError> {
+    use Argument::*;
+
+    let value = |operand: &Operand, min: i64, max: i64| -> Result<u16, ParseError> {
+        let value = evaluate(operand, symbols)?;
+        if value < min || value > max {
+            return Err((operand.column, ErrorKind::OutOfRange { value, min, max }));
+        }
+
+        // negative bytes are stored in two's complement
+        Ok((value & 0xffff) as u16)
+    };
+    let address = |operand: &Operand| value(operand, 0, 0xfff);
+    let byte = |operand: &Operand| Ok(value(operand, -128, 0xff)? as u8);
+    let nibble = |operand: &Operand| Ok(value(operand, 0, 0xf)? as u8);
+    // operands are range checked while matching them, encoding cannot fail
+    let word = |opcode: Opcode| {
+        opcode
+            .encode()
+            .map_err(|_| (column, ErrorKind::InvalidOperands(mnemonic.to_string())))
+    };
+
+    let arguments: Vec<Argument> = operands.iter().map(argument).collect();
+
+    let opcode = match (mnemonic, arguments.as_slice()) {
+        ("CLS", []) => Opcode::Cls,
+        ("RET", []) => Opcode::Ret,
+        ("SCR", []) => Opcode::Scr,
+        ("SCL", []) => Opcode::Scl,
+        ("EXIT", []) => Opcode::Exit,
+        ("LOW", []) => Opcode::Low,
+        ("HIGH", []) => Opcode::High,
+        ("AUDIO", []) => Opcode::LdAudio,
+        // 00C0 and 00D0 are not instructions
+        ("SCD", [Value(n)]) => Opcode::Scd(value(n, 1, 0xf)? as u8),
+        ("SCU", [Value(n)]) => Opcode::Scu(value(n, 1, 0xf)? as u8),
+        ("JP", [Value(nnn)]) => Opcode::Jp(address(nnn)?),
+        ("JP", [Register(0), Value(nnn)]) => Opcode::Jp0(address(nnn)?),
+        ("CALL", [Value(nnn)]) => Opcode::Call(address(nnn)?),
+        ("SE", [Register(x), Register(y)]) => Opcode::SeReg { x: *x, y: *y },
+        ("SE", [Register(x), Value(kk)]) => Opcode::Se {
+            x: *x,
+            byte: byte(kk)?,
+        },
+        ("SNE", [Register(x), Register(y)]) => Opcode::SneReg { x: *x, y: *y },
+        ("SNE", [Register(x), Value(kk)]) => Opcode::Sne {
+            x: *x,
+            byte: byte(kk)?,
+        },
+        ("SAVE", [Register(x), Register(y)]) => Opcode::LdRangeInto { x: *x, y: *y },
+        ("LOAD", [Register(x), Register(y)]) => Opcode::LdRangeFrom { x: *x, y: *y },
+        ("LD", [Register(x), Register(y)]) => Opcode::LdReg { x: *x, y: *y },
+        ("LD", [Register(x), Value(kk)]) => Opcode::Ld {
+            x: *x,
+            byte: byte(kk)?,
+        },
+        ("LD", [I, Value(nnn)]) => Opcode::Ldi(address(nnn)?),
+        ("LD", [I, Long(nnnn)]) => {
+            let nnnn = value(nnnn, 0, 0xffff)?;
+            let opcode = word(Opcode::LdILong)?;
+            return Ok([opcode.to_be_bytes(), nnnn.to_be_bytes()].concat());
+        }
+        ("LD", [Register(x), Dt]) => Opcode::LdFromDt(*x),
+        ("LD", [Register(x), K]) => Opcode::LdK(*x),
+        ("LD", [Dt, Register(x)]) => Opcode::LdIntoDt(*x),
+        ("LD", [St, Register(x)]) => Opcode::LdIntoSt(*x),
+        ("LD", [F, Register(x)]) => Opcode::LdF(*x),
+        ("LD", [Hf, Register(x)]) => Opcode::LdHf(*x),
+        ("LD", [B, Register(x)]) => Opcode::LdB(*x),
+        ("LD", [IndirectI, Register(x)]) => Opcode::LdBatchInto(*x),
+        ("LD", [Register(x), IndirectI]) => Opcode::LdBatchFrom(*x),
+        ("LD", [R, Register(x)]) => Opcode::LdRInto(*x),
+        ("LD", [Register(x), R]) => Opcode::LdRFrom(*x),
+        ("ADD", [Register(x), Register(y)]) => Opcode::AddReg { x: *x, y: *y },
+        ("ADD", [Register(x), Value(kk)]) => Opcode::Add {
+            x: *x,
+            byte: byte(kk)?,
+        },
+        ("ADD", [I, Register(x)]) => Opcode::AddI(*x),
+        ("OR", [Register(x), Register(y)]) => Opcode::Or { x: *x, y: *y },
+        ("AND", [Register(x), Register(y)]) => Opcode::And { x: *x, y: *y },
+        ("XOR", [Register(x), Register(y)]) => Opcode::Xor { x: *x, y: *y },
+        ("SUB", [Register(x), Register(y)]) => Opcode::Sub { x: *x, y: *y },
+        // without VY the shift works on VX whatever the quirks
+        ("SHR", [Register(x)]) => Opcode::Shr { x: *x, y: *x },
+        ("SHR", [Register(x), Register(y)]) => Opcode::Shr { x: *x, y: *y },
+        ("SUBN", [Register(x), Register(y)]) => Opcode::Subn { x: *x, y: *y },
+        ("SHL", [Register(x)]) => Opcode::Shl { x: *x, y: *x },
+        ("SHL", [Register(x), Register(y)]) => Opcode::Shl { x: *x, y: *y },
+        ("RND", [Register(x), Value(kk)]) => Opcode::Rnd {
+            x: *x,
+            byte: byte(kk)?,
+        },
+        ("DRW", [Register(x), Register(y), Value(n)]) => Opcode::Draw {
+            x: *x,
+            y: *y,
+            n: nibble(n)?,
+        },
+        ("SKP", [Register(x)]) => Opcode::Skp(*x),
+        ("SKNP", [Register(x)]) => Opcode::Sknp(*x),
+        ("PLANE", [Value(n)]) => Opcode::Plane(nibble(n)?),
+        ("PITCH", [Register(x)]) => Opcode::LdPitch(*x),
+        _ if is_mnemonic(mnemonic) => {
+            let column = operands.first().map_or(column, |operand| operand.column);
+            return Err((column, ErrorKind::InvalidOperands(mnemonic.to_string())));
+        }
+        _ => return Err((column, ErrorKind::UnknownMnemonic(mnemonic.to_string()))),
+    };
+
+    Ok(word(opcode)?.to_be_bytes().to_vec())
+}
+
+fn is_mnemonic(mnemonic: &str) -> bool {
+    matches!(
+        mnemonic,
+        "CLS"
+            | "RET"
+            | "SCR"
+            | "SCL"
+            | "EXIT"
+            | "LOW"
+            | "HIGH"
+            | "AUDIO"
+            | "SCD"
+            | "SCU"
+            | "JP"
+            | "CALL"
+            | "SE"
+            | "SNE"
+            | "SAVE"
+            | "LOAD"
+            | "LD"
+            | "ADD"
+            | "OR"
+            | "AND"
+            | "XOR"
+            | "SUB"
+            | "SHR"
+            | "SUBN"
+            | "SHL"
+            | "RND"
+            | "DRW"
+            | "SKP"
+            | "SKNP"
+            | "PLANE"
+            | "PITCH"
+    )
+}
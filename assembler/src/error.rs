@@ -0,0 +1,39 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ErrorKind {
+    #[error("Unknown mnemonic `{0}`")]
+    UnknownMnemonic(String),
+    #[error("Invalid operands for `{0}`")]
+    InvalidOperands(String),
+    #[error("Expected an expression")]
+    MissingExpression,
+    #[error("Invalid number `{0}`")]
+    InvalidNumber(String),
+    #[error("Invalid symbol name `{0}`")]
+    InvalidSymbol(String),
+    #[error("Undefined symbol `{0}`")]
+    UndefinedSymbol(String),
+    #[error("Symbol `{0}` already defined")]
+    DuplicateSymbol(String),
+    #[error("Value {value} out of range {min}..={max}")]
+    OutOfRange { value: i64, min: i64, max: i64 },
+    #[error("Expected a quoted file name")]
+    InvalidInclude,
+    #[error("Cannot read `{0}`: {1}")]
+    Io(String, std::io::Error),
+    #[error("`{0}` includes itself")]
+    IncludeCycle(String),
+    #[error("Rom of {0} bytes does not fit in memory")]
+    RomTooLarge(usize),
+}
+
+/// Error in a source file, lines and columns start at 1.
+#[derive(Debug, Error)]
+#[error("{file}:{line}:{column}: {kind}")]
+pub struct AssemblerError {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub kind: ErrorKind,
+}
@@ -0,0 +1,233 @@
+mod encoder;
+mod error;
+mod parser;
+
+pub use error::{AssemblerError, ErrorKind};
+
+use chip8_core::emulator::memory;
+use chip8_core::source_map::{SourceLocation, SourceMap};
+use parser::{Line, Statement};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const START_ADDRESS: u16 = 0x200;
+
+/// Rom assembled from a source file, with the addresses of its labels
+/// and the source location of each instruction.
+#[derive(Debug)]
+pub struct Assembly {
+    rom: Vec<u8>,
+    symbols: BTreeMap<String, i64>,
+    source_map: SourceMap,
+}
+
+impl Assembly {
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    /// Labels and constants by name.
+    pub fn symbols(&self) -> &BTreeMap<String, i64> {
+        &self.symbols
+    }
+
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+}
+
+// a parsed line and where it comes from
+struct SourceLine {
+    file: String,
+    number: usize,
+    line: Line,
+}
+
+/// Assembles the file at `path`, included files are read relative to the
+/// file including them.
+pub fn assemble_file<P: AsRef<Path>>(path: P) -> Result<Assembly, AssemblerError> {
+    let path = path.as_ref();
+    let file = path.to_string_lossy().to_string();
+    let source = fs::read_to_string(path).map_err(|error| AssemblerError {
+        file: file.clone(),
+        line: 0,
+        column: 0,
+        kind: ErrorKind::Io(file.clone(), error),
+    })?;
+
+    assemble(&source, &file)
+}
+
+/// Assembles `source`, `file` names it in errors and in the source map.
+pub fn assemble(source: &str, file: &str) -> Result<Assembly, AssemblerError> {
+    let mut lines = vec![];
+    let mut included = vec![canonical(Path::new(file))];
+    read_lines(source, file, &mut included, &mut lines)?;
+
+    let symbols = define_symbols(&lines)?;
+
+    let mut rom = vec![];
+    let mut source_map = SourceMap::new();
+    for source_line in &lines {
+        let error = |(column, kind)| AssemblerError {
+            file: source_line.file.clone(),
+            line: source_line.number,
+            column,
+            kind,
+        };
+
+        match &source_line.line.statement {
+            Some(Statement::Instruction {
+                mnemonic,
+                column,
+                operands,
+            }) => {
+                let address = START_ADDRESS as usize + rom.len();
+                let location = SourceLocation::new(&source_line.file, source_line.number, *column);
+                source_map.insert(address as u16, location);
+
+                let bytes =
+                    encoder::encode(mnemonic, *column, operands, &symbols).map_err(error)?;
+                rom.extend(bytes);
+            }
+            Some(Statement::Data { size, values }) => {
+                for value in values {
+                    let (min, max) = if *size == 1 {
+                        (-0x80, 0xff)
+                    } else {
+                        (-0x8000, 0xffff)
+                    };
+                    let number = parser::evaluate(value, &symbols).map_err(error)?;
+                    if number < min || number > max {
+                        let kind = ErrorKind::OutOfRange {
+                            value: number,
+                            min,
+                            max,
+                        };
+                        return Err(error((value.column, kind)));
+                    }
+
+                    let bytes = (number as u16).to_be_bytes();
+                    rom.extend(&bytes[2 - size..]);
+                }
+            }
+            _ => {}
+        }
+
+        if rom.len() > memory::SIZE - START_ADDRESS as usize {
+            return Err(error((1, ErrorKind::RomTooLarge(rom.len()))));
+        }
+    }
+
+    Ok(Assembly {
+        rom,
+        symbols: symbols.into_iter().collect(),
+        source_map,
+    })
+}
+
+// parses `source` into `lines`, replacing includes with the lines of their files
+fn read_lines(
+    source: &str,
+    file: &str,
+    included: &mut Vec<PathBuf>,
+    lines: &mut Vec<SourceLine>,
+) -> Result<(), AssemblerError> {
+    for (index, text) in source.lines().enumerate() {
+        let error = |(column, kind)| AssemblerError {
+            file: file.to_string(),
+            line: index + 1,
+            column,
+            kind,
+        };
+
+        let line = parser::parse_line(text).map_err(error)?;
+        if let Some(Statement::Include { path }) = &line.statement {
+            let column = text.find('"').unwrap_or_default() + 1;
+            let path = Path::new(file)
+                .parent()
+                .unwrap_or_else(|| Path::new(""))
+                .join(path);
+            let include = path.to_string_lossy().to_string();
+
+            let canonical_path = canonical(&path);
+            if included.contains(&canonical_path) {
+                return Err(error((column, ErrorKind::IncludeCycle(include))));
+            }
+
+            let source = fs::read_to_string(&path)
+                .map_err(|io_error| error((column, ErrorKind::Io(include.clone(), io_error))))?;
+
+            // a label before the include names its first line
+            lines.push(SourceLine {
+                file: file.to_string(),
+                number: index + 1,
+                line: Line {
+                    label: line.label,
+                    statement: None,
+                },
+            });
+
+            included.push(canonical_path);
+            read_lines(&source, &include, included, lines)?;
+            included.pop();
+        } else {
+            lines.push(SourceLine {
+                file: file.to_string(),
+                number: index + 1,
+                line,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+// addresses of the labels and values of the constants, which can only use
+// the symbols defined before them
+fn define_symbols(lines: &[SourceLine]) -> Result<HashMap<String, i64>, AssemblerError> {
+    let mut symbols = HashMap::new();
+    let mut address = START_ADDRESS as i64;
+
+    for source_line in lines {
+        let error = |(column, kind)| AssemblerError {
+            file: source_line.file.clone(),
+            line: source_line.number,
+            column,
+            kind,
+        };
+        let define = |symbols: &mut HashMap<String, i64>, name: &str, column, value| {
+            if symbols.insert(name.to_string(), value).is_some() {
+                return Err(error((
+                    column,
+                    ErrorKind::DuplicateSymbol(name.to_string()),
+                )));
+            }
+            Ok(())
+        };
+
+        if let Some((name, column)) = &source_line.line.label {
+            define(&mut symbols, name, *column, address)?;
+        }
+
+        match &source_line.line.statement {
+            Some(Statement::Instruction {
+                mnemonic, operands, ..
+            }) => address += encoder::size(mnemonic, operands) as i64,
+            Some(Statement::Data { size, values }) => address += (size * values.len()) as i64,
+            Some(Statement::Constant { name, value }) => {
+                let number = parser::evaluate(value, &symbols).map_err(error)?;
+                define(&mut symbols, name, value.column, number)?;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(symbols)
+}
+
+// path used to recognize a file included twice
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
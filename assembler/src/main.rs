@@ -0,0 +1,73 @@
+use assembler::assemble_file;
+use chip8_core::source_map::{SourceLocation, SourceMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct Opt {
+    /// Source file to assemble
+    file: PathBuf,
+    /// Rom to write, defaults to the source file with the .ch8 extension
+    #[structopt(short, long)]
+    output: Option<PathBuf>,
+    /// Writes the address of every label and constant to this file
+    #[structopt(long)]
+    symbols: Option<PathBuf>,
+    /// Writes the source location of every instruction to this file
+    #[structopt(long)]
+    source_map: Option<PathBuf>,
+}
+
+fn main() {
+    let opt: Opt = Opt::from_args();
+
+    if let Err(error) = run(&opt) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
+
+fn run(opt: &Opt) -> Result<(), Box<dyn std::error::Error>> {
+    let assembly = assemble_file(&opt.file)?;
+
+    let output = opt
+        .output
+        .clone()
+        .unwrap_or_else(|| opt.file.with_extension("ch8"));
+    fs::write(output, assembly.rom())?;
+
+    if let Some(path) = &opt.symbols {
+        let symbols: String = assembly
+            .symbols()
+            .iter()
+            .map(|(name, value)| format!("{} = 0x{:04X}\n", name, value))
+            .collect();
+        fs::write(path, symbols)?;
+    }
+
+    if let Some(path) = &opt.source_map {
+        // the debugger looks for the sources relative to the source map
+        let directory = fs::canonicalize(path.parent().unwrap_or_else(|| Path::new("")).join("."))?;
+        let mut source_map = SourceMap::new();
+        for (address, location) in assembly.source_map().iter() {
+            let file = relative_path(Path::new(location.file()), &directory);
+            let location = SourceLocation::new(&file, location.line(), location.column());
+            source_map.insert(address, location);
+        }
+
+        fs::write(path, source_map.encode())?;
+    }
+
+    Ok(())
+}
+
+// `path` relative to `directory` when it is inside it, absolute otherwise
+fn relative_path(path: &Path, directory: &Path) -> String {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+    path.strip_prefix(directory)
+        .unwrap_or(&path)
+        .to_string_lossy()
+        .to_string()
+}
@@ -0,0 +1,277 @@
+use crate::error::ErrorKind;
+use std::collections::HashMap;
+
+// names which can not be used for labels and constants
+const RESERVED: [&str; 9] = ["I", "DT", "ST", "K", "F", "HF", "B", "R", "LONG"];
+
+/// Text of an operand and its column in the line.
+#[derive(Debug, Clone)]
+pub struct Operand {
+    pub text: String,
+    pub column: usize,
+}
+
+#[derive(Debug)]
+pub enum Statement {
+    Instruction {
+        mnemonic: String,
+        column: usize,
+        operands: Vec<Operand>,
+    },
+    /// DB and DW, values of `size` bytes.
+    Data {
+        size: usize,
+        values: Vec<Operand>,
+    },
+    /// NAME EQU value
+    Constant {
+        name: String,
+        value: Operand,
+    },
+    Include {
+        path: String,
+    },
+}
+
+#[derive(Debug, Default)]
+pub struct Line {
+    pub label: Option<(String, usize)>,
+    pub statement: Option<Statement>,
+}
+
+// error with the column it was found at
+pub type ParseError = (usize, ErrorKind);
+
+/// Splits a line of source into its label and statement.
+pub fn parse_line(text: &str) -> Result<Line, ParseError> {
+    let code = strip_comment(text);
+    let mut line = Line::default();
+    let mut position = skip_whitespace(code, 0);
+
+    // label:
+    let label_end = word_end(code, position);
+    if code[label_end..].starts_with(':') {
+        let name = &code[position..label_end];
+        validate_symbol(name, position + 1)?;
+
+        line.label = Some((name.to_string(), position + 1));
+        position = skip_whitespace(code, label_end + 1);
+    }
+
+    if position >= code.len() {
+        return Ok(line);
+    }
+
+    let mnemonic_end = word_end(code, position);
+    let word = &code[position..mnemonic_end];
+    let column = position + 1;
+
+    // NAME EQU value
+    let next = skip_whitespace(code, mnemonic_end);
+    let next_word_end = word_end(code, next);
+    if code[next..next_word_end].eq_ignore_ascii_case("EQU") {
+        validate_symbol(word, column)?;
+
+        let mut operands = operands(code, next_word_end)?;
+        if operands.len() != 1 {
+            return Err((next + 1, ErrorKind::MissingExpression));
+        }
+
+        line.statement = Some(Statement::Constant {
+            name: word.to_string(),
+            value: operands.remove(0),
+        });
+        return Ok(line);
+    }
+
+    let mnemonic = word.to_ascii_uppercase();
+    line.statement = Some(match mnemonic.as_str() {
+        "DB" | "DW" => Statement::Data {
+            size: if mnemonic == "DB" { 1 } else { 2 },
+            values: operands(code, mnemonic_end)?,
+        },
+        "INCLUDE" => {
+            let start = skip_whitespace(code, mnemonic_end);
+            let path = code[start..].trim_end();
+
+            if path.len() < 2 || !path.starts_with('"') || !path.ends_with('"') {
+                return Err((start + 1, ErrorKind::InvalidInclude));
+            }
+
+            Statement::Include {
+                path: path[1..path.len() - 1].to_string(),
+            }
+        }
+        _ => Statement::Instruction {
+            mnemonic,
+            column,
+            operands: operands(code, mnemonic_end)?,
+        },
+    });
+
+    Ok(line)
+}
+
+/// Value of an expression: numbers and symbols added and subtracted.
+pub fn evaluate(operand: &Operand, symbols: &HashMap<String, i64>) -> Result<i64, ParseError> {
+    let text = &operand.text;
+    let mut value: i64 = 0;
+    let mut sign = 1;
+    let mut position = 0;
+    let mut expects_term = true;
+
+    while position < text.len() {
+        let c = text[position..].chars().next().unwrap_or_default();
+        let column = operand.column + position;
+
+        if c.is_whitespace() {
+            position += c.len_utf8();
+        } else if expects_term && (c == '-' || c == '+') {
+            // unary sign
+            if c == '-' {
+                sign = -sign;
+            }
+            position += 1;
+        } else if expects_term {
+            let end = text[position..]
+                .find(|c: char| c == '+' || c == '-' || c.is_whitespace())
+                .map_or(text.len(), |end| position + end);
+            let term = &text[position..end];
+
+            let term_value = term_value(term, sign < 0, column, symbols)?;
+            value = value
+                .checked_add(term_value)
+                .ok_or_else(|| (operand.column, ErrorKind::InvalidNumber(text.to_string())))?;
+            sign = 1;
+            expects_term = false;
+            position = end;
+        } else if c == '+' || c == '-' {
+            sign = if c == '-' { -1 } else { 1 };
+            expects_term = true;
+            position += 1;
+        } else {
+            return Err((
+                column,
+                ErrorKind::InvalidNumber(text[position..].to_string()),
+            ));
+        }
+    }
+
+    if expects_term {
+        return Err((operand.column + text.len(), ErrorKind::MissingExpression));
+    }
+
+    Ok(value)
+}
+
+// `negative` terms are parsed with their sign, so that -2^63 fits
+fn term_value(
+    term: &str,
+    negative: bool,
+    column: usize,
+    symbols: &HashMap<String, i64>,
+) -> Result<i64, ParseError> {
+    let invalid = || (column, ErrorKind::InvalidNumber(term.to_string()));
+
+    let (digits, radix) = if let Some(hex) = term
+        .strip_prefix("0x")
+        .or_else(|| term.strip_prefix("0X"))
+        .or_else(|| term.strip_prefix('#'))
+        .or_else(|| term.strip_prefix('$'))
+    {
+        (hex, 16)
+    } else if let Some(binary) = term
+        .strip_prefix("0b")
+        .or_else(|| term.strip_prefix("0B"))
+        .or_else(|| term.strip_prefix('%'))
+    {
+        (binary, 2)
+    } else if term.starts_with(|c: char| c.is_ascii_digit()) {
+        (term, 10)
+    } else {
+        validate_symbol(term, column)?;
+        let value = symbols
+            .get(term)
+            .cloned()
+            .ok_or_else(|| (column, ErrorKind::UndefinedSymbol(term.to_string())))?;
+        return match negative {
+            true => value.checked_neg().ok_or_else(invalid),
+            false => Ok(value),
+        };
+    };
+
+    let sign = if negative { "-" } else { "" };
+    i64::from_str_radix(&format!("{}{}", sign, digits.replace('_', "")), radix)
+        .map_err(|_| invalid())
+}
+
+fn validate_symbol(name: &str, column: usize) -> Result<(), ParseError> {
+    let is_register = name.len() == 2
+        && (name.starts_with('V') || name.starts_with('v'))
+        && name[1..].chars().all(|c| c.is_ascii_hexdigit());
+    let is_reserved = is_register
+        || RESERVED
+            .iter()
+            .any(|reserved| reserved.eq_ignore_ascii_case(name));
+    let is_identifier = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if is_reserved || !is_identifier {
+        return Err((column, ErrorKind::InvalidSymbol(name.to_string())));
+    }
+
+    Ok(())
+}
+
+// operands separated by commas, from `start` to the end of the line
+fn operands(code: &str, start: usize) -> Result<Vec<Operand>, ParseError> {
+    let mut operands = vec![];
+    if code[start..].trim().is_empty() {
+        return Ok(operands);
+    }
+
+    let mut position = start;
+    for part in code[start..].split(',') {
+        let leading = part.len() - part.trim_start().len();
+        let text = part.trim();
+        let column = position + leading + 1;
+
+        if text.is_empty() {
+            return Err((column, ErrorKind::MissingExpression));
+        }
+
+        operands.push(Operand {
+            text: text.to_string(),
+            column,
+        });
+        position += part.len() + 1;
+    }
+
+    Ok(operands)
+}
+
+// the line up to its `;` comment, quoted file names may contain semicolons
+fn strip_comment(text: &str) -> &str {
+    let mut is_quoted = false;
+    for (index, c) in text.char_indices() {
+        match c {
+            '"' => is_quoted = !is_quoted,
+            ';' if !is_quoted => return &text[..index],
+            _ => {}
+        }
+    }
+
+    text
+}
+
+fn skip_whitespace(code: &str, position: usize) -> usize {
+    code[position..]
+        .find(|c: char| !c.is_whitespace())
+        .map_or(code.len(), |offset| position + offset)
+}
+
+fn word_end(code: &str, position: usize) -> usize {
+    code[position..]
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .map_or(code.len(), |offset| position + offset)
+}
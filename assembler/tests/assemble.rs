@@ -0,0 +1,275 @@
+//! Sources assembled into roms: symbols, data, includes and the position of
+//! the errors.
+
+use assembler::{assemble, assemble_file, AssemblerError, ErrorKind};
+use chip8_core::disassembler::Disassembler;
+use chip8_core::emulator::platform::Platform;
+use std::fs;
+use std::path::PathBuf;
+
+fn rom(source: &str) -> Vec<u8> {
+    assemble(source, "test.asm")
+        .unwrap_or_else(|error| panic!("{}", error))
+        .rom()
+        .to_vec()
+}
+
+fn error(source: &str) -> AssemblerError {
+    assemble(source, "test.asm").unwrap_err()
+}
+
+// files written to a directory of their own, removed on drop
+struct Directory(PathBuf);
+
+impl Directory {
+    fn new(name: &str, files: &[(&str, &str)]) -> Self {
+        let path = std::env::temp_dir().join(format!("chip8-asm-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&path).unwrap();
+        for (name, source) in files {
+            let file = path.join(name);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(file, source).unwrap();
+        }
+
+        Self(path)
+    }
+}
+
+impl Drop for Directory {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.0).ok();
+    }
+}
+
+#[test]
+fn labels_are_resolved_backward_and_forward() {
+    let source = "\
+start:
+    CALL sub        ; forward
+    JP start
+sub: LD V0, 1
+    RET
+";
+    let assembly = assemble(source, "test.asm").unwrap();
+
+    assert_eq!(
+        assembly.rom(),
+        [0x22, 0x04, 0x12, 0x00, 0x60, 0x01, 0x00, 0xEE]
+    );
+    assert_eq!(assembly.symbols()["start"], 0x200);
+    assert_eq!(assembly.symbols()["sub"], 0x204);
+}
+
+#[test]
+fn long_loads_move_the_following_labels() {
+    let source = "\
+    LD I, LONG data
+    JP data
+data: DB 1
+";
+
+    assert_eq!(rom(source), [0xF0, 0x00, 0x02, 0x06, 0x12, 0x06, 0x01]);
+}
+
+#[test]
+fn constants_are_evaluated_in_order() {
+    let source = "\
+SPEED EQU 3
+FAST equ SPEED + 2 - -1
+    LD V0, FAST
+    ADD V1, SPEED - 4
+";
+
+    assert_eq!(rom(source), [0x60, 0x06, 0x71, 0xFF]);
+
+    let error = error("X EQU Y\nY EQU 1\n");
+    assert!(matches!(error.kind, ErrorKind::UndefinedSymbol(ref name) if name == "Y"));
+    assert_eq!((error.line, error.column), (1, 7));
+}
+
+#[test]
+fn numbers_in_every_base() {
+    assert_eq!(
+        rom("DB 10, 0x10, #10, $10, 0b10, %1000_0001, -1"),
+        [10, 0x10, 0x10, 0x10, 0b10, 0x81, 0xFF]
+    );
+}
+
+#[test]
+fn data_is_stored_big_endian() {
+    let source = "\
+    LD I, sprite
+sprite:
+    DB 0xF0, 0x90
+    DW 0x1234, sprite, -2
+";
+
+    assert_eq!(
+        rom(source),
+        [0xA2, 0x02, 0xF0, 0x90, 0x12, 0x34, 0x02, 0x02, 0xFF, 0xFE]
+    );
+
+    let error = error("    DB 1, 256");
+    assert!(matches!(
+        error.kind,
+        ErrorKind::OutOfRange { value: 256, .. }
+    ));
+    assert_eq!((error.line, error.column), (1, 11));
+}
+
+#[test]
+fn expressions_out_of_64_bits_are_rejected() {
+    assert_eq!(
+        rom("MIN EQU -9223372036854775808\n    LD V0, MIN + 9223372036854775807 + 1"),
+        [0x60, 0x00]
+    );
+
+    let cases: &[(&str, usize)] = &[
+        ("    LD V0, 9223372036854775807 + 1", 12),
+        ("    LD V0, -9223372036854775808 - 1", 12),
+        ("    LD V0, --9223372036854775808", 14),
+        ("MIN EQU -9223372036854775808\n    LD V0, -MIN", 13),
+    ];
+    for (source, column) in cases {
+        let error = error(source);
+        assert!(
+            matches!(error.kind, ErrorKind::InvalidNumber(_)),
+            "{:?}: {}",
+            source,
+            error
+        );
+        assert_eq!(error.column, *column, "{:?}", source);
+    }
+}
+
+#[test]
+fn non_ascii_whitespace_separates_terms() {
+    assert_eq!(rom("    LD V0, 5\u{a0}+ 1"), [0x60, 0x06]);
+    assert_eq!(rom("X EQU 1\u{a0}+ 1\n    LD V0, X"), [0x60, 0x02]);
+}
+
+#[test]
+fn scrolls_by_zero_rows_are_rejected() {
+    assert_eq!(
+        rom("SCD 1\nSCD 15\nSCU 1\nSCU 15"),
+        [0x00, 0xC1, 0x00, 0xCF, 0x00, 0xD1, 0x00, 0xDF]
+    );
+
+    for source in &["    SCD 0", "    SCU 0"] {
+        let error = error(source);
+        assert!(matches!(
+            error.kind,
+            ErrorKind::OutOfRange {
+                value: 0,
+                min: 1,
+                max: 15
+            }
+        ));
+        assert_eq!((error.line, error.column), (1, 9));
+    }
+}
+
+#[test]
+fn errors_report_their_line_and_column() {
+    let cases: &[(&str, usize, usize)] = &[
+        ("CLS\n    FOO V0", 2, 5),
+        ("CLS\nCLS\n    LD V0, 0x100", 3, 12),
+        ("    JP nowhere", 1, 8),
+        ("a: CLS\na: CLS", 2, 1),
+        ("    LD V0,", 1, 11),
+        ("    LD V0, 1 +", 1, 15),
+        ("V1: CLS", 1, 1),
+        ("    INCLUDE missing.asm", 1, 13),
+    ];
+
+    for (source, line, column) in cases {
+        let error = error(source);
+        assert_eq!(
+            (error.file.as_str(), error.line, error.column),
+            ("test.asm", *line, *column),
+            "{:?}: {}",
+            source,
+            error
+        );
+    }
+
+    assert_eq!(
+        error("    LD V0, 0x100").to_string(),
+        "test.asm:1:12: Value 256 out of range -128..=255"
+    );
+}
+
+#[test]
+fn includes_are_inserted_in_place() {
+    let directory = Directory::new(
+        "include",
+        &[
+            (
+                "main.asm",
+                "    CALL draw\n    JP 0x200\nINCLUDE \"lib/draw.asm\"\n",
+            ),
+            (
+                "lib/draw.asm",
+                "draw: DRW V0, V1, 5\n    RET\n    INCLUDE \"bad.asm\"\n",
+            ),
+            ("lib/bad.asm", "    LD V0, V\n"),
+        ],
+    );
+
+    // the error is located in the included file, relative to the includer
+    let error = assemble_file(directory.0.join("main.asm")).unwrap_err();
+    assert!(error.file.ends_with("bad.asm"), "{}", error.file);
+    assert_eq!((error.line, error.column), (1, 12));
+
+    fs::write(directory.0.join("lib/bad.asm"), "    CLS\n").unwrap();
+    let assembly = assemble_file(directory.0.join("main.asm")).unwrap();
+    assert_eq!(
+        assembly.rom(),
+        [0x22, 0x04, 0x12, 0x00, 0xD0, 0x15, 0x00, 0xEE, 0x00, 0xE0]
+    );
+    let location = assembly.source_map().location(0x204).unwrap();
+    assert!(location.file().ends_with("draw.asm"));
+    assert_eq!((location.line(), location.column()), (1, 7));
+}
+
+#[test]
+fn include_cycles_are_detected() {
+    let directory = Directory::new(
+        "include_cycle",
+        &[
+            ("a.asm", "    CLS\n    INCLUDE \"b.asm\"\n"),
+            ("b.asm", "    RET\n  INCLUDE \"a.asm\"\n"),
+            ("self.asm", "INCLUDE \"self.asm\"\n"),
+        ],
+    );
+
+    let error = assemble_file(directory.0.join("a.asm")).unwrap_err();
+    assert!(matches!(error.kind, ErrorKind::IncludeCycle(_)));
+    assert!(error.file.ends_with("b.asm"));
+    assert_eq!((error.line, error.column), (2, 11));
+
+    let error = assemble_file(directory.0.join("self.asm")).unwrap_err();
+    assert!(matches!(error.kind, ErrorKind::IncludeCycle(_)));
+}
+
+#[test]
+fn assembled_source_disassembles_to_the_same_listing() {
+    let source = "    HIGH
+    LD I, LONG sprite
+    LD V0, [I]
+    SCD 4
+    DRW V0, V1, 0
+    SAVE V1, V4
+    LD R, V7
+    JP V0, 0x300
+sprite:
+    DB 0xFF
+";
+    let rom = rom(source);
+
+    let listing = Disassembler::new(Platform::XoChip)
+        .disassemble(&rom)
+        .to_string();
+    assert_eq!(listing, source.replace("sprite", "data_212"));
+    assert_eq!(assemble(&listing, "listing.asm").unwrap().rom(), rom);
+}
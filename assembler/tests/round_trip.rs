@@ -0,0 +1,61 @@
+//! Disassembling a rom and assembling the listing gives back the same bytes,
+//! whatever part of it is taken for code or for data.
+
+use chip8_core::disassembler::Disassembler;
+use chip8_core::emulator::platform::Platform;
+use std::fs;
+use std::path::Path;
+
+const PLATFORMS: &[Platform] = &[Platform::Chip8, Platform::SuperChip, Platform::XoChip];
+
+fn assert_round_trip(rom: &[u8], name: &str) {
+    for platform in PLATFORMS {
+        for show_addresses in &[false, true] {
+            let listing = Disassembler::new(*platform)
+                .show_addresses(*show_addresses)
+                .disassemble(rom)
+                .to_string();
+
+            let assembly = assembler::assemble(&listing, name)
+                .unwrap_or_else(|error| panic!("{} on {:?}: {}", name, platform, error));
+            assert_eq!(assembly.rom(), rom, "{} on {:?}", name, platform);
+        }
+    }
+}
+
+#[test]
+fn bundled_roms_round_trip() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+    let mut roms = 0;
+
+    for entry in fs::read_dir(root).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|extension| extension == "ch8") {
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            assert_round_trip(&fs::read(&path).unwrap(), &name);
+            roms += 1;
+        }
+    }
+
+    assert!(roms >= 6);
+}
+
+#[test]
+fn data_interleaved_with_code_round_trips() {
+    let rom = [
+        0x12, 0x08, // 200: JP 208, over data looking like code
+        0x60, 0x01, 0x00, 0xEE, // 202: data
+        0xFF, // 206: data, an odd byte
+        0x00, // 207: data
+        0xA2, 0x0F, // 208: LD I, 20F, in the middle of the next instruction
+        0x22, 0x12, // 20A: CALL 212
+        0x3A, 0x00, // 20C: SE VA, 0
+        0xF0, 0x00, // 20E: XO-CHIP long load, data on the other platforms
+        0x02, 0x05, // 210: address 205, in the middle of the data above
+        0xD0, 0x15, // 212: DRW V0, V1, 5
+        0x00, 0xEE, // 214: RET
+        0xF0, 0x90, 0x90, // 216: trailing sprite
+    ];
+
+    assert_round_trip(&rom, "interleaved.ch8");
+}
@@ -0,0 +1,93 @@
+//! Compares the throughput of the interpreter decoding every instruction it
+//! executes with the one running them from the decode cache, and measures
+//! drawing in high resolution.
+//!
+//! Run with `cargo bench -p core --bench interpreter`.
+
+use core::emulator::chip8::Chip8;
+use core::emulator::platform::Platform;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use std::fs;
+use std::path::Path;
+
+const FRAMES: u64 = 60;
+// far above the usual speed, so that the frames are spent interpreting
+const INSTRUCTIONS_PER_FRAME: usize = 1000;
+
+// without the display wait of the COSMAC VIP, which leaves the cpu idle
+// after every draw
+const PLATFORM: Platform = Platform::XoChip;
+
+const ROMS: &[(&str, &str)] = &[
+    ("test_opcode", "test_opcode.ch8"),
+    ("brix", "Brix [Andreas Gustafsson, 1990].ch8"),
+    ("tetris", "Tetris [Fran Dachille, 1991].ch8"),
+];
+
+fn build(rom: &[u8], decode_cache: bool) -> Chip8 {
+    Chip8::builder()
+        .rom(rom)
+        .platform(PLATFORM)
+        .instructions_per_frame(INSTRUCTIONS_PER_FRAME)
+        .decode_cache(decode_cache)
+        .build()
+        .unwrap()
+}
+
+fn run(mut chip8: Chip8) -> Chip8 {
+    for _ in 0..FRAMES {
+        chip8.run_frame().unwrap();
+    }
+    chip8
+}
+
+fn interpreter(c: &mut Criterion) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+    let mut group = c.benchmark_group("interpreter");
+    group.throughput(Throughput::Elements(FRAMES * INSTRUCTIONS_PER_FRAME as u64));
+
+    for (name, file) in ROMS {
+        let rom = fs::read(root.join(file)).unwrap();
+        for decode_cache in &[false, true] {
+            let id = if *decode_cache { "cache" } else { "decode" };
+            group.bench_with_input(BenchmarkId::new(id, name), &rom, |b, rom| {
+                b.iter_batched(|| build(rom, *decode_cache), run, BatchSize::LargeInput)
+            });
+        }
+    }
+
+    group.finish();
+}
+
+// draws 16x16 sprites on both planes in high resolution, moving them across
+// the edges of the screen
+#[rustfmt::skip]
+const DRAW_ROM: &[u8] = &[
+    0x00, 0xff, // 200: HIGH
+    0xf3, 0x01, // 202: PLANE 3
+    0xa2, 0x20, // 204: LD I, 0x220
+    0xd0, 0x10, // 206: DRW V0, V1, 0
+    0x70, 0x07, // 208: ADD V0, 0x07
+    0x71, 0x03, // 20A: ADD V1, 0x03
+    0x12, 0x06, // 20C: JP 0x206
+];
+
+fn draw(c: &mut Criterion) {
+    // the sprites of both planes, whatever the bytes
+    let mut rom = DRAW_ROM.to_vec();
+    rom.resize(0x20, 0);
+    rom.extend((0..64u8).map(|byte| byte.wrapping_mul(37)));
+
+    let mut group = c.benchmark_group("display");
+    // a draw every 4 instructions
+    group.throughput(Throughput::Elements(
+        FRAMES * INSTRUCTIONS_PER_FRAME as u64 / 4,
+    ));
+    group.bench_function("draw_hires", |b| {
+        b.iter_batched(|| build(&rom, true), run, BatchSize::LargeInput)
+    });
+    group.finish();
+}
+
+criterion_group!(benches, interpreter, draw);
+criterion_main!(benches);
@@ -0,0 +1,54 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use chip8::emulator::chip8::Chip8;
+use chip8::emulator::keyboard::KeyboardState;
+use chip8::emulator::platform::Platform;
+use libfuzzer_sys::fuzz_target;
+
+// 10 seconds of emulation at a speed well above the usual one, about 100k
+// instructions for every input
+const FRAMES: usize = 600;
+const INSTRUCTIONS_PER_FRAME: usize = 160;
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    platform: u8,
+    seed: u64,
+    decode_cache: bool,
+    // keys held down, a bitmask for every frame played in loop
+    keys: Vec<u16>,
+    rom: Vec<u8>,
+}
+
+// Any rom and any key input can stop the machine with an error, never panic.
+fuzz_target!(|input: Input| {
+    let platform = match input.platform % 3 {
+        0 => Platform::Chip8,
+        1 => Platform::SuperChip,
+        _ => Platform::XoChip,
+    };
+
+    let mut chip8 = match Chip8::builder()
+        .rom(&input.rom)
+        .platform(platform)
+        .seed(input.seed)
+        .instructions_per_frame(INSTRUCTIONS_PER_FRAME)
+        .decode_cache(input.decode_cache)
+        .build()
+    {
+        Ok(chip8) => chip8,
+        Err(_) => return,
+    };
+
+    for frame in 0..FRAMES {
+        if !input.keys.is_empty() {
+            let mask = input.keys[frame % input.keys.len()];
+            chip8.set_keyboard_state(KeyboardState::from_mask(mask));
+        }
+
+        if chip8.run_frame().is_err() || chip8.is_exited() {
+            break;
+        }
+    }
+});
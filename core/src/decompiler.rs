@@ -0,0 +1,622 @@
+use crate::disassembler::{Disassembler, Disassembly, Instruction, DATA_PER_LINE, ROM_START};
+use crate::emulator::platform::Platform;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+// the Octo compiler starts the program at main, placing it first avoids the
+// jump to it
+const MAIN: &str = "main";
+// indentation of every block level
+const INDENT: &str = "  ";
+
+/// Block recovered from the skips and jumps of the program.
+#[derive(Debug, Clone, Copy)]
+enum Structure {
+    /// `loop` at `start`, `again` is the backward jump at `again`.
+    Loop { start: u16, again: u16 },
+    /// `if ... begin` at the skip at `start`, followed by a jump to `end`.
+    If { start: u16, end: u16 },
+    /// `if ... begin ... else ... end`, the jump at `else_jump` skips the
+    /// else block ending at `end`.
+    IfElse {
+        start: u16,
+        else_jump: u16,
+        end: u16,
+    },
+}
+
+impl Structure {
+    // addresses from the first instruction to the end of the block
+    fn extent(&self) -> (u32, u32) {
+        match *self {
+            Structure::Loop { start, again } => (start as u32, again as u32 + 2),
+            Structure::If { start, end } | Structure::IfElse { start, end, .. } => {
+                (start as u32, end as u32)
+            }
+        }
+    }
+
+    // ranges which can contain other blocks
+    fn bodies(&self) -> Vec<(u32, u32)> {
+        match *self {
+            Structure::Loop { start, again } => vec![(start as u32, again as u32)],
+            Structure::If { start, end } => vec![(start as u32 + 4, end as u32)],
+            Structure::IfElse {
+                start,
+                else_jump,
+                end,
+            } => vec![
+                (start as u32 + 4, else_jump as u32),
+                (else_jump as u32 + 2, end as u32),
+            ],
+        }
+    }
+
+    // jumps and skips written as keywords instead of instructions
+    fn consumed(&self) -> Vec<u16> {
+        match *self {
+            Structure::Loop { again, .. } => vec![again],
+            Structure::If { start, .. } => vec![start, start + 2],
+            Structure::IfElse {
+                start, else_jump, ..
+            } => vec![start, start + 2, else_jump],
+        }
+    }
+
+    fn is_inside(&self, other: &Structure) -> bool {
+        let (start, end) = self.extent();
+        other
+            .bodies()
+            .iter()
+            .any(|(body_start, body_end)| *body_start <= start && end <= *body_end)
+    }
+
+    fn is_compatible(&self, other: &Structure) -> bool {
+        let (start, end) = self.extent();
+        let (other_start, other_end) = other.extent();
+
+        end <= other_start || start >= other_end || self.is_inside(other) || other.is_inside(self)
+    }
+}
+
+/// Turns roms into Octo source which compiles back to the same bytes.
+/// Loops and conditional blocks are recovered from the skips and jumps of
+/// the code found by the disassembler.
+pub struct Decompiler {
+    platform: Platform,
+}
+
+impl Decompiler {
+    /// Instructions of `platform` extensions are recognized, the other ones
+    /// are left as data.
+    pub fn new(platform: Platform) -> Self {
+        Self { platform }
+    }
+
+    pub fn decompile(&self, rom: &[u8]) -> Decompilation {
+        let disassembly = Disassembler::new(self.platform).disassemble(rom);
+
+        let mut decompilation = Decompilation {
+            structures: vec![],
+            whiles: BTreeSet::new(),
+            consumed: BTreeSet::new(),
+            sprites: BTreeMap::new(),
+            disassembly,
+        };
+        decompilation.find_structures();
+        decompilation.find_whiles();
+        decompilation.find_sprites();
+        decompilation
+    }
+}
+
+/// Octo source of a rom.
+pub struct Decompilation {
+    disassembly: Disassembly,
+    structures: Vec<Structure>,
+    // skips at the start of a `while`
+    whiles: BTreeSet<u16>,
+    // instructions written by a structure keyword
+    consumed: BTreeSet<u16>,
+    // sprite data addresses with their size in bytes
+    sprites: BTreeMap<u16, usize>,
+}
+
+impl Decompilation {
+    fn instruction(&self, address: u16) -> Option<&Instruction> {
+        self.disassembly.instructions.get(&address)
+    }
+
+    // jump target, if the instruction at `address` is a jump
+    fn jump_target(&self, address: u16) -> Option<u16> {
+        let instruction = self.instruction(address)?;
+        (instruction.opcode >> 12 == 0x1).then_some(instruction.opcode & 0x0fff)
+    }
+
+    fn is_skip(&self, address: u16) -> bool {
+        self.instruction(address)
+            .is_some_and(|instruction| is_skip(instruction.opcode))
+    }
+
+    // whether instructions follow each other from `start` to `end`
+    fn is_code(&self, start: u16, end: u16) -> bool {
+        let mut address = start;
+        while address < end {
+            match self.instruction(address) {
+                Some(instruction) => address = address.wrapping_add(instruction.size),
+                None => return false,
+            }
+        }
+
+        address == end
+    }
+
+    fn has_label(&self, address: u16) -> bool {
+        self.disassembly.label(address).is_some()
+    }
+
+    fn find_structures(&mut self) {
+        // blocks found, the alternatives of each one from the preferred
+        let mut candidates: Vec<Vec<Structure>> = vec![];
+
+        for (address, instruction) in &self.disassembly.instructions {
+            let address = *address;
+
+            // a backward jump closes a loop
+            if let Some(start) = self.jump_target(address) {
+                if start <= address && self.is_code(start, address) {
+                    candidates.push(vec![Structure::Loop {
+                        start,
+                        again: address,
+                    }]);
+                }
+            }
+
+            // a skip over a forward jump opens a conditional block, which
+            // must end within the addresses reached by jumps
+            if !is_skip(instruction.opcode) || address > 0xffb || self.has_label(address + 2) {
+                continue;
+            }
+            let end = match self.jump_target(address + 2) {
+                Some(end) if end >= address + 4 && self.is_code(address + 4, end) => end,
+                _ => continue,
+            };
+
+            let mut alternatives = vec![];
+            let else_jump = end.wrapping_sub(2);
+            if else_jump >= address + 4 {
+                if let Some(else_end) = self.jump_target(else_jump) {
+                    if else_end > end && self.is_code(end, else_end) {
+                        alternatives.push(Structure::IfElse {
+                            start: address,
+                            else_jump,
+                            end: else_end,
+                        });
+                    }
+                }
+            }
+            alternatives.push(Structure::If {
+                start: address,
+                end,
+            });
+            candidates.push(alternatives);
+        }
+
+        // outer blocks first
+        candidates.sort_by_key(|alternatives| {
+            let (start, end) = alternatives[0].extent();
+            (start, u32::MAX - end)
+        });
+
+        for alternatives in candidates {
+            let accepted = alternatives.into_iter().find(|candidate| {
+                candidate
+                    .consumed()
+                    .iter()
+                    .all(|address| !self.consumed.contains(address))
+                    && self
+                        .structures
+                        .iter()
+                        .all(|structure| candidate.is_compatible(structure))
+            });
+
+            if let Some(structure) = accepted {
+                self.consumed.extend(structure.consumed());
+                self.structures.push(structure);
+            }
+        }
+    }
+
+    // a skip over a jump past the end of the innermost loop is a `while`
+    fn find_whiles(&mut self) {
+        let skips: Vec<u16> = self
+            .disassembly
+            .instructions
+            .keys()
+            .copied()
+            .filter(|address| self.is_skip(*address))
+            .collect();
+
+        for address in skips {
+            if address > 0xffb {
+                continue;
+            }
+
+            let jump = address + 2;
+            if self.consumed.contains(&address)
+                || self.consumed.contains(&jump)
+                || self.has_label(jump)
+            {
+                continue;
+            }
+
+            let target = match self.jump_target(jump) {
+                Some(target) => target as u32,
+                None => continue,
+            };
+
+            // both instructions must sit in the same block
+            let statement = Structure::If {
+                start: address,
+                end: address + 4,
+            };
+            let innermost_loop = self
+                .structures
+                .iter()
+                .filter(|structure| matches!(structure, Structure::Loop { .. }))
+                .filter(|structure| statement.is_inside(structure))
+                .min_by_key(|structure| structure.extent().1 - structure.extent().0);
+            let is_compatible = self.structures.iter().all(|structure| {
+                let (start, end) = structure.extent();
+                end <= address as u32
+                    || start >= address as u32 + 4
+                    || statement.is_inside(structure)
+            });
+
+            if is_compatible && innermost_loop.is_some_and(|loop_| loop_.extent().1 == target) {
+                self.whiles.insert(address);
+                self.consumed.extend([address, jump]);
+            }
+        }
+    }
+
+    // data drawn after I is set to it, with the size of the tallest sprite
+    fn find_sprites(&mut self) {
+        let mut sprite = None;
+
+        for instruction in self.disassembly.instructions.values() {
+            let opcode = instruction.opcode;
+            match opcode >> 12 {
+                0xa => sprite = Some(opcode & 0x0fff),
+                0xf if instruction.size == 4 => sprite = Some(instruction.long),
+                0xd => {
+                    if let Some(address) = sprite {
+                        // 16x16 SCHIP sprites take 32 bytes
+                        let size = match opcode & 0xf {
+                            0 => 32,
+                            n => n as usize,
+                        };
+                        let sprite_size = self.sprites.entry(address).or_default();
+                        *sprite_size = (*sprite_size).max(size);
+                    }
+                }
+                // the flow leaves the straight code, or I changes
+                0x0..=0x2 | 0xb => sprite = None,
+                0xf if matches!(opcode & 0xff, 0x1e | 0x29 | 0x30) => sprite = None,
+                _ => {}
+            }
+        }
+    }
+
+    // addresses used by the instructions still written as such
+    fn referenced(&self) -> BTreeSet<u16> {
+        let mut referenced = BTreeSet::new();
+        for (address, instruction) in &self.disassembly.instructions {
+            if self.consumed.contains(address) {
+                continue;
+            }
+
+            match instruction.opcode >> 12 {
+                0x1 | 0x2 | 0xa | 0xb => referenced.insert(instruction.opcode & 0x0fff),
+                0xf if instruction.size == 4 => referenced.insert(instruction.long),
+                _ => false,
+            };
+        }
+
+        referenced
+    }
+
+    fn label(&self, address: u16) -> Option<String> {
+        if address == ROM_START {
+            return Some(MAIN.to_string());
+        }
+
+        self.disassembly.label(address)
+    }
+
+    // label of the address, or the address itself when it has none
+    fn operand(&self, address: u16) -> String {
+        self.label(address)
+            .unwrap_or_else(|| format!("{:#05X}", address).replace("0X", "0x"))
+    }
+
+    fn statement(&self, instruction: &Instruction) -> String {
+        let opcode = instruction.opcode;
+        let nnn = opcode & 0x0fff;
+        let x = (opcode >> 8) & 0xf;
+        let y = (opcode >> 4) & 0xf;
+        let n = opcode & 0xf;
+        let kk = opcode & 0xff;
+
+        match opcode >> 12 {
+            0x0 => match opcode {
+                0x00e0 => "clear".to_string(),
+                0x00ee => "return".to_string(),
+                0x00fb => "scroll-right".to_string(),
+                0x00fc => "scroll-left".to_string(),
+                0x00fd => "exit".to_string(),
+                0x00fe => "lores".to_string(),
+                0x00ff => "hires".to_string(),
+                _ if y == 0xc => format!("scroll-down {}", n),
+                _ => format!("scroll-up {}", n),
+            },
+            0x1 => format!("jump {}", self.operand(nnn)),
+            0x2 => match self.label(nnn) {
+                Some(label) => label,
+                None => format!(":call {}", self.operand(nnn)),
+            },
+            0x3..=0x5 | 0x9 | 0xe if is_skip(opcode) => {
+                format!("if {} then", condition(opcode, true))
+            }
+            0x5 => match n {
+                0x2 => format!("save v{:x} - v{:x}", x, y),
+                _ => format!("load v{:x} - v{:x}", x, y),
+            },
+            0x6 => format!("v{:x} := {}", x, kk),
+            0x7 => format!("v{:x} += {}", x, kk),
+            0x8 => {
+                let operator = match n {
+                    0x0 => ":=",
+                    0x1 => "|=",
+                    0x2 => "&=",
+                    0x3 => "^=",
+                    0x4 => "+=",
+                    0x5 => "-=",
+                    0x6 => ">>=",
+                    0x7 => "=-",
+                    _ => "<<=",
+                };
+                format!("v{:x} {} v{:x}", x, operator, y)
+            }
+            0xa => format!("i := {}", self.operand(nnn)),
+            0xb => format!("jump0 {}", self.operand(nnn)),
+            0xc => format!("v{:x} := random {}", x, kk),
+            0xd => format!("sprite v{:x} v{:x} {}", x, y, n),
+            _ => match kk {
+                0x00 => format!("i := long {}", self.operand(instruction.long)),
+                0x01 => format!("plane {}", x),
+                0x02 => "audio".to_string(),
+                0x07 => format!("v{:x} := delay", x),
+                0x0a => format!("v{:x} := key", x),
+                0x15 => format!("delay := v{:x}", x),
+                0x18 => format!("buzzer := v{:x}", x),
+                0x1e => format!("i += v{:x}", x),
+                0x29 => format!("i := hex v{:x}", x),
+                0x30 => format!("i := bighex v{:x}", x),
+                0x33 => format!("bcd v{:x}", x),
+                0x3a => format!("pitch := v{:x}", x),
+                0x55 => format!("save v{:x}", x),
+                0x65 => format!("load v{:x}", x),
+                0x75 => format!("saveflags v{:x}", x),
+                _ => format!("loadflags v{:x}", x),
+            },
+        }
+    }
+
+    // data bytes from `offset` up to the next label or instruction, sprites
+    // are written in binary, one row per line
+    fn data_lines(&self, offset: usize, sprite_end: Option<usize>) -> (usize, Vec<String>) {
+        let rom = &self.disassembly.rom;
+
+        let limit = match sprite_end {
+            Some(end) => end.min(rom.len()),
+            None => (offset + DATA_PER_LINE).min(rom.len()),
+        };
+        let mut end = offset + 1;
+        while end < limit {
+            let address = ROM_START.wrapping_add(end as u16);
+            if self.instruction(address).is_some() || self.disassembly.label(address).is_some() {
+                break;
+            }
+            end += 1;
+        }
+
+        let bytes = &rom[offset..end];
+        let lines = match sprite_end {
+            Some(_) => bytes.iter().map(|byte| format!("{:#010b}", byte)).collect(),
+            None => {
+                let bytes: Vec<String> = bytes
+                    .iter()
+                    .map(|byte| format!("{:#04X}", byte).replace("0X", "0x"))
+                    .collect();
+                vec![bytes.join(" ")]
+            }
+        };
+
+        (end, lines)
+    }
+}
+
+impl fmt::Display for Decompilation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rom = &self.disassembly.rom;
+        let referenced = self.referenced();
+        let mut depth = 0;
+        let line = |f: &mut fmt::Formatter, depth: usize, text: &str| {
+            writeln!(f, "{}{}", INDENT.repeat(depth + 1), text)
+        };
+
+        // closes the blocks ending at `address`, innermost first
+        let mut open_ends: Vec<u32> = vec![];
+        let close =
+            |f: &mut fmt::Formatter, open_ends: &mut Vec<u32>, depth: &mut usize, address: u32| {
+                while open_ends.last() == Some(&address) {
+                    open_ends.pop();
+                    *depth -= 1;
+                    line(f, *depth, "end")?;
+                }
+                Ok(())
+            };
+
+        writeln!(f, ": {}", MAIN)?;
+
+        let mut offset = 0;
+        let mut sprite_end = None;
+        // `if ... then` shares the line of the statement it skips
+        let mut then: Option<String> = None;
+        while offset < rom.len() {
+            let address = ROM_START.wrapping_add(offset as u16);
+
+            let is_statement = self.instruction(address).is_some()
+                && !self.consumed.contains(&address)
+                && !self.is_skip(address)
+                && !referenced.contains(&address)
+                && open_ends.last() != Some(&(address as u32))
+                && self
+                    .structures
+                    .iter()
+                    .all(|structure| structure.extent().0 != address as u32);
+            if !is_statement {
+                if let Some(text) = then.take() {
+                    line(f, depth, &text)?;
+                }
+            }
+
+            close(f, &mut open_ends, &mut depth, address as u32)?;
+
+            if address != ROM_START && referenced.contains(&address) {
+                if let Some(label) = self.label(address) {
+                    writeln!(f, ": {}", label)?;
+                }
+            }
+
+            let instruction = match self.instruction(address) {
+                Some(instruction) => instruction,
+                None => {
+                    if let Some(size) = self.sprites.get(&address) {
+                        sprite_end = Some(offset + size);
+                    }
+                    let is_sprite = sprite_end.is_some_and(|end| offset < end);
+
+                    let (end, lines) = self.data_lines(offset, sprite_end.filter(|_| is_sprite));
+                    for text in lines {
+                        line(f, depth, &text)?;
+                    }
+                    offset = end;
+                    continue;
+                }
+            };
+            sprite_end = None;
+
+            // blocks opening here, outer first
+            let mut opening: Vec<&Structure> = self
+                .structures
+                .iter()
+                .filter(|structure| structure.extent().0 == address as u32)
+                .collect();
+            opening.sort_by_key(|structure| u32::MAX - structure.extent().1);
+
+            for structure in opening {
+                match structure {
+                    Structure::Loop { .. } => line(f, depth, "loop")?,
+                    _ => {
+                        let text = format!("if {} begin", condition(instruction.opcode, false));
+                        line(f, depth, &text)?;
+                        open_ends.push(structure.extent().1);
+                    }
+                }
+                depth += 1;
+            }
+
+            let is_else = self.structures.iter().any(|structure| {
+                matches!(structure, Structure::IfElse { else_jump, .. } if *else_jump == address)
+            });
+            let is_again = self.structures.iter().any(
+                |structure| matches!(structure, Structure::Loop { again, .. } if *again == address),
+            );
+
+            if is_else {
+                line(f, depth - 1, "else")?;
+            } else if is_again {
+                depth -= 1;
+                line(f, depth, "again")?;
+            } else if self.whiles.contains(&address) {
+                line(
+                    f,
+                    depth,
+                    &format!("while {}", condition(instruction.opcode, false)),
+                )?;
+                offset += 2;
+            } else if !self.consumed.contains(&address) {
+                let statement = self.statement(instruction);
+                match then.take() {
+                    Some(condition) => line(f, depth, &format!("{} {}", condition, statement))?,
+                    None if is_skip(instruction.opcode) => then = Some(statement),
+                    None => line(f, depth, &statement)?,
+                }
+            }
+
+            offset += instruction.size as usize;
+        }
+
+        if let Some(text) = then {
+            line(f, depth, &text)?;
+        }
+
+        // blocks ending with the rom
+        while open_ends.pop().is_some() {
+            depth -= 1;
+            line(f, depth, "end")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn is_skip(opcode: u16) -> bool {
+    match opcode >> 12 {
+        0x3 | 0x4 | 0xe => true,
+        0x5 | 0x9 => opcode & 0xf == 0,
+        _ => false,
+    }
+}
+
+// Octo condition of a skip, which holds when it skips or, for `if ... then`,
+// when it does not
+fn condition(opcode: u16, is_then: bool) -> String {
+    let x = (opcode >> 8) & 0xf;
+    let y = (opcode >> 4) & 0xf;
+    let kk = opcode & 0xff;
+
+    let skips_if_equal = matches!(opcode >> 12, 0x3 | 0x5);
+    let comparison = if skips_if_equal != is_then {
+        "=="
+    } else {
+        "!="
+    };
+
+    match opcode >> 12 {
+        0x3 | 0x4 => format!("v{:x} {} {}", x, comparison, kk),
+        0x5 | 0x9 => format!("v{:x} {} v{:x}", x, comparison, y),
+        // Ex9E skips if the key is pressed
+        _ => {
+            let skips_if_pressed = kk == 0x9e;
+            let key = if skips_if_pressed != is_then {
+                "key"
+            } else {
+                "-key"
+            };
+            format!("v{:x} {}", x, key)
+        }
+    }
+}
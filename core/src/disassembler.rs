@@ -0,0 +1,282 @@
+use crate::emulator::cpu::opcode::{hex_address, Opcode};
+use crate::emulator::platform::Platform;
+use std::collections::BTreeMap;
+use std::fmt;
+
+// address the roms are loaded at, and where their execution starts
+pub(crate) const ROM_START: u16 = 0x200;
+// data bytes on a single DB line
+pub(crate) const DATA_PER_LINE: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum LabelKind {
+    // addressed by I
+    Data,
+    Jump,
+    Subroutine,
+}
+
+/// Turns roms into listings of Cowgod's mnemonics, telling code from data by
+/// following the flow of the program from its start.
+pub struct Disassembler {
+    platform: Platform,
+    show_addresses: bool,
+}
+
+impl Disassembler {
+    /// Instructions of `platform` extensions are recognized, the other ones
+    /// are left as data.
+    pub fn new(platform: Platform) -> Self {
+        Self {
+            platform,
+            show_addresses: false,
+        }
+    }
+
+    /// Comments every line with its address and bytes.
+    pub fn show_addresses(mut self, show_addresses: bool) -> Self {
+        self.show_addresses = show_addresses;
+        self
+    }
+
+    pub fn disassemble(&self, rom: &[u8]) -> Disassembly {
+        let mut disassembly = Disassembly {
+            rom: rom.to_vec(),
+            instructions: BTreeMap::new(),
+            labels: BTreeMap::new(),
+            show_addresses: self.show_addresses,
+        };
+
+        // recursive descent: every address reached by the program is decoded
+        // and the possible next ones are queued
+        let mut pending = vec![ROM_START];
+        while let Some(address) = pending.pop() {
+            if disassembly.instructions.contains_key(&address) {
+                continue;
+            }
+
+            let instruction = match self.decode(rom, address) {
+                Some(instruction) => instruction,
+                None => continue,
+            };
+
+            let next = address.wrapping_add(instruction.size);
+
+            match instruction.decoded {
+                Opcode::Ret | Opcode::Exit => {}
+                Opcode::Jp(addr) => {
+                    disassembly.add_label(addr, LabelKind::Jump);
+                    pending.push(addr);
+                }
+                Opcode::Call(addr) => {
+                    disassembly.add_label(addr, LabelKind::Subroutine);
+                    pending.push(addr);
+                    pending.push(next);
+                }
+                Opcode::Se { .. }
+                | Opcode::Sne { .. }
+                | Opcode::SeReg { .. }
+                | Opcode::SneReg { .. }
+                | Opcode::Skp(_)
+                | Opcode::Sknp(_) => {
+                    pending.push(next);
+                    // the skipped instruction may be a 4 bytes long load
+                    let skipped = self.decode(rom, next).map_or(2, |skipped| skipped.size);
+                    pending.push(next.wrapping_add(skipped));
+                }
+                Opcode::Ldi(addr) => {
+                    disassembly.add_label(addr, LabelKind::Data);
+                    pending.push(next);
+                }
+                // the base of a jump table, usually made of jumps
+                Opcode::Jp0(addr) => {
+                    disassembly.add_label(addr, LabelKind::Jump);
+                    pending.push(addr);
+                }
+                Opcode::LdILong => {
+                    disassembly.add_label(instruction.long, LabelKind::Data);
+                    pending.push(next);
+                }
+                _ => pending.push(next),
+            }
+
+            disassembly.instructions.insert(address, instruction);
+        }
+
+        disassembly.remove_overlapping_instructions();
+        disassembly
+    }
+
+    fn decode(&self, rom: &[u8], address: u16) -> Option<Instruction> {
+        let byte = |address: u16| rom.get(address.checked_sub(ROM_START)? as usize).cloned();
+        let word = |address: u16| {
+            Some((byte(address)? as u16) << 8 | byte(address.wrapping_add(1))? as u16)
+        };
+
+        let opcode = word(address)?;
+        let decoded = Opcode::decode(opcode).ok()?;
+        if !decoded.is_supported_by(self.platform) {
+            return None;
+        }
+
+        let long = match decoded {
+            Opcode::LdILong => word(address.wrapping_add(2))?,
+            _ => 0,
+        };
+
+        Some(Instruction {
+            opcode,
+            decoded,
+            long,
+            size: decoded.size(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Instruction {
+    pub(crate) opcode: u16,
+    pub(crate) decoded: Opcode,
+    // address following the XO-CHIP F000 long load
+    pub(crate) long: u16,
+    pub(crate) size: u16,
+}
+
+/// Listing of a rom, displayed as assembly source.
+pub struct Disassembly {
+    pub(crate) rom: Vec<u8>,
+    pub(crate) instructions: BTreeMap<u16, Instruction>,
+    labels: BTreeMap<u16, LabelKind>,
+    show_addresses: bool,
+}
+
+impl Disassembly {
+    /// Whether an instruction starts at `address`.
+    pub fn is_code(&self, address: u16) -> bool {
+        self.instructions.contains_key(&address)
+    }
+
+    /// Generated labels, by address. Only addresses starting a line of the
+    /// listing get one.
+    pub fn labels(&self) -> BTreeMap<u16, String> {
+        self.labels
+            .keys()
+            .filter_map(|address| Some((*address, self.label(*address)?)))
+            .collect()
+    }
+
+    fn add_label(&mut self, address: u16, kind: LabelKind) {
+        let label = self.labels.entry(address).or_insert(kind);
+        *label = (*label).max(kind);
+    }
+
+    pub(crate) fn label(&self, address: u16) -> Option<String> {
+        let kind = self.labels.get(&address)?;
+
+        let offset = address.checked_sub(ROM_START)? as usize;
+        if offset >= self.rom.len() || self.is_inside_instruction(address) {
+            return None;
+        }
+
+        let prefix = match kind {
+            LabelKind::Data => "data",
+            LabelKind::Jump => "label",
+            LabelKind::Subroutine => "sub",
+        };
+
+        Some(format!("{}_{:03X}", prefix, address))
+    }
+
+    // label of the address, or the address itself when it has none
+    fn operand(&self, address: u16) -> String {
+        self.label(address).unwrap_or_else(|| hex_address(address))
+    }
+
+    fn is_inside_instruction(&self, address: u16) -> bool {
+        self.instructions
+            .range(..address)
+            .next_back()
+            .is_some_and(|(start, instruction)| {
+                (address as u32) < *start as u32 + instruction.size as u32
+            })
+    }
+
+    // code reached at an odd offset can overlap other instructions, the first
+    // one in address order is kept
+    fn remove_overlapping_instructions(&mut self) {
+        let mut end = 0;
+        let mut overlapping = vec![];
+        for (address, instruction) in &self.instructions {
+            if (*address as u32) < end {
+                overlapping.push(*address);
+            } else {
+                end = *address as u32 + instruction.size as u32;
+            }
+        }
+
+        for address in overlapping {
+            self.instructions.remove(&address);
+        }
+    }
+
+    fn fmt_line(
+        &self,
+        f: &mut fmt::Formatter,
+        address: u16,
+        bytes: &[u8],
+        text: &str,
+    ) -> fmt::Result {
+        if !self.show_addresses {
+            return writeln!(f, "    {}", text);
+        }
+
+        let bytes: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        writeln!(f, "    {:<32}; {:03X}: {}", text, address, bytes.join(" "))
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut offset = 0;
+        while offset < self.rom.len() {
+            let address = ROM_START.wrapping_add(offset as u16);
+
+            if let Some(label) = self.label(address) {
+                writeln!(f, "{}:", label)?;
+            }
+
+            if let Some(instruction) = self.instructions.get(&address) {
+                let size = instruction.size as usize;
+                let mut text = instruction
+                    .decoded
+                    .mnemonic(|address| self.operand(address));
+                if instruction.decoded == Opcode::LdILong {
+                    text = format!("{} {}", text, self.operand(instruction.long));
+                }
+
+                self.fmt_line(f, address, &self.rom[offset..offset + size], &text)?;
+                offset += size;
+                continue;
+            }
+
+            // data up to the next label or instruction
+            let mut end = offset + 1;
+            while end < self.rom.len() && end - offset < DATA_PER_LINE {
+                let address = ROM_START.wrapping_add(end as u16);
+                if self.instructions.contains_key(&address) || self.label(address).is_some() {
+                    break;
+                }
+                end += 1;
+            }
+
+            let bytes = &self.rom[offset..end];
+            let text: Vec<String> = bytes.iter().map(|byte| format!("{:#04X}", byte)).collect();
+            let text = format!("DB {}", text.join(", ").replace("0X", "0x"));
+
+            self.fmt_line(f, address, bytes, &text)?;
+            offset = end;
+        }
+
+        Ok(())
+    }
+}
@@ -1,4 +1,105 @@
+pub const PATTERN_SIZE: usize = 16;
+
+// pitch at which the pattern is played at 4000 bits per second
+const DEFAULT_PITCH: u8 = 64;
+const BASE_SAMPLE_RATE: f32 = 4000.0;
+
 pub trait Audio {
-    fn beep(&mut self) -> Result<(), Box<dyn std::error::Error>>;
+    fn beep(&mut self, pattern: &AudioPattern) -> Result<(), Box<dyn std::error::Error>>;
     fn stop_beep(&mut self);
 }
+
+/// Audio which plays nothing, for frontends without sound.
+pub struct NoAudio;
+
+impl Audio for NoAudio {
+    fn beep(&mut self, _pattern: &AudioPattern) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn stop_beep(&mut self) {}
+}
+
+/// XO-CHIP sound: a 128 bit 1-bit waveform played in loop at a rate
+/// controlled by the pitch register.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AudioPattern {
+    buffer: [u8; PATTERN_SIZE],
+    pitch: u8,
+}
+
+impl Default for AudioPattern {
+    // square wave played by programs which never load a pattern
+    fn default() -> Self {
+        Self {
+            buffer: [
+                0xff, 0x00, 0xff, 0x00, 0xff, 0x00, 0xff, 0x00, 0xff, 0x00, 0xff, 0x00, 0xff, 0x00,
+                0xff, 0x00,
+            ],
+            pitch: DEFAULT_PITCH,
+        }
+    }
+}
+
+impl AudioPattern {
+    pub fn new(buffer: [u8; PATTERN_SIZE], pitch: u8) -> Self {
+        Self { buffer, pitch }
+    }
+
+    pub fn buffer(&self) -> &[u8; PATTERN_SIZE] {
+        &self.buffer
+    }
+
+    pub fn pitch(&self) -> u8 {
+        self.pitch
+    }
+
+    pub fn set_buffer(&mut self, buffer: [u8; PATTERN_SIZE]) {
+        self.buffer = buffer;
+    }
+
+    pub fn set_pitch(&mut self, pitch: u8) {
+        self.pitch = pitch;
+    }
+
+    /// Bits of the pattern played per second.
+    pub fn sample_rate(&self) -> f32 {
+        BASE_SAMPLE_RATE * 2_f32.powf((self.pitch as f32 - DEFAULT_PITCH as f32) / 48.0)
+    }
+
+    /// Value of the bit at `index`, wrapping around the pattern.
+    pub fn sample(&self, index: usize) -> f32 {
+        let bit = index % (PATTERN_SIZE * 8);
+        if (self.buffer[bit / 8] >> (7 - bit % 8)) & 0x1 == 1 {
+            1.0
+        } else {
+            -1.0
+        }
+    }
+}
+
+/// Plays an `AudioPattern` in loop, resampling it to the rate of the output device.
+#[derive(Debug, Default)]
+pub struct Waveform {
+    pattern: AudioPattern,
+    position: f32,
+}
+
+impl Waveform {
+    pub fn pattern(&self) -> &AudioPattern {
+        &self.pattern
+    }
+
+    pub fn set_pattern(&mut self, pattern: AudioPattern) {
+        self.pattern = pattern;
+    }
+
+    pub fn next_sample(&mut self, output_sample_rate: f32) -> f32 {
+        let sample = self.pattern.sample(self.position as usize);
+
+        self.position = (self.position + self.pattern.sample_rate() / output_sample_rate)
+            % (PATTERN_SIZE * 8) as f32;
+
+        sample
+    }
+}
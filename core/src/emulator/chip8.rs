@@ -0,0 +1,261 @@
+use crate::emulator::audio::{Audio, NoAudio};
+use crate::emulator::cpu::registers::Registers;
+use crate::emulator::cpu::tracer::Tracer;
+use crate::emulator::cpu::{Cpu, DEFAULT_SEED};
+use crate::emulator::display::Display;
+use crate::emulator::keyboard::KeyboardState;
+use crate::emulator::memory::{Memory, MemoryError, MAX_ROM_SIZE};
+use crate::emulator::platform::Platform;
+use crate::emulator::quirks::Quirks;
+use crate::emulator::state::{Snapshot, StateError};
+use crate::emulator::EmulatorError;
+
+const DEFAULT_INSTRUCTIONS_PER_FRAME: usize = 10;
+
+/// A whole machine: cpu, memory, display, audio and the state of the keypad.
+pub struct Chip8 {
+    cpu: Cpu,
+    display: Display,
+    audio: Box<dyn Audio>,
+    keyboard_state: KeyboardState,
+    rom: Vec<u8>,
+    platform: Option<Platform>,
+    quirks: Quirks,
+    seed: u64,
+    instructions_per_frame: usize,
+    decode_cache: bool,
+}
+
+impl Chip8 {
+    pub fn builder() -> Chip8Builder {
+        Chip8Builder::default()
+    }
+
+    /// Replaces the rom and restarts the machine, the machine is left
+    /// untouched when the rom does not fit in memory.
+    pub fn load(&mut self, rom: &[u8]) -> Result<(), MemoryError> {
+        if rom.len() > MAX_ROM_SIZE {
+            return Err(MemoryError::RomTooLarge(rom.len()));
+        }
+
+        self.rom = rom.to_vec();
+        self.reset()
+    }
+
+    /// Restarts the machine with the loaded rom, as after a power cycle.
+    pub fn reset(&mut self) -> Result<(), MemoryError> {
+        let mut memory = Memory::default();
+        memory.load_rom(&self.rom)?;
+        memory.set_decode_cache(self.decode_cache);
+
+        let tracer = self.cpu.take_tracer();
+        self.cpu = Cpu::new(memory, self.quirks);
+        self.cpu.set_seed(self.seed);
+        self.cpu.set_tracer(tracer);
+        self.display = Display::default();
+        self.audio.stop_beep();
+
+        Ok(())
+    }
+
+    /// Executes a single instruction, timers are left untouched.
+    pub fn step(&mut self) -> Result<(), EmulatorError> {
+        Ok(self
+            .cpu
+            .step_instruction(&mut self.display, &self.keyboard_state)?)
+    }
+
+    /// Emulates one 60Hz frame.
+    pub fn run_frame(&mut self) -> Result<(), EmulatorError> {
+        Ok(self.cpu.run_frame(
+            self.instructions_per_frame,
+            &mut self.display,
+            self.audio.as_mut(),
+            &self.keyboard_state,
+        )?)
+    }
+
+    /// Refreshes the timers at the end of a frame run one step at a time.
+    pub fn tick_timers(&mut self) -> Result<(), EmulatorError> {
+        Ok(self.cpu.tick_timers(self.audio.as_mut())?)
+    }
+
+    pub fn save_state(&self) -> Snapshot {
+        Snapshot::capture(&self.cpu, &self.display)
+    }
+
+    /// Saves the state into `snapshot`, reusing its buffer.
+    pub fn save_state_into(&self, snapshot: &mut Snapshot) {
+        snapshot.capture_into(&self.cpu, &self.display)
+    }
+
+    pub fn load_state(&mut self, snapshot: &Snapshot) -> Result<(), StateError> {
+        snapshot.restore(&mut self.cpu, &mut self.display)?;
+        self.audio.stop_beep();
+        Ok(())
+    }
+
+    /// Traces every instruction executed from now on, or stops with `None`.
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        self.cpu.set_tracer(tracer);
+    }
+
+    pub fn tracer_mut(&mut self) -> Option<&mut Tracer> {
+        self.cpu.tracer_mut()
+    }
+
+    /// Keys held down from now on.
+    pub fn set_keyboard_state(&mut self, keyboard_state: KeyboardState) {
+        self.keyboard_state = keyboard_state;
+    }
+
+    pub fn is_exited(&self) -> bool {
+        self.cpu.is_exited()
+    }
+
+    pub fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    pub fn registers(&self) -> &Registers {
+        self.cpu.registers()
+    }
+
+    pub fn memory(&self) -> &Memory {
+        self.cpu.memory()
+    }
+
+    /// Write access for debuggers, the machine is not reset.
+    pub fn registers_mut(&mut self) -> &mut Registers {
+        self.cpu.registers_mut()
+    }
+
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        self.cpu.memory_mut()
+    }
+
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    pub fn keyboard_state(&self) -> &KeyboardState {
+        &self.keyboard_state
+    }
+
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    /// `None` for a machine built without a platform, running every
+    /// instruction with Cowgod's interpretation.
+    pub fn platform(&self) -> Option<Platform> {
+        self.platform
+    }
+
+    pub fn quirks(&self) -> &Quirks {
+        &self.quirks
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn instructions_per_frame(&self) -> usize {
+        self.instructions_per_frame
+    }
+
+    pub fn is_decode_cache_enabled(&self) -> bool {
+        self.decode_cache
+    }
+}
+
+pub struct Chip8Builder {
+    rom: Vec<u8>,
+    platform: Option<Platform>,
+    quirks: Option<Quirks>,
+    seed: u64,
+    instructions_per_frame: usize,
+    decode_cache: bool,
+    audio: Option<Box<dyn Audio>>,
+}
+
+impl Default for Chip8Builder {
+    fn default() -> Self {
+        Self {
+            rom: vec![],
+            platform: None,
+            quirks: None,
+            seed: DEFAULT_SEED,
+            instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
+            decode_cache: false,
+            audio: None,
+        }
+    }
+}
+
+impl Chip8Builder {
+    pub fn rom(mut self, rom: &[u8]) -> Self {
+        self.rom = rom.to_vec();
+        self
+    }
+
+    /// Selects the quirks of `platform`. Without a platform every instruction
+    /// is recognized and interpreted as in Cowgod's reference.
+    pub fn platform(mut self, platform: Platform) -> Self {
+        self.platform = Some(platform);
+        self
+    }
+
+    /// Overrides the quirks of the platform.
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = Some(quirks);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn instructions_per_frame(mut self, instructions_per_frame: usize) -> Self {
+        self.instructions_per_frame = instructions_per_frame;
+        self
+    }
+
+    /// Decodes every instruction once and runs it from a cache afterwards,
+    /// until the rom overwrites it. Faster, at the cost of 256KB of memory.
+    pub fn decode_cache(mut self, decode_cache: bool) -> Self {
+        self.decode_cache = decode_cache;
+        self
+    }
+
+    /// Sound output, the machine is silent when not set.
+    pub fn audio(mut self, audio: Box<dyn Audio>) -> Self {
+        self.audio = Some(audio);
+        self
+    }
+
+    /// Fails when the rom does not fit in memory.
+    pub fn build(self) -> Result<Chip8, MemoryError> {
+        let quirks = self.quirks.unwrap_or_else(|| match self.platform {
+            Some(platform) => platform.quirks(),
+            None => Quirks::COWGOD,
+        });
+
+        let mut chip8 = Chip8 {
+            cpu: Cpu::new(Memory::default(), quirks),
+            display: Display::default(),
+            audio: self.audio.unwrap_or_else(|| Box::new(NoAudio)),
+            keyboard_state: KeyboardState::default(),
+            rom: self.rom,
+            platform: self.platform,
+            quirks,
+            seed: self.seed,
+            instructions_per_frame: self.instructions_per_frame,
+            decode_cache: self.decode_cache,
+        };
+        chip8.reset()?;
+
+        Ok(chip8)
+    }
+}
@@ -0,0 +1,67 @@
+// PackBits run-length encoding: a header byte n in 0..=127 is followed by
+// n + 1 literal bytes, a header byte n in 129..=255 by one byte repeated
+// 257 - n times.
+
+const MAX_RUN: usize = 128;
+
+pub(crate) fn compress(bytes: &[u8]) -> Vec<u8> {
+    let mut compressed = Vec::with_capacity(bytes.len() / 4);
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let run = run_length(&bytes[index..]);
+
+        if run >= 3 {
+            compressed.push((257 - run) as u8);
+            compressed.push(bytes[index]);
+            index += run;
+            continue;
+        }
+
+        // literals last until the next run worth encoding
+        let start = index;
+        while index < bytes.len() && index - start < MAX_RUN && run_length(&bytes[index..]) < 3 {
+            index += 1;
+        }
+
+        compressed.push((index - start - 1) as u8);
+        compressed.extend_from_slice(&bytes[start..index]);
+    }
+
+    compressed
+}
+
+/// `None` when `compressed` is truncated.
+pub(crate) fn decompress(compressed: &[u8]) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(compressed.len() * 4);
+    let mut index = 0;
+
+    while index < compressed.len() {
+        let header = compressed[index] as usize;
+        index += 1;
+
+        match header {
+            0..=127 => {
+                let literals = compressed.get(index..index + header + 1)?;
+                bytes.extend_from_slice(literals);
+                index += header + 1;
+            }
+            128 => {}
+            _ => {
+                let byte = *compressed.get(index)?;
+                bytes.extend(std::iter::repeat_n(byte, 257 - header));
+                index += 1;
+            }
+        }
+    }
+
+    Some(bytes)
+}
+
+fn run_length(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .take(MAX_RUN)
+        .take_while(|byte| **byte == bytes[0])
+        .count()
+}
@@ -1,22 +1,38 @@
-use crate::emulator::audio::Audio;
-use crate::emulator::cpu::instruction::Instruction;
+use crate::emulator::audio::{Audio, AudioPattern, PATTERN_SIZE};
+use crate::emulator::cpu::opcode::Opcode;
 use crate::emulator::cpu::registers::{Registers, RegistersError};
+use crate::emulator::cpu::rng::Rng;
+use crate::emulator::cpu::tracer::Tracer;
 use crate::emulator::display::Display;
 use crate::emulator::keyboard::KeyboardState;
 use crate::emulator::memory::Memory;
-use random::Source;
+use crate::emulator::quirks::{LoadStore, Quirks};
 use thiserror::Error;
 
-mod instruction;
-mod registers;
+pub mod opcode;
+pub mod registers;
+pub mod rng;
+pub mod tracer;
+
+#[cfg(test)]
+mod tests;
+
+pub const DEFAULT_SEED: u64 = 42;
 
 pub struct Cpu {
     memory: Memory,
     registers: Registers,
+    quirks: Quirks,
     is_waiting_key: bool,
     waiting_key_register: u8,
-    timer: instant::Instant,
-    rng: random::Default,
+    // SUPER-CHIP persistent user flags (HP-48 RPL flags)
+    rpl_flags: [u8; 16],
+    is_exited: bool,
+    // set by a draw when the display wait quirk is enabled, until the next refresh
+    is_waiting_display: bool,
+    audio_pattern: AudioPattern,
+    rng: Rng,
+    tracer: Option<Tracer>,
 }
 
 #[derive(Debug, Error)]
@@ -25,42 +41,155 @@ pub enum CpuError {
     UnhandledInstruction(u16),
     #[error(transparent)]
     RegistersError(#[from] RegistersError),
+    #[error("Cannot write the trace: {0}")]
+    TraceError(#[from] std::io::Error),
     #[error(transparent)]
     CpuError(#[from] Box<dyn std::error::Error>),
 }
 
 impl Cpu {
-    pub fn new(memory: Memory) -> Self {
+    pub fn new(memory: Memory, quirks: Quirks) -> Self {
         Self {
             memory,
             registers: Default::default(),
+            quirks,
             is_waiting_key: false,
             waiting_key_register: 0,
-            timer: instant::Instant::now(),
-            rng: random::default().seed([42, 69]),
+            rpl_flags: [0; 16],
+            is_exited: false,
+            is_waiting_display: false,
+            audio_pattern: Default::default(),
+            rng: Rng::new([DEFAULT_SEED, 69]),
+            tracer: None,
         }
     }
 
-    pub fn cycle(
+    /// Restarts the random number generator from `seed`.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::new([seed, 69]);
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    pub fn is_waiting_key(&self) -> bool {
+        self.is_waiting_key
+    }
+
+    pub fn waiting_key_register(&self) -> u8 {
+        self.waiting_key_register
+    }
+
+    pub fn rpl_flags(&self) -> &[u8; 16] {
+        &self.rpl_flags
+    }
+
+    pub fn is_waiting_display(&self) -> bool {
+        self.is_waiting_display
+    }
+
+    pub fn audio_pattern(&self) -> &AudioPattern {
+        &self.audio_pattern
+    }
+
+    pub fn rng(&self) -> &Rng {
+        &self.rng
+    }
+
+    /// Starts tracing every instruction executed, or stops with `None`.
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        self.tracer = tracer;
+    }
+
+    pub fn tracer_mut(&mut self) -> Option<&mut Tracer> {
+        self.tracer.as_mut()
+    }
+
+    pub(crate) fn take_tracer(&mut self) -> Option<Tracer> {
+        self.tracer.take()
+    }
+
+    pub(crate) fn registers_mut(&mut self) -> &mut Registers {
+        &mut self.registers
+    }
+
+    pub(crate) fn set_waiting_key(&mut self, is_waiting_key: bool, waiting_key_register: u8) {
+        self.is_waiting_key = is_waiting_key;
+        self.waiting_key_register = waiting_key_register;
+    }
+
+    pub(crate) fn set_rpl_flags(&mut self, rpl_flags: [u8; 16]) {
+        self.rpl_flags = rpl_flags;
+    }
+
+    pub(crate) fn set_exited(&mut self, is_exited: bool) {
+        self.is_exited = is_exited;
+    }
+
+    pub(crate) fn set_waiting_display(&mut self, is_waiting_display: bool) {
+        self.is_waiting_display = is_waiting_display;
+    }
+
+    pub(crate) fn set_audio_pattern(&mut self, audio_pattern: AudioPattern) {
+        self.audio_pattern = audio_pattern;
+    }
+
+    pub(crate) fn set_rng(&mut self, rng: Rng) {
+        self.rng = rng;
+    }
+
+    /// Emulates one 60Hz frame: executes `instructions_per_frame` instructions
+    /// and then refreshes the timers, independently of the wall clock.
+    pub fn run_frame(
         &mut self,
+        instructions_per_frame: usize,
         display: &mut Display,
         audio: &mut dyn Audio,
-        keyboard_state: KeyboardState,
+        keyboard_state: &KeyboardState,
     ) -> Result<(), CpuError> {
-        let now = instant::Instant::now();
-
-        if now.duration_since(self.timer).as_micros() >= 16666 {
-            self.timer = now;
-            self.registers.decrement_st();
-            self.registers.decrement_dt();
+        for _ in 0..instructions_per_frame {
+            self.step_instruction(display, keyboard_state)?;
         }
 
-        if self.registers.st() > 0 {
-            audio.beep()?;
+        self.tick_timers(audio)
+    }
+
+    /// Decrements the delay and sound timers by one 60Hz frame, starting or
+    /// stopping the sound accordingly.
+    pub fn tick_timers(&mut self, audio: &mut dyn Audio) -> Result<(), CpuError> {
+        self.registers.decrement_st();
+        self.registers.decrement_dt();
+        self.is_waiting_display = false;
+
+        if self.registers.st() > 0 && !self.is_exited {
+            audio.beep(&self.audio_pattern)?;
         } else {
             audio.stop_beep();
         }
 
+        Ok(())
+    }
+
+    /// Executes the instruction pointed by the program counter, unless the
+    /// cpu is waiting for a key press or for the display refresh.
+    pub fn step_instruction(
+        &mut self,
+        display: &mut Display,
+        keyboard_state: &KeyboardState,
+    ) -> Result<(), CpuError> {
+        if self.is_exited {
+            return Ok(());
+        }
+
         if self.is_waiting_key {
             if let Some(key) = keyboard_state.get_key_pressed() {
                 self.registers
@@ -71,97 +200,95 @@ impl Cpu {
             return Ok(());
         }
 
-        let instruction = self.memory.read_16(self.registers.pc());
-        self.registers.inc_pc_by(2);
+        if self.is_waiting_display {
+            return Ok(());
+        }
 
-        let i: Instruction = instruction.into();
+        let pc = self.registers.pc();
 
-        match i.prefix() {
-            0x0 => self.x0nnn(i, display),
-            0x1 => {
-                self.jp(i.nnn());
-                Ok(())
-            }
-            0x2 => Ok(self.call(i.nnn())?),
-            0x3 => Ok(self.se(i.x(), i.kk())?),
-            0x4 => Ok(self.sne(i.x(), i.kk())?),
-            0x5 => Ok(self.se_reg(i.x(), i.y())?),
-            0x6 => Ok(self.ld(i.x(), i.kk())?),
-            0x7 => Ok(self.add(i.x(), i.kk())?),
-            0x8 => self.x8nnn(i),
-            0x9 => Ok(self.sne_reg(i.x(), i.y())?),
-            0xa => {
-                self.ldi(i.nnn());
-                Ok(())
-            }
-            0xb => Ok(self.jp_0(i.nnn())?),
-            0xc => Ok(self.rnd(i.x(), i.kk())?),
-            0xd => Ok(self.draw(i.x(), i.y(), i.suffix_4(), display)?),
-            0xe => self.xennn(i, keyboard_state),
-            0xf => self.xfnnn(i),
-            _ => Err(CpuError::UnhandledInstruction(instruction)),
-        }?;
+        if let Some(tracer) = &mut self.tracer {
+            tracer.trace(pc, self.memory.read_16(pc), &self.registers, &self.memory)?;
+        }
+        self.registers.inc_pc_by(2);
 
-        Ok(())
+        let opcode = match self.memory.decode(pc) {
+            Ok(opcode) => opcode,
+            Err(_) => return Err(CpuError::UnhandledInstruction(self.memory.read_16(pc))),
+        };
+        self.execute(opcode, display, keyboard_state)
     }
 
-    pub fn x0nnn(
+    /// Executes `opcode` as if it was the instruction pointed by the program
+    /// counter, which must already be past it.
+    pub fn execute(
         &mut self,
-        instruction: Instruction,
+        opcode: Opcode,
         display: &mut Display,
+        keyboard_state: &KeyboardState,
     ) -> Result<(), CpuError> {
-        if instruction.original() == 0x00e0 {
-            self.cls(display);
-            return Ok(());
+        match opcode {
+            Opcode::Cls => self.cls(display),
+            Opcode::Ret => self.ret()?,
+            Opcode::Scd(n) => self.scd(n, display),
+            Opcode::Scu(n) => self.scu(n, display),
+            Opcode::Scr => self.scr(display),
+            Opcode::Scl => self.scl(display),
+            Opcode::Exit => self.exit(),
+            Opcode::Low => self.low(display),
+            Opcode::High => self.high(display),
+            Opcode::Jp(addr) => self.jp(addr),
+            Opcode::Call(addr) => self.call(addr)?,
+            Opcode::Se { x, byte } => self.se(x, byte)?,
+            Opcode::Sne { x, byte } => self.sne(x, byte)?,
+            Opcode::SeReg { x, y } => self.se_reg(x, y)?,
+            Opcode::LdRangeInto { x, y } => self.ld_range_into(x, y)?,
+            Opcode::LdRangeFrom { x, y } => self.ld_range_from(x, y)?,
+            Opcode::Ld { x, byte } => self.ld(x, byte)?,
+            Opcode::Add { x, byte } => self.add(x, byte)?,
+            Opcode::LdReg { x, y } => self.ld_reg(x, y)?,
+            Opcode::Or { x, y } => self.or(x, y)?,
+            Opcode::And { x, y } => self.and(x, y)?,
+            Opcode::Xor { x, y } => self.xor(x, y)?,
+            Opcode::AddReg { x, y } => self.add_reg(x, y)?,
+            Opcode::Sub { x, y } => self.sub(x, y)?,
+            Opcode::Shr { x, y } => self.shr(x, y)?,
+            Opcode::Subn { x, y } => self.subn(x, y)?,
+            Opcode::Shl { x, y } => self.shl(x, y)?,
+            Opcode::SneReg { x, y } => self.sne_reg(x, y)?,
+            Opcode::Ldi(addr) => self.ldi(addr),
+            Opcode::Jp0(addr) => self.jp_0(addr)?,
+            Opcode::Rnd { x, byte } => self.rnd(x, byte)?,
+            Opcode::Draw { x, y, n } => self.draw(x, y, n, display)?,
+            Opcode::Skp(x) => self.skp(x, keyboard_state)?,
+            Opcode::Sknp(x) => self.sknp(x, keyboard_state)?,
+            Opcode::LdILong => self.ld_i_long(),
+            Opcode::Plane(mask) => self.plane(mask, display),
+            Opcode::LdAudio => self.ld_audio(),
+            Opcode::LdFromDt(x) => self.ld_from_dt(x)?,
+            Opcode::LdK(x) => self.ld_k(x)?,
+            Opcode::LdIntoDt(x) => self.ld_into_dt(x)?,
+            Opcode::LdIntoSt(x) => self.ld_into_st(x)?,
+            Opcode::AddI(x) => self.add_i(x)?,
+            Opcode::LdF(x) => self.ld_f(x)?,
+            Opcode::LdHf(x) => self.ld_hf(x)?,
+            Opcode::LdB(x) => self.ld_b(x)?,
+            Opcode::LdPitch(x) => self.ld_pitch(x)?,
+            Opcode::LdBatchInto(x) => self.ld_batch_into(x)?,
+            Opcode::LdBatchFrom(x) => self.ld_batch_from(x)?,
+            Opcode::LdRInto(x) => self.ld_r_into(x)?,
+            Opcode::LdRFrom(x) => self.ld_r_from(x)?,
         }
 
-        if instruction.original() == 0x00ee {
-            return Ok(self.ret()?);
-        }
-
-        Err(CpuError::UnhandledInstruction(instruction.original()))
-    }
-
-    pub fn x8nnn(&mut self, instruction: Instruction) -> Result<(), CpuError> {
-        match instruction.suffix_4() {
-            0x0 => Ok(self.ld_reg(instruction.x(), instruction.y())?),
-            0x1 => Ok(self.or(instruction.x(), instruction.y())?),
-            0x2 => Ok(self.and(instruction.x(), instruction.y())?),
-            0x3 => Ok(self.xor(instruction.x(), instruction.y())?),
-            0x4 => Ok(self.add_reg(instruction.x(), instruction.y())?),
-            0x5 => Ok(self.sub(instruction.x(), instruction.y())?),
-            0x6 => Ok(self.shr(instruction.x())?),
-            0x7 => Ok(self.subn(instruction.x(), instruction.y())?),
-            0xe => Ok(self.shl(instruction.x())?),
-            _ => Err(CpuError::UnhandledInstruction(instruction.original())),
-        }
+        Ok(())
     }
 
-    pub fn xennn(
-        &mut self,
-        instruction: Instruction,
-        keyboard: KeyboardState,
-    ) -> Result<(), CpuError> {
-        match instruction.suffix_8() {
-            0x9e => Ok(self.skp(instruction.x(), keyboard)?),
-            0xa1 => Ok(self.sknp(instruction.x(), keyboard)?),
-            _ => Err(CpuError::UnhandledInstruction(instruction.original())),
-        }
+    pub fn quirks(&self) -> &Quirks {
+        &self.quirks
     }
 
-    pub fn xfnnn(&mut self, instruction: Instruction) -> Result<(), CpuError> {
-        match instruction.suffix_8() {
-            0x07 => Ok(self.ld_from_dt(instruction.x())?),
-            0x0a => Ok(self.ld_k(instruction.x())?),
-            0x15 => Ok(self.ld_into_dt(instruction.x())?),
-            0x18 => Ok(self.ld_into_st(instruction.x())?),
-            0x1e => Ok(self.add_i(instruction.x())?),
-            0x29 => Ok(self.ld_f(instruction.x())?),
-            0x33 => Ok(self.ld_b(instruction.x())?),
-            0x55 => Ok(self.ld_batch_into(instruction.x())?),
-            0x65 => Ok(self.ld_batch_from(instruction.x())?),
-            _ => Err(CpuError::UnhandledInstruction(instruction.original())),
-        }
+    /// Whether the program stopped the interpreter through `00FD`.
+    pub fn is_exited(&self) -> bool {
+        self.is_exited
     }
 
     pub fn cls(&mut self, display: &mut Display) {
@@ -173,6 +300,43 @@ impl Cpu {
         Ok(())
     }
 
+    pub fn scd(&mut self, nibble: u8, display: &mut Display) {
+        display.scroll_down(nibble as usize)
+    }
+
+    pub fn scu(&mut self, nibble: u8, display: &mut Display) {
+        display.scroll_up(nibble as usize)
+    }
+
+    pub fn scr(&mut self, display: &mut Display) {
+        display.scroll_right(4)
+    }
+
+    pub fn scl(&mut self, display: &mut Display) {
+        display.scroll_left(4)
+    }
+
+    pub fn exit(&mut self) {
+        self.is_exited = true
+    }
+
+    pub fn low(&mut self, display: &mut Display) {
+        display.set_hires(false)
+    }
+
+    pub fn high(&mut self, display: &mut Display) {
+        display.set_hires(true)
+    }
+
+    // skips the next instruction, stepping over the whole 4 bytes of an XO-CHIP long load
+    fn skip(&mut self) {
+        if self.memory.read_16(self.registers.pc()) == 0xf000 {
+            self.registers.inc_pc_by(4)
+        } else {
+            self.registers.inc_pc_by(2)
+        }
+    }
+
     pub fn jp(&mut self, addr: u16) {
         self.registers.set_pc(addr)
     }
@@ -184,7 +348,7 @@ impl Cpu {
     pub fn se(&mut self, v_x: u8, byte: u8) -> Result<(), RegistersError> {
         let register_value = self.registers.register(v_x)?;
         if register_value == byte {
-            self.registers.inc_pc_by(2)
+            self.skip()
         }
 
         Ok(())
@@ -193,7 +357,7 @@ impl Cpu {
     pub fn sne(&mut self, v_x: u8, byte: u8) -> Result<(), RegistersError> {
         let register_value = self.registers.register(v_x)?;
         if register_value != byte {
-            self.registers.inc_pc_by(2)
+            self.skip()
         }
 
         Ok(())
@@ -203,7 +367,7 @@ impl Cpu {
         let register_1_value = self.registers.register(v_x)?;
         let register_2_value = self.registers.register(v_y)?;
         if register_1_value == register_2_value {
-            self.registers.inc_pc_by(2)
+            self.skip()
         }
 
         Ok(())
@@ -228,14 +392,8 @@ impl Cpu {
         let register_1_value = self.registers.register(v_x)?;
         let register_2_value = self.registers.register(v_y)?;
 
-        let (sum, overflow) = register_1_value.overflowing_add(register_2_value);
-        if overflow {
-            self.registers.set_v_f(1)
-        } else {
-            self.registers.set_v_f(0)
-        }
-
-        self.registers.set_register(v_x, sum)
+        let (sum, carry) = register_1_value.overflowing_add(register_2_value);
+        self.set_result_and_flag(v_x, sum, carry as u8)
     }
 
     pub fn or(&mut self, v_x: u8, v_y: u8) -> Result<(), RegistersError> {
@@ -243,7 +401,9 @@ impl Cpu {
         let register_2_value = self.registers.register(v_y)?;
 
         self.registers
-            .set_register(v_x, register_1_value | register_2_value)
+            .set_register(v_x, register_1_value | register_2_value)?;
+        self.reset_v_f();
+        Ok(())
     }
 
     pub fn and(&mut self, v_x: u8, v_y: u8) -> Result<(), RegistersError> {
@@ -251,7 +411,9 @@ impl Cpu {
         let register_2_value = self.registers.register(v_y)?;
 
         self.registers
-            .set_register(v_x, register_1_value & register_2_value)
+            .set_register(v_x, register_1_value & register_2_value)?;
+        self.reset_v_f();
+        Ok(())
     }
 
     pub fn xor(&mut self, v_x: u8, v_y: u8) -> Result<(), RegistersError> {
@@ -259,56 +421,64 @@ impl Cpu {
         let register_2_value = self.registers.register(v_y)?;
 
         self.registers
-            .set_register(v_x, register_1_value ^ register_2_value)
+            .set_register(v_x, register_1_value ^ register_2_value)?;
+        self.reset_v_f();
+        Ok(())
+    }
+
+    fn reset_v_f(&mut self) {
+        if self.quirks.vf_reset {
+            self.registers.set_v_f(0)
+        }
     }
 
     pub fn sub(&mut self, v_x: u8, v_y: u8) -> Result<(), RegistersError> {
         let register_1_value = self.registers.register(v_x)?;
         let register_2_value = self.registers.register(v_y)?;
 
-        if register_1_value > register_2_value {
-            self.registers.set_v_f(1)
-        } else {
-            self.registers.set_v_f(0)
-        }
+        let (difference, borrow) = register_1_value.overflowing_sub(register_2_value);
+        self.set_result_and_flag(v_x, difference, !borrow as u8)
+    }
 
-        self.registers
-            .set_register(v_x, register_1_value.overflowing_sub(register_2_value).0)
+    pub fn shr(&mut self, v_x: u8, v_y: u8) -> Result<(), RegistersError> {
+        let register_1_value = self.shift_operand(v_x, v_y)?;
+        self.set_result_and_flag(v_x, register_1_value >> 1, register_1_value & 0x1)
     }
 
-    pub fn shr(&mut self, v_x: u8) -> Result<(), RegistersError> {
-        let register_1_value = self.registers.register(v_x)?;
-        self.registers.set_v_f((register_1_value & 0x1) as u8);
-        self.registers.set_register(v_x, register_1_value / 2)
+    fn shift_operand(&self, v_x: u8, v_y: u8) -> Result<u8, RegistersError> {
+        if self.quirks.shift_uses_vy {
+            self.registers.register(v_y)
+        } else {
+            self.registers.register(v_x)
+        }
     }
 
     pub fn subn(&mut self, v_x: u8, v_y: u8) -> Result<(), RegistersError> {
         let register_1_value = self.registers.register(v_x)?;
         let register_2_value = self.registers.register(v_y)?;
 
-        if register_1_value < register_2_value {
-            self.registers.set_v_f(1)
-        } else {
-            self.registers.set_v_f(0)
-        }
+        let (difference, borrow) = register_2_value.overflowing_sub(register_1_value);
+        self.set_result_and_flag(v_x, difference, !borrow as u8)
+    }
 
-        self.registers
-            .set_register(v_x, register_1_value - register_2_value)
+    pub fn shl(&mut self, v_x: u8, v_y: u8) -> Result<(), RegistersError> {
+        let register_1_value = self.shift_operand(v_x, v_y)?;
+        self.set_result_and_flag(v_x, register_1_value << 1, register_1_value >> 7)
     }
 
-    pub fn shl(&mut self, v_x: u8) -> Result<(), RegistersError> {
-        let register_1_value = self.registers.register(v_x)?;
-        self.registers
-            .set_v_f(((register_1_value & 0xA0) >> 7) as u8);
-        self.registers
-            .set_register(v_x, register_1_value.overflowing_mul(2).0)
+    // as on the VIP the flag is written last, so it replaces the result when
+    // VF is the destination
+    fn set_result_and_flag(&mut self, v_x: u8, result: u8, flag: u8) -> Result<(), RegistersError> {
+        self.registers.set_register(v_x, result)?;
+        self.registers.set_v_f(flag);
+        Ok(())
     }
 
     pub fn sne_reg(&mut self, v_x: u8, v_y: u8) -> Result<(), RegistersError> {
         let register_1_value = self.registers.register(v_x)?;
         let register_2_value = self.registers.register(v_y)?;
         if register_1_value != register_2_value {
-            self.registers.inc_pc_by(2)
+            self.skip()
         }
 
         Ok(())
@@ -319,13 +489,19 @@ impl Cpu {
     }
 
     pub fn jp_0(&mut self, addr: u16) -> Result<(), RegistersError> {
+        let v_x = if self.quirks.jump_uses_vx {
+            (addr >> 8) as u8
+        } else {
+            0
+        };
+
         self.registers
-            .set_pc(addr + self.registers.register(0)? as u16);
+            .set_pc(addr + self.registers.register(v_x)? as u16);
         Ok(())
     }
 
     pub fn rnd(&mut self, v_x: u8, byte: u8) -> Result<(), RegistersError> {
-        let random: u8 = self.rng.read::<u8>();
+        let random: u8 = self.rng.read_u8();
         self.registers.set_register(v_x, random & byte)
     }
 
@@ -336,44 +512,88 @@ impl Cpu {
         nibble: u8,
         display: &mut Display,
     ) -> Result<(), CpuError> {
-        let x = self.registers.register(v_x)?;
-        let y = self.registers.register(v_y)?;
+        // the origin wraps around the screen, the sprite may not
+        let x = self.registers.register(v_x)? as usize % display.width();
+        let y = self.registers.register(v_y)? as usize % display.height();
 
-        self.registers.set_v_f(0);
-        for row in 0..nibble {
-            let byte = self.memory.read_8(self.registers.i() + row as u16);
+        // Dxy0 draws a 16x16 SUPER-CHIP sprite, two bytes per row
+        let (rows, bytes_per_row) = if nibble == 0 {
+            (16, 2)
+        } else {
+            (nibble as u16, 1)
+        };
 
-            for column in 0..8 {
-                let x = (x + column) % Display::width() as u8;
-                let y = (y + row) % Display::height() as u8;
+        // with both XO-CHIP planes selected the sprite for the second plane
+        // immediately follows the one for the first
+        let mut address = self.registers.i();
 
-                let old_value = display.pixel(x as usize, y as usize);
-                let to_set: bool = (((byte as usize) >> (7 - column)) & 0x1) > 0;
+        self.registers.set_v_f(0);
+        for plane in display.selected_planes() {
+            for row in 0..rows {
+                let y = y + row as usize;
+                if self.quirks.clip_sprites && y >= display.height() {
+                    continue;
+                }
 
-                display.set_pixel(x as usize, y as usize, to_set);
+                let row_address = address.wrapping_add(row * bytes_per_row);
+                let mut sprite = (self.memory.read_8(row_address) as u16) << 8;
+                if bytes_per_row == 2 {
+                    sprite |= self.memory.read_8(row_address.wrapping_add(1)) as u16;
+                }
 
-                if old_value && !display.pixel(x as usize, y as usize) {
+                let y = y % display.height();
+                if display.xor_sprite_row(plane, x, y, sprite, self.quirks.clip_sprites) {
                     self.registers.set_v_f(1);
                 }
             }
+
+            address = address.wrapping_add(rows * bytes_per_row);
         }
 
+        self.is_waiting_display = self.quirks.display_wait;
+
         Ok(())
     }
 
-    pub fn skp(&mut self, v_x: u8, keyboard: KeyboardState) -> Result<(), CpuError> {
+    pub fn skp(&mut self, v_x: u8, keyboard: &KeyboardState) -> Result<(), CpuError> {
         if keyboard.is_key_pressed(self.registers.register(v_x)?) {
-            self.registers.inc_pc_by(2);
+            self.skip();
         }
 
         Ok(())
     }
 
-    pub fn sknp(&mut self, v_x: u8, keyboard: KeyboardState) -> Result<(), CpuError> {
+    pub fn sknp(&mut self, v_x: u8, keyboard: &KeyboardState) -> Result<(), CpuError> {
         if !keyboard.is_key_pressed(self.registers.register(v_x)?) {
-            self.registers.inc_pc_by(2);
+            self.skip();
+        }
+
+        Ok(())
+    }
+
+    pub fn ld_i_long(&mut self) {
+        let addr = self.memory.read_16(self.registers.pc());
+        self.registers.inc_pc_by(2);
+        self.registers.set_i(addr)
+    }
+
+    pub fn plane(&mut self, mask: u8, display: &mut Display) {
+        display.select_planes(mask)
+    }
+
+    pub fn ld_audio(&mut self) {
+        let mut buffer = [0; PATTERN_SIZE];
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            *byte = self
+                .memory
+                .read_8(self.registers.i().wrapping_add(offset as u16));
         }
 
+        self.audio_pattern.set_buffer(buffer)
+    }
+
+    pub fn ld_pitch(&mut self, v_x: u8) -> Result<(), RegistersError> {
+        self.audio_pattern.set_pitch(self.registers.register(v_x)?);
         Ok(())
     }
 
@@ -413,6 +633,12 @@ impl Cpu {
         Ok(())
     }
 
+    pub fn ld_hf(&mut self, v_x: u8) -> Result<(), CpuError> {
+        let font = self.registers.register(v_x)?;
+        self.registers.set_i(self.memory.get_big_font_address(font));
+        Ok(())
+    }
+
     pub fn ld_b(&mut self, v_x: u8) -> Result<(), RegistersError> {
         let value = self.registers.register(v_x)?;
 
@@ -420,30 +646,97 @@ impl Cpu {
         let tens = (value / 10) % 10;
         let hundreds = (value / 100) % 10;
 
-        self.memory.write_8(self.registers.i(), hundreds);
-        self.memory.write_8(self.registers.i() + 1, tens);
-        self.memory.write_8(self.registers.i() + 2, units);
+        let i = self.registers.i();
+        self.memory.write_8(i, hundreds);
+        self.memory.write_8(i.wrapping_add(1), tens);
+        self.memory.write_8(i.wrapping_add(2), units);
 
         Ok(())
     }
 
+    fn increment_i_after_batch(&mut self, v_x: u8) {
+        let increment = match self.quirks.load_store {
+            LoadStore::Unchanged => return,
+            LoadStore::IncrementByX => v_x as u16,
+            LoadStore::IncrementByXPlusOne => v_x as u16 + 1,
+        };
+
+        self.registers
+            .set_i(self.registers.i().wrapping_add(increment));
+    }
+
     pub fn ld_batch_into(&mut self, v_x: u8) -> Result<(), CpuError> {
         for index in 0..(v_x + 1) {
             let to_write = self.registers.register(index)?;
             self.memory
-                .write_8(self.registers.i() + index as u16, to_write);
+                .write_8(self.registers.i().wrapping_add(index as u16), to_write);
         }
 
+        self.increment_i_after_batch(v_x);
+
         Ok(())
     }
 
     pub fn ld_batch_from(&mut self, v_x: u8) -> Result<(), CpuError> {
         for index in 0..(v_x + 1) {
-            let to_load = self.memory.read_8(self.registers.i() + index as u16);
+            let to_load = self
+                .memory
+                .read_8(self.registers.i().wrapping_add(index as u16));
+
+            self.registers.set_register(index, to_load)?;
+        }
+
+        self.increment_i_after_batch(v_x);
+
+        Ok(())
+    }
+
+    pub fn ld_r_into(&mut self, v_x: u8) -> Result<(), CpuError> {
+        for index in 0..(v_x + 1) {
+            self.rpl_flags[index as usize] = self.registers.register(index)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn ld_r_from(&mut self, v_x: u8) -> Result<(), CpuError> {
+        for index in 0..(v_x + 1) {
+            self.registers
+                .set_register(index, self.rpl_flags[index as usize])?;
+        }
+
+        Ok(())
+    }
 
+    pub fn ld_range_into(&mut self, v_x: u8, v_y: u8) -> Result<(), CpuError> {
+        for (offset, index) in register_range(v_x, v_y).enumerate() {
+            let to_write = self.registers.register(index)?;
+            self.memory
+                .write_8(self.registers.i().wrapping_add(offset as u16), to_write);
+        }
+
+        Ok(())
+    }
+
+    pub fn ld_range_from(&mut self, v_x: u8, v_y: u8) -> Result<(), CpuError> {
+        for (offset, index) in register_range(v_x, v_y).enumerate() {
+            let to_load = self
+                .memory
+                .read_8(self.registers.i().wrapping_add(offset as u16));
             self.registers.set_register(index, to_load)?;
         }
 
         Ok(())
     }
 }
+
+// registers from v_x to v_y, both included, in descending order when v_x > v_y
+fn register_range(v_x: u8, v_y: u8) -> impl Iterator<Item = u8> {
+    (0..=v_x.abs_diff(v_y)).map(move |offset| {
+        if v_x <= v_y {
+            v_x + offset
+        } else {
+            v_x - offset
+        }
+    })
+}
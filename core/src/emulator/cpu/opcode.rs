@@ -0,0 +1,356 @@
+use crate::emulator::platform::Platform;
+use std::fmt;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum OpcodeError {
+    #[error("Unknown opcode: {0:04X}")]
+    UnknownOpcode(u16),
+    #[error("Operands out of range: {0}")]
+    InvalidOperands(Opcode),
+}
+
+/// Instruction of CHIP-8 or of its SUPER-CHIP and XO-CHIP extensions, with
+/// its operands: `x` and `y` are register indexes, `n` and `byte` constants.
+///
+/// Displayed with Cowgod's mnemonics, the same read by the assembler and
+/// printed by the disassembler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    /// 00E0
+    Cls,
+    /// 00EE
+    Ret,
+    /// 00Cn, scrolls down `n` rows, `n` is never 0
+    Scd(u8),
+    /// 00Dn, scrolls up `n` rows, `n` is never 0
+    Scu(u8),
+    /// 00FB
+    Scr,
+    /// 00FC
+    Scl,
+    /// 00FD
+    Exit,
+    /// 00FE
+    Low,
+    /// 00FF
+    High,
+    /// 1nnn
+    Jp(u16),
+    /// 2nnn
+    Call(u16),
+    /// 3xkk
+    Se { x: u8, byte: u8 },
+    /// 4xkk
+    Sne { x: u8, byte: u8 },
+    /// 5xy0
+    SeReg { x: u8, y: u8 },
+    /// 5xy2, stores VX to VY from I
+    LdRangeInto { x: u8, y: u8 },
+    /// 5xy3, loads VX to VY from I
+    LdRangeFrom { x: u8, y: u8 },
+    /// 6xkk
+    Ld { x: u8, byte: u8 },
+    /// 7xkk
+    Add { x: u8, byte: u8 },
+    /// 8xy0
+    LdReg { x: u8, y: u8 },
+    /// 8xy1
+    Or { x: u8, y: u8 },
+    /// 8xy2
+    And { x: u8, y: u8 },
+    /// 8xy3
+    Xor { x: u8, y: u8 },
+    /// 8xy4
+    AddReg { x: u8, y: u8 },
+    /// 8xy5
+    Sub { x: u8, y: u8 },
+    /// 8xy6
+    Shr { x: u8, y: u8 },
+    /// 8xy7
+    Subn { x: u8, y: u8 },
+    /// 8xyE
+    Shl { x: u8, y: u8 },
+    /// 9xy0
+    SneReg { x: u8, y: u8 },
+    /// Annn
+    Ldi(u16),
+    /// Bnnn
+    Jp0(u16),
+    /// Cxkk
+    Rnd { x: u8, byte: u8 },
+    /// Dxyn
+    Draw { x: u8, y: u8, n: u8 },
+    /// Ex9E
+    Skp(u8),
+    /// ExA1
+    Sknp(u8),
+    /// F000, followed by the 16 bit address loaded into I
+    LdILong,
+    /// Fn01, selects the planes in the mask `n`
+    Plane(u8),
+    /// F002
+    LdAudio,
+    /// Fx07
+    LdFromDt(u8),
+    /// Fx0A
+    LdK(u8),
+    /// Fx15
+    LdIntoDt(u8),
+    /// Fx18
+    LdIntoSt(u8),
+    /// Fx1E
+    AddI(u8),
+    /// Fx29
+    LdF(u8),
+    /// Fx30
+    LdHf(u8),
+    /// Fx33
+    LdB(u8),
+    /// Fx3A
+    LdPitch(u8),
+    /// Fx55
+    LdBatchInto(u8),
+    /// Fx65
+    LdBatchFrom(u8),
+    /// Fx75
+    LdRInto(u8),
+    /// Fx85
+    LdRFrom(u8),
+}
+
+impl Opcode {
+    pub fn decode(opcode: u16) -> Result<Opcode, OpcodeError> {
+        use Opcode::*;
+
+        let nnn = opcode & 0x0fff;
+        let x = ((opcode >> 8) & 0xf) as u8;
+        let y = ((opcode >> 4) & 0xf) as u8;
+        let n = (opcode & 0xf) as u8;
+        let byte = opcode as u8;
+
+        Ok(match (opcode >> 12, x, y, n) {
+            (0x0, 0x0, 0xe, 0x0) => Cls,
+            (0x0, 0x0, 0xe, 0xe) => Ret,
+            (0x0, 0x0, 0xc, 0x1..=0xf) => Scd(n),
+            (0x0, 0x0, 0xd, 0x1..=0xf) => Scu(n),
+            (0x0, 0x0, 0xf, 0xb) => Scr,
+            (0x0, 0x0, 0xf, 0xc) => Scl,
+            (0x0, 0x0, 0xf, 0xd) => Exit,
+            (0x0, 0x0, 0xf, 0xe) => Low,
+            (0x0, 0x0, 0xf, 0xf) => High,
+            (0x1, _, _, _) => Jp(nnn),
+            (0x2, _, _, _) => Call(nnn),
+            (0x3, _, _, _) => Se { x, byte },
+            (0x4, _, _, _) => Sne { x, byte },
+            (0x5, _, _, 0x0) => SeReg { x, y },
+            (0x5, _, _, 0x2) => LdRangeInto { x, y },
+            (0x5, _, _, 0x3) => LdRangeFrom { x, y },
+            (0x6, _, _, _) => Ld { x, byte },
+            (0x7, _, _, _) => Add { x, byte },
+            (0x8, _, _, 0x0) => LdReg { x, y },
+            (0x8, _, _, 0x1) => Or { x, y },
+            (0x8, _, _, 0x2) => And { x, y },
+            (0x8, _, _, 0x3) => Xor { x, y },
+            (0x8, _, _, 0x4) => AddReg { x, y },
+            (0x8, _, _, 0x5) => Sub { x, y },
+            (0x8, _, _, 0x6) => Shr { x, y },
+            (0x8, _, _, 0x7) => Subn { x, y },
+            (0x8, _, _, 0xe) => Shl { x, y },
+            (0x9, _, _, 0x0) => SneReg { x, y },
+            (0xa, _, _, _) => Ldi(nnn),
+            (0xb, _, _, _) => Jp0(nnn),
+            (0xc, _, _, _) => Rnd { x, byte },
+            (0xd, _, _, _) => Draw { x, y, n },
+            (0xe, _, 0x9, 0xe) => Skp(x),
+            (0xe, _, 0xa, 0x1) => Sknp(x),
+            (0xf, 0x0, 0x0, 0x0) => LdILong,
+            (0xf, _, 0x0, 0x1) => Plane(x),
+            (0xf, 0x0, 0x0, 0x2) => LdAudio,
+            (0xf, _, 0x0, 0x7) => LdFromDt(x),
+            (0xf, _, 0x0, 0xa) => LdK(x),
+            (0xf, _, 0x1, 0x5) => LdIntoDt(x),
+            (0xf, _, 0x1, 0x8) => LdIntoSt(x),
+            (0xf, _, 0x1, 0xe) => AddI(x),
+            (0xf, _, 0x2, 0x9) => LdF(x),
+            (0xf, _, 0x3, 0x0) => LdHf(x),
+            (0xf, _, 0x3, 0x3) => LdB(x),
+            (0xf, _, 0x3, 0xa) => LdPitch(x),
+            (0xf, _, 0x5, 0x5) => LdBatchInto(x),
+            (0xf, _, 0x6, 0x5) => LdBatchFrom(x),
+            (0xf, _, 0x7, 0x5) => LdRInto(x),
+            (0xf, _, 0x8, 0x5) => LdRFrom(x),
+            _ => return Err(OpcodeError::UnknownOpcode(opcode)),
+        })
+    }
+
+    /// Fails when an operand does not fit in its bits or is a value the
+    /// instruction does not take, like the 0 rows of `Scd`, so that every
+    /// word encoded decodes to the same instruction.
+    pub fn encode(&self) -> Result<u16, OpcodeError> {
+        use Opcode::*;
+
+        let xyn = |prefix: u16, x: u8, y: u8, n: u8| {
+            prefix << 12 | (x as u16 & 0xf) << 8 | (y as u16 & 0xf) << 4 | n as u16 & 0xf
+        };
+        let xkk = |prefix: u16, x: u8, byte: u8| prefix << 12 | (x as u16 & 0xf) << 8 | byte as u16;
+        let nnn = |prefix: u16, nnn: u16| prefix << 12 | nnn & 0x0fff;
+
+        let opcode = match *self {
+            Cls => 0x00e0,
+            Ret => 0x00ee,
+            Scd(n) => xyn(0x0, 0x0, 0xc, n),
+            Scu(n) => xyn(0x0, 0x0, 0xd, n),
+            Scr => 0x00fb,
+            Scl => 0x00fc,
+            Exit => 0x00fd,
+            Low => 0x00fe,
+            High => 0x00ff,
+            Jp(addr) => nnn(0x1, addr),
+            Call(addr) => nnn(0x2, addr),
+            Se { x, byte } => xkk(0x3, x, byte),
+            Sne { x, byte } => xkk(0x4, x, byte),
+            SeReg { x, y } => xyn(0x5, x, y, 0x0),
+            LdRangeInto { x, y } => xyn(0x5, x, y, 0x2),
+            LdRangeFrom { x, y } => xyn(0x5, x, y, 0x3),
+            Ld { x, byte } => xkk(0x6, x, byte),
+            Add { x, byte } => xkk(0x7, x, byte),
+            LdReg { x, y } => xyn(0x8, x, y, 0x0),
+            Or { x, y } => xyn(0x8, x, y, 0x1),
+            And { x, y } => xyn(0x8, x, y, 0x2),
+            Xor { x, y } => xyn(0x8, x, y, 0x3),
+            AddReg { x, y } => xyn(0x8, x, y, 0x4),
+            Sub { x, y } => xyn(0x8, x, y, 0x5),
+            Shr { x, y } => xyn(0x8, x, y, 0x6),
+            Subn { x, y } => xyn(0x8, x, y, 0x7),
+            Shl { x, y } => xyn(0x8, x, y, 0xe),
+            SneReg { x, y } => xyn(0x9, x, y, 0x0),
+            Ldi(addr) => nnn(0xa, addr),
+            Jp0(addr) => nnn(0xb, addr),
+            Rnd { x, byte } => xkk(0xc, x, byte),
+            Draw { x, y, n } => xyn(0xd, x, y, n),
+            Skp(x) => xkk(0xe, x, 0x9e),
+            Sknp(x) => xkk(0xe, x, 0xa1),
+            LdILong => 0xf000,
+            Plane(mask) => xkk(0xf, mask, 0x01),
+            LdAudio => 0xf002,
+            LdFromDt(x) => xkk(0xf, x, 0x07),
+            LdK(x) => xkk(0xf, x, 0x0a),
+            LdIntoDt(x) => xkk(0xf, x, 0x15),
+            LdIntoSt(x) => xkk(0xf, x, 0x18),
+            AddI(x) => xkk(0xf, x, 0x1e),
+            LdF(x) => xkk(0xf, x, 0x29),
+            LdHf(x) => xkk(0xf, x, 0x30),
+            LdB(x) => xkk(0xf, x, 0x33),
+            LdPitch(x) => xkk(0xf, x, 0x3a),
+            LdBatchInto(x) => xkk(0xf, x, 0x55),
+            LdBatchFrom(x) => xkk(0xf, x, 0x65),
+            LdRInto(x) => xkk(0xf, x, 0x75),
+            LdRFrom(x) => xkk(0xf, x, 0x85),
+        };
+
+        match Opcode::decode(opcode) {
+            Ok(decoded) if decoded == *self => Ok(opcode),
+            _ => Err(OpcodeError::InvalidOperands(*self)),
+        }
+    }
+
+    /// Bytes taken in memory, the XO-CHIP long load is followed by its address.
+    pub fn size(&self) -> u16 {
+        match self {
+            Opcode::LdILong => 4,
+            _ => 2,
+        }
+    }
+
+    /// Whether the interpreters of `platform` know the instruction.
+    pub fn is_supported_by(&self, platform: Platform) -> bool {
+        use Opcode::*;
+
+        match self {
+            Scd(_) | Scr | Scl | Exit | Low | High | LdHf(_) | LdRInto(_) | LdRFrom(_) => {
+                platform != Platform::Chip8
+            }
+            Scu(_)
+            | LdRangeInto { .. }
+            | LdRangeFrom { .. }
+            | LdILong
+            | Plane(_)
+            | LdAudio
+            | LdPitch(_) => platform == Platform::XoChip,
+            _ => true,
+        }
+    }
+
+    /// Cowgod's mnemonic, with the addresses written by `address`. The
+    /// address of the long load is not part of the opcode and is left out.
+    pub(crate) fn mnemonic(&self, address: impl Fn(u16) -> String) -> String {
+        use Opcode::*;
+
+        let byte = |byte: u8| format!("{:#04X}", byte).replace("0X", "0x");
+
+        match *self {
+            Cls => "CLS".to_string(),
+            Ret => "RET".to_string(),
+            Scd(n) => format!("SCD {}", n),
+            Scu(n) => format!("SCU {}", n),
+            Scr => "SCR".to_string(),
+            Scl => "SCL".to_string(),
+            Exit => "EXIT".to_string(),
+            Low => "LOW".to_string(),
+            High => "HIGH".to_string(),
+            Jp(addr) => format!("JP {}", address(addr)),
+            Call(addr) => format!("CALL {}", address(addr)),
+            Se { x, byte: kk } => format!("SE V{:X}, {}", x, byte(kk)),
+            Sne { x, byte: kk } => format!("SNE V{:X}, {}", x, byte(kk)),
+            SeReg { x, y } => format!("SE V{:X}, V{:X}", x, y),
+            LdRangeInto { x, y } => format!("SAVE V{:X}, V{:X}", x, y),
+            LdRangeFrom { x, y } => format!("LOAD V{:X}, V{:X}", x, y),
+            Ld { x, byte: kk } => format!("LD V{:X}, {}", x, byte(kk)),
+            Add { x, byte: kk } => format!("ADD V{:X}, {}", x, byte(kk)),
+            LdReg { x, y } => format!("LD V{:X}, V{:X}", x, y),
+            Or { x, y } => format!("OR V{:X}, V{:X}", x, y),
+            And { x, y } => format!("AND V{:X}, V{:X}", x, y),
+            Xor { x, y } => format!("XOR V{:X}, V{:X}", x, y),
+            AddReg { x, y } => format!("ADD V{:X}, V{:X}", x, y),
+            Sub { x, y } => format!("SUB V{:X}, V{:X}", x, y),
+            Shr { x, y } => format!("SHR V{:X}, V{:X}", x, y),
+            Subn { x, y } => format!("SUBN V{:X}, V{:X}", x, y),
+            Shl { x, y } => format!("SHL V{:X}, V{:X}", x, y),
+            SneReg { x, y } => format!("SNE V{:X}, V{:X}", x, y),
+            Ldi(addr) => format!("LD I, {}", address(addr)),
+            Jp0(addr) => format!("JP V0, {}", address(addr)),
+            Rnd { x, byte: kk } => format!("RND V{:X}, {}", x, byte(kk)),
+            Draw { x, y, n } => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+            Skp(x) => format!("SKP V{:X}", x),
+            Sknp(x) => format!("SKNP V{:X}", x),
+            LdILong => "LD I, LONG".to_string(),
+            Plane(mask) => format!("PLANE {}", mask),
+            LdAudio => "AUDIO".to_string(),
+            LdFromDt(x) => format!("LD V{:X}, DT", x),
+            LdK(x) => format!("LD V{:X}, K", x),
+            LdIntoDt(x) => format!("LD DT, V{:X}", x),
+            LdIntoSt(x) => format!("LD ST, V{:X}", x),
+            AddI(x) => format!("ADD I, V{:X}", x),
+            LdF(x) => format!("LD F, V{:X}", x),
+            LdHf(x) => format!("LD HF, V{:X}", x),
+            LdB(x) => format!("LD B, V{:X}", x),
+            LdPitch(x) => format!("PITCH V{:X}", x),
+            LdBatchInto(x) => format!("LD [I], V{:X}", x),
+            LdBatchFrom(x) => format!("LD V{:X}, [I]", x),
+            LdRInto(x) => format!("LD R, V{:X}", x),
+            LdRFrom(x) => format!("LD V{:X}, R", x),
+        }
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mnemonic(hex_address))
+    }
+}
+
+// 0x followed by 3 uppercase digits at least
+pub(crate) fn hex_address(address: u16) -> String {
+    format!("{:#05X}", address).replace("0X", "0x")
+}
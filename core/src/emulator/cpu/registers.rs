@@ -1,5 +1,8 @@
 use thiserror::Error;
 
+// return addresses the stack holds at most
+const STACK_SIZE: usize = 16;
+
 #[derive(Debug, Error)]
 pub enum RegistersError {
     #[error("Stack overflow error")]
@@ -10,6 +13,7 @@ pub enum RegistersError {
     InvalidRegister(u8),
 }
 
+#[derive(Debug, Clone)]
 pub struct Registers {
     v_0: u8,
     v_1: u8,
@@ -61,7 +65,7 @@ impl Default for Registers {
             dt: 0,
             st: 0,
             pc: 0x200,
-            stack: vec![],
+            stack: Vec::with_capacity(STACK_SIZE),
         }
     }
 }
@@ -100,6 +104,9 @@ impl Registers {
     pub fn pc(&self) -> u16 {
         self.pc
     }
+    pub fn stack(&self) -> &[u16] {
+        &self.stack
+    }
 
     pub fn set_register(&mut self, x: u8, byte: u8) -> Result<(), RegistersError> {
         match x {
@@ -187,6 +194,11 @@ impl Registers {
     pub fn set_pc(&mut self, pc: u16) {
         self.pc = pc;
     }
+    pub fn set_stack(&mut self, stack: Vec<u16>) {
+        // keeps the room reserved for the whole stack
+        self.stack.clear();
+        self.stack.extend(stack);
+    }
 
     pub fn pop_stack(&mut self) -> Result<(), RegistersError> {
         let addr = self.stack.last().ok_or(RegistersError::EmptyStack)?;
@@ -196,7 +208,7 @@ impl Registers {
     }
 
     pub fn inc_pc_by(&mut self, value: u16) {
-        self.pc += value
+        self.pc = self.pc.wrapping_add(value)
     }
 
     pub fn decrement_st(&mut self) {
@@ -212,7 +224,7 @@ impl Registers {
     }
 
     pub fn push_stack(&mut self, addr: u16) -> Result<(), RegistersError> {
-        if self.stack.len() == 16 {
+        if self.stack.len() == STACK_SIZE {
             return Err(RegistersError::StackOverflow);
         }
         self.stack.push(self.pc);
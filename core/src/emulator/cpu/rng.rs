@@ -0,0 +1,30 @@
+/// Xorshift128+ random number generator, whose state can be saved and restored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rng {
+    state: [u64; 2],
+}
+
+impl Rng {
+    /// At least one bit of the seed should be one.
+    pub fn new(seed: [u64; 2]) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn state(&self) -> [u64; 2] {
+        self.state
+    }
+
+    pub fn read_u64(&mut self) -> u64 {
+        let (mut x, y) = (self.state[0], self.state[1]);
+        self.state[0] = y;
+        x ^= x << 23;
+        x ^= x >> 17;
+        x ^= y ^ (y >> 26);
+        self.state[1] = x;
+        x.wrapping_add(y)
+    }
+
+    pub fn read_u8(&mut self) -> u8 {
+        self.read_u64() as u8
+    }
+}
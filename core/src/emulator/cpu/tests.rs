@@ -0,0 +1,588 @@
+use crate::emulator::cpu::opcode::Opcode;
+use crate::emulator::cpu::Cpu;
+use crate::emulator::display::Display;
+use crate::emulator::keyboard::KeyboardState;
+use crate::emulator::memory::Memory;
+use crate::emulator::quirks::{LoadStore, Quirks};
+
+// VX, VY, expected VX and expected VF
+type Case = (u8, u8, u8, u8);
+
+fn execute(opcode: u16, quirks: Quirks, registers: &[(u8, u8)]) -> Cpu {
+    let mut cpu = Cpu::new(Memory::default(), quirks);
+    for (x, value) in registers {
+        cpu.registers_mut().set_register(*x, *value).unwrap();
+    }
+
+    let opcode = Opcode::decode(opcode).unwrap();
+    cpu.execute(opcode, &mut Display::default(), &KeyboardState::default())
+        .unwrap();
+    cpu
+}
+
+fn register(cpu: &Cpu, x: u8) -> u8 {
+    cpu.registers().register(x).unwrap()
+}
+
+// `opcode` is 8xyN with x and y left to 0, every case is executed with VX and
+// VY distinct from VF, with VF as VX and with VF as VY
+fn check(opcode: u16, quirks: Quirks, cases: &[Case]) {
+    for (x_value, y_value, result, flag) in cases {
+        let cpu = execute(opcode | 0x120, quirks, &[(0x1, *x_value), (0x2, *y_value)]);
+        assert_eq!(
+            (register(&cpu, 0x1), register(&cpu, 0xf)),
+            (*result, *flag),
+            "{:04X} with V1 {:02X} and V2 {:02X}",
+            opcode | 0x120,
+            x_value,
+            y_value
+        );
+
+        // the flag is written after the result, replacing it
+        let cpu = execute(opcode | 0xf20, quirks, &[(0xf, *x_value), (0x2, *y_value)]);
+        assert_eq!(
+            register(&cpu, 0xf),
+            *flag,
+            "{:04X} with VF {:02X} and V2 {:02X}",
+            opcode | 0xf20,
+            x_value,
+            y_value
+        );
+
+        // VF is read as an operand before the flag is written
+        let cpu = execute(opcode | 0x1f0, quirks, &[(0x1, *x_value), (0xf, *y_value)]);
+        assert_eq!(
+            (register(&cpu, 0x1), register(&cpu, 0xf)),
+            (*result, *flag),
+            "{:04X} with V1 {:02X} and VF {:02X}",
+            opcode | 0x1f0,
+            x_value,
+            y_value
+        );
+    }
+}
+
+#[test]
+fn or() {
+    check(
+        0x8001,
+        Quirks::COSMAC_VIP,
+        &[
+            (0xf0, 0x0f, 0xff, 0),
+            (0x00, 0x00, 0x00, 0),
+            (0xa5, 0x81, 0xa5, 0),
+        ],
+    );
+}
+
+#[test]
+fn and() {
+    check(
+        0x8002,
+        Quirks::COSMAC_VIP,
+        &[
+            (0xf0, 0x3c, 0x30, 0),
+            (0xff, 0xff, 0xff, 0),
+            (0xaa, 0x55, 0x00, 0),
+        ],
+    );
+}
+
+#[test]
+fn xor() {
+    check(
+        0x8003,
+        Quirks::COSMAC_VIP,
+        &[
+            (0xff, 0x0f, 0xf0, 0),
+            (0xaa, 0xaa, 0x00, 0),
+            (0xaa, 0x55, 0xff, 0),
+        ],
+    );
+}
+
+#[test]
+fn logic_without_vf_reset_keeps_v_f() {
+    for opcode in &[0x8121, 0x8122, 0x8123] {
+        let cpu = execute(
+            *opcode,
+            Quirks::SCHIP_1_1,
+            &[(0x1, 0x0f), (0x2, 0xf0), (0xf, 0x5a)],
+        );
+        assert_eq!(register(&cpu, 0xf), 0x5a, "{:04X}", opcode);
+    }
+}
+
+#[test]
+fn add() {
+    check(
+        0x8004,
+        Quirks::COSMAC_VIP,
+        &[
+            (0x01, 0x02, 0x03, 0),
+            (0x7f, 0x80, 0xff, 0),
+            (0xff, 0x01, 0x00, 1),
+            (0x80, 0x80, 0x00, 1),
+            (0xff, 0xff, 0xfe, 1),
+        ],
+    );
+}
+
+#[test]
+fn sub() {
+    check(
+        0x8005,
+        Quirks::COSMAC_VIP,
+        &[
+            (0x05, 0x03, 0x02, 1),
+            (0x03, 0x03, 0x00, 1),
+            (0xff, 0x00, 0xff, 1),
+            (0x03, 0x05, 0xfe, 0),
+            (0x00, 0xff, 0x01, 0),
+        ],
+    );
+}
+
+#[test]
+fn shr_shifting_vy() {
+    check(
+        0x8006,
+        Quirks::COSMAC_VIP,
+        &[
+            (0x00, 0x03, 0x01, 1),
+            (0xff, 0x02, 0x01, 0),
+            (0x00, 0x80, 0x40, 0),
+            (0x00, 0xff, 0x7f, 1),
+        ],
+    );
+}
+
+#[test]
+fn shr_shifting_vx() {
+    check(
+        0x8006,
+        Quirks::SCHIP_1_1,
+        &[
+            (0x03, 0x00, 0x01, 1),
+            (0x02, 0xff, 0x01, 0),
+            (0x80, 0x00, 0x40, 0),
+            (0xff, 0x00, 0x7f, 1),
+        ],
+    );
+}
+
+#[test]
+fn subn() {
+    check(
+        0x8007,
+        Quirks::COSMAC_VIP,
+        &[
+            (0x03, 0x05, 0x02, 1),
+            (0x03, 0x03, 0x00, 1),
+            (0x00, 0xff, 0xff, 1),
+            (0x05, 0x03, 0xfe, 0),
+            (0xff, 0x00, 0x01, 0),
+        ],
+    );
+}
+
+#[test]
+fn shl_shifting_vy() {
+    check(
+        0x800e,
+        Quirks::COSMAC_VIP,
+        &[
+            (0x00, 0x81, 0x02, 1),
+            (0xff, 0x40, 0x80, 0),
+            (0x00, 0x20, 0x40, 0),
+            (0x00, 0x7f, 0xfe, 0),
+            (0x00, 0xff, 0xfe, 1),
+        ],
+    );
+}
+
+#[test]
+fn shl_shifting_vx() {
+    check(
+        0x800e,
+        Quirks::SCHIP_1_1,
+        &[
+            (0x81, 0x00, 0x02, 1),
+            (0x40, 0xff, 0x80, 0),
+            (0x20, 0x00, 0x40, 0),
+            (0x7f, 0x00, 0xfe, 0),
+            (0xff, 0x00, 0xfe, 1),
+        ],
+    );
+}
+
+// executes every opcode in order on the same machine
+fn run(cpu: &mut Cpu, display: &mut Display, opcodes: &[u16]) {
+    for opcode in opcodes {
+        let opcode = Opcode::decode(*opcode).unwrap();
+        cpu.execute(opcode, display, &KeyboardState::default())
+            .unwrap();
+    }
+}
+
+// coordinates of the pixels lit on any plane, row by row
+fn lit(display: &Display) -> Vec<(usize, usize)> {
+    (0..display.height())
+        .flat_map(|y| (0..display.width()).map(move |x| (x, y)))
+        .filter(|(x, y)| display.pixel(*x, *y))
+        .collect()
+}
+
+fn schip() -> (Cpu, Display) {
+    (
+        Cpu::new(Memory::default(), Quirks::SCHIP_1_1),
+        Display::default(),
+    )
+}
+
+#[test]
+fn high_and_low_switch_resolution_and_clear() {
+    let (mut cpu, mut display) = schip();
+    display.set_pixel(3, 4, true);
+
+    run(&mut cpu, &mut display, &[0x00ff]);
+    assert!(display.is_hires());
+    assert_eq!((display.width(), display.height()), (128, 64));
+    assert!(lit(&display).is_empty());
+
+    display.set_pixel(100, 50, true);
+    run(&mut cpu, &mut display, &[0x00fe]);
+    assert!(!display.is_hires());
+    assert_eq!((display.width(), display.height()), (64, 32));
+    assert!(lit(&display).is_empty());
+}
+
+#[test]
+fn scroll_down() {
+    let (mut cpu, mut display) = schip();
+    run(&mut cpu, &mut display, &[0x00ff]);
+    display.set_pixel(10, 0, true);
+    display.set_pixel(20, 62, true);
+
+    // the pixels pushed past the bottom edge are lost
+    run(&mut cpu, &mut display, &[0x00c3]);
+    assert_eq!(lit(&display), vec![(10, 3)]);
+}
+
+#[test]
+fn scroll_right_and_left() {
+    let (mut cpu, mut display) = schip();
+    run(&mut cpu, &mut display, &[0x00ff]);
+    display.set_pixel(0, 1, true);
+    display.set_pixel(125, 2, true);
+
+    run(&mut cpu, &mut display, &[0x00fb]);
+    assert_eq!(lit(&display), vec![(4, 1)]);
+
+    display.set_pixel(1, 3, true);
+    run(&mut cpu, &mut display, &[0x00fc]);
+    assert_eq!(lit(&display), vec![(0, 1)]);
+}
+
+#[test]
+fn draw_16x16_sprite_with_collision() {
+    let (mut cpu, mut display) = schip();
+    run(&mut cpu, &mut display, &[0x00ff]);
+
+    // a 16x16 square, two bytes per row
+    for offset in 0..32 {
+        cpu.memory_mut().write_8(0x300 + offset, 0xff);
+    }
+    cpu.registers_mut().set_i(0x300);
+    cpu.registers_mut().set_register(0x1, 20).unwrap();
+    cpu.registers_mut().set_register(0x2, 10).unwrap();
+
+    run(&mut cpu, &mut display, &[0xd120]);
+    let pixels = lit(&display);
+    assert_eq!(pixels.len(), 256);
+    assert_eq!(pixels.first(), Some(&(20, 10)));
+    assert_eq!(pixels.last(), Some(&(35, 25)));
+    assert_eq!(register(&cpu, 0xf), 0);
+
+    // drawing it again erases every pixel
+    run(&mut cpu, &mut display, &[0xd120]);
+    assert!(lit(&display).is_empty());
+    assert_eq!(register(&cpu, 0xf), 1);
+}
+
+#[test]
+fn ld_hf_points_to_big_font() {
+    let (mut cpu, mut display) = schip();
+    cpu.registers_mut().set_register(0x4, 0x3).unwrap();
+
+    run(&mut cpu, &mut display, &[0xf430]);
+    let i = cpu.registers().i();
+    let digit: Vec<u8> = (0..10)
+        .map(|offset| cpu.memory().read_8(i + offset))
+        .collect();
+    assert_eq!(
+        digit,
+        vec![0xff, 0xff, 0x03, 0x03, 0xff, 0xff, 0x03, 0x03, 0xff, 0xff]
+    );
+}
+
+#[test]
+fn rpl_flags_save_and_restore_registers() {
+    let (mut cpu, mut display) = schip();
+    for x in 0..16 {
+        cpu.registers_mut().set_register(x, 0x10 + x).unwrap();
+    }
+
+    // saves V0 to V3 only
+    run(&mut cpu, &mut display, &[0xf375]);
+    assert_eq!(&cpu.rpl_flags()[..5], &[0x10, 0x11, 0x12, 0x13, 0x00]);
+
+    for x in 0..16 {
+        cpu.registers_mut().set_register(x, 0).unwrap();
+    }
+    run(&mut cpu, &mut display, &[0xf285]);
+    let registers: Vec<u8> = (0..5).map(|x| register(&cpu, x)).collect();
+    assert_eq!(registers, vec![0x10, 0x11, 0x12, 0x00, 0x00]);
+}
+
+#[test]
+fn exit_stops_the_cpu() {
+    let (mut cpu, mut display) = schip();
+    run(&mut cpu, &mut display, &[0x00fd]);
+    assert!(cpu.is_exited());
+}
+
+// Cowgod's interpretation with a single quirk changed
+fn quirks_with(change: impl Fn(&mut Quirks)) -> Quirks {
+    let mut quirks = Quirks::COWGOD;
+    change(&mut quirks);
+    quirks
+}
+
+#[test]
+fn quirk_shift_uses_vy() {
+    let registers = [(0x1, 0x04), (0x2, 0x10)];
+    for (shift_uses_vy, shr, shl) in &[(true, 0x08, 0x20), (false, 0x02, 0x08)] {
+        let quirks = quirks_with(|quirks| quirks.shift_uses_vy = *shift_uses_vy);
+        assert_eq!(register(&execute(0x8126, quirks, &registers), 0x1), *shr);
+        assert_eq!(register(&execute(0x812e, quirks, &registers), 0x1), *shl);
+    }
+}
+
+#[test]
+fn quirk_load_store() {
+    let cases = [
+        (LoadStore::Unchanged, 0x300),
+        (LoadStore::IncrementByX, 0x302),
+        (LoadStore::IncrementByXPlusOne, 0x303),
+    ];
+    for (load_store, i) in &cases {
+        let quirks = quirks_with(|quirks| quirks.load_store = *load_store);
+        for opcode in &[0xf255, 0xf265] {
+            let mut cpu = Cpu::new(Memory::default(), quirks);
+            cpu.registers_mut().set_i(0x300);
+            run(&mut cpu, &mut Display::default(), &[*opcode]);
+            assert_eq!(cpu.registers().i(), *i, "{:04X} {:?}", opcode, load_store);
+        }
+    }
+}
+
+#[test]
+fn quirk_clip_sprites() {
+    for (clip_sprites, pixels) in &[(true, 8), (false, 32)] {
+        let quirks = quirks_with(|quirks| quirks.clip_sprites = *clip_sprites);
+        let mut cpu = Cpu::new(Memory::default(), quirks);
+        let mut display = Display::default();
+        for offset in 0..4 {
+            cpu.memory_mut().write_8(0x300 + offset, 0xff);
+        }
+        cpu.registers_mut().set_i(0x300);
+        cpu.registers_mut().set_register(0x1, 60).unwrap();
+        cpu.registers_mut().set_register(0x2, 30).unwrap();
+
+        // an 8x4 sprite at the bottom right corner
+        run(&mut cpu, &mut display, &[0xd124]);
+        assert_eq!(lit(&display).len(), *pixels);
+        assert_eq!(display.pixel(0, 0), !clip_sprites);
+        assert!(display.pixel(63, 31));
+    }
+}
+
+#[test]
+fn quirk_jump_uses_vx() {
+    for (jump_uses_vx, pc) in &[(true, 0x244), (false, 0x235)] {
+        let quirks = quirks_with(|quirks| quirks.jump_uses_vx = *jump_uses_vx);
+        let cpu = execute(0xb234, quirks, &[(0x0, 0x01), (0x2, 0x10)]);
+        assert_eq!(cpu.registers().pc(), *pc);
+    }
+}
+
+#[test]
+fn quirk_vf_reset() {
+    for (vf_reset, flag) in &[(true, 0x00), (false, 0x5a)] {
+        let quirks = quirks_with(|quirks| quirks.vf_reset = *vf_reset);
+        for opcode in &[0x8121, 0x8122, 0x8123] {
+            let cpu = execute(*opcode, quirks, &[(0x1, 0x0f), (0x2, 0xf0), (0xf, 0x5a)]);
+            assert_eq!(register(&cpu, 0xf), *flag, "{:04X}", opcode);
+        }
+    }
+}
+
+#[test]
+fn quirk_display_wait() {
+    for display_wait in &[true, false] {
+        let quirks = quirks_with(|quirks| quirks.display_wait = *display_wait);
+        let cpu = execute(0xd121, quirks, &[]);
+        assert_eq!(cpu.is_waiting_display(), *display_wait);
+    }
+}
+
+// machine with `words` written from 0x200, where the program counter starts
+fn program(words: &[u16]) -> Cpu {
+    let mut cpu = Cpu::new(Memory::default(), Quirks::COWGOD);
+    for (index, word) in words.iter().enumerate() {
+        for (offset, byte) in word.to_be_bytes().iter().enumerate() {
+            cpu.memory_mut()
+                .write_8(0x200 + 2 * index as u16 + offset as u16, *byte);
+        }
+    }
+    cpu
+}
+
+// executes the next `count` instructions from the program counter
+fn step(cpu: &mut Cpu, display: &mut Display, count: usize) {
+    for _ in 0..count {
+        cpu.step_instruction(display, &KeyboardState::default())
+            .unwrap();
+    }
+}
+
+#[test]
+fn long_load_sets_a_16_bit_i() {
+    let mut cpu = program(&[0xf000, 0xabcd, 0x6001]);
+    let mut display = Display::default();
+
+    step(&mut cpu, &mut display, 1);
+    assert_eq!(cpu.registers().i(), 0xabcd);
+    assert_eq!(cpu.registers().pc(), 0x204);
+
+    // the address is not executed as an instruction
+    step(&mut cpu, &mut display, 1);
+    assert_eq!(register(&cpu, 0x0), 0x01);
+}
+
+#[test]
+fn skips_step_over_long_loads() {
+    // V0, V1 and the keys are 0 and VF is 1, each skip is taken
+    for opcode in &[0x3000, 0x4001, 0x5010, 0x9f10, 0xe0a1] {
+        let mut cpu = program(&[*opcode, 0xf000, 0x1234, 0x6001]);
+        cpu.registers_mut().set_register(0xf, 0x01).unwrap();
+        step(&mut cpu, &mut Display::default(), 1);
+        assert_eq!(cpu.registers().pc(), 0x206, "{:04X}", opcode);
+    }
+
+    let mut cpu = program(&[0x3000, 0x6001, 0x6002]);
+    step(&mut cpu, &mut Display::default(), 1);
+    assert_eq!(cpu.registers().pc(), 0x204);
+
+    let mut cpu = program(&[0x3001, 0xf000, 0x1234]);
+    step(&mut cpu, &mut Display::default(), 1);
+    assert_eq!(cpu.registers().pc(), 0x202);
+}
+
+#[test]
+fn register_ranges_save_and_load_in_both_orders() {
+    let cases = [(0x5132, [0x11, 0x12, 0x13]), (0x5312, [0x13, 0x12, 0x11])];
+    for (opcode, bytes) in &cases {
+        let mut cpu = program(&[*opcode]);
+        for x in 0..16 {
+            cpu.registers_mut().set_register(x, 0x10 + x).unwrap();
+        }
+        cpu.registers_mut().set_i(0x300);
+
+        step(&mut cpu, &mut Display::default(), 1);
+        let memory: Vec<u8> = (0..4)
+            .map(|offset| cpu.memory().read_8(0x300 + offset))
+            .collect();
+        assert_eq!(memory[..3], bytes[..], "{:04X}", opcode);
+        assert_eq!(memory[3], 0x00);
+        assert_eq!(cpu.registers().i(), 0x300);
+    }
+
+    let cases = [(0x5133, [0x21, 0x22, 0x23]), (0x5313, [0x23, 0x22, 0x21])];
+    for (opcode, registers) in &cases {
+        let mut cpu = program(&[*opcode]);
+        for offset in 0..4 {
+            cpu.memory_mut()
+                .write_8(0x300 + offset, 0x21 + offset as u8);
+        }
+        cpu.registers_mut().set_i(0x300);
+
+        step(&mut cpu, &mut Display::default(), 1);
+        let values: Vec<u8> = (0..5).map(|x| register(&cpu, x)).collect();
+        assert_eq!(values[1..4], registers[..], "{:04X}", opcode);
+        assert_eq!((values[0], values[4]), (0x00, 0x00));
+        assert_eq!(cpu.registers().i(), 0x300);
+    }
+}
+
+#[test]
+fn planes_draw_the_sprites_following_each_other() {
+    // Fn01 selecting planes and Dxy1 drawing a one row sprite, at 0, 0
+    let mut cpu = program(&[
+        0xf301, 0xd001, 0xf201, 0xd001, 0xf301, 0xd001, 0xf001, 0xd001,
+    ]);
+    let mut display = Display::default();
+    for (offset, byte) in [0x80, 0x40, 0xff].iter().enumerate() {
+        cpu.memory_mut().write_8(0x300 + offset as u16, *byte);
+    }
+    cpu.registers_mut().set_i(0x300);
+
+    // both planes read a byte each, the third one is never drawn
+    step(&mut cpu, &mut display, 2);
+    assert_eq!(lit(&display), vec![(0, 0), (1, 0)]);
+    assert!(display.plane_pixel(0, 0, 0) && !display.plane_pixel(1, 0, 0));
+    assert!(display.plane_pixel(1, 1, 0) && !display.plane_pixel(0, 1, 0));
+    assert_eq!(register(&cpu, 0xf), 0);
+
+    // the second plane alone reads the first byte, erasing nothing
+    step(&mut cpu, &mut display, 2);
+    assert!(display.plane_pixel(1, 0, 0) && display.plane_pixel(1, 1, 0));
+    assert_eq!(register(&cpu, 0xf), 0);
+
+    // a pixel erased on any plane collides
+    step(&mut cpu, &mut display, 2);
+    assert_eq!(lit(&display), vec![(0, 0)]);
+    assert!(!display.plane_pixel(0, 0, 0) && display.plane_pixel(1, 0, 0));
+    assert_eq!(register(&cpu, 0xf), 1);
+
+    // with no plane selected nothing is drawn
+    step(&mut cpu, &mut display, 2);
+    assert_eq!(lit(&display), vec![(0, 0)]);
+    assert_eq!(register(&cpu, 0xf), 0);
+}
+
+#[test]
+fn audio_pattern_is_loaded_from_i() {
+    let mut cpu = program(&[0xf002]);
+    for offset in 0..17 {
+        cpu.memory_mut().write_8(0x2ff + offset, offset as u8);
+    }
+    cpu.registers_mut().set_i(0x300);
+
+    step(&mut cpu, &mut Display::default(), 1);
+    let buffer: Vec<u8> = (1..17).collect();
+    assert_eq!(cpu.audio_pattern().buffer()[..], buffer[..]);
+    assert_eq!(cpu.audio_pattern().pitch(), 64);
+    assert_eq!(cpu.registers().i(), 0x300);
+}
+
+#[test]
+fn pitch_is_loaded_from_v_x() {
+    let mut cpu = program(&[0xf53a]);
+    cpu.registers_mut().set_register(0x5, 112).unwrap();
+
+    step(&mut cpu, &mut Display::default(), 1);
+    assert_eq!(cpu.audio_pattern().pitch(), 112);
+    assert_eq!(cpu.audio_pattern().sample_rate(), 8000.0);
+}
@@ -0,0 +1,101 @@
+use crate::emulator::cpu::opcode::{hex_address, Opcode};
+use crate::emulator::cpu::registers::Registers;
+use crate::emulator::memory::Memory;
+use std::io;
+use std::io::Write;
+use std::ops::{Range, RangeInclusive};
+
+/// Writes a line for every instruction executed, with the state of the
+/// registers before executing it:
+///
+/// ```text
+/// 0000000012 0218 8124 ADD V1, V2           V 05 0A 00 00 00 00 00 00 00 00 00 00 00 00 00 00 I 02EA SP 01 DT 00 ST 00
+/// ```
+///
+/// The fields are the index of the instruction, the program counter, the
+/// opcode, its mnemonic, V0-VF, I, the depth of the stack and the timers, all
+/// of them with a fixed width so traces can be diffed line by line.
+pub struct Tracer {
+    output: Box<dyn Write>,
+    addresses: RangeInclusive<u16>,
+    instructions: Range<u64>,
+    // instructions executed since tracing started
+    count: u64,
+}
+
+impl Tracer {
+    pub fn new(output: Box<dyn Write>) -> Self {
+        Self {
+            output,
+            addresses: 0..=u16::MAX,
+            instructions: 0..u64::MAX,
+            count: 0,
+        }
+    }
+
+    /// Only traces the instructions at these addresses.
+    pub fn addresses(mut self, addresses: RangeInclusive<u16>) -> Self {
+        self.addresses = addresses;
+        self
+    }
+
+    /// Only traces the instructions executed with these indexes, counted
+    /// from 0 whatever their address.
+    pub fn instructions(mut self, instructions: Range<u64>) -> Self {
+        self.instructions = instructions;
+        self
+    }
+
+    /// Instructions executed since tracing started.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+
+    // `pc` is the address of `opcode`, the registers are not updated yet
+    pub(crate) fn trace(
+        &mut self,
+        pc: u16,
+        opcode: u16,
+        registers: &Registers,
+        memory: &Memory,
+    ) -> io::Result<()> {
+        let index = self.count;
+        self.count += 1;
+
+        if !self.addresses.contains(&pc) || !self.instructions.contains(&index) {
+            return Ok(());
+        }
+
+        // unknown opcodes make the cpu fail right after the trace
+        let mnemonic = match Opcode::decode(opcode) {
+            Ok(Opcode::LdILong) => {
+                let address = hex_address(memory.read_16(pc.wrapping_add(2)));
+                format!("{} {}", Opcode::LdILong, address)
+            }
+            Ok(decoded) => decoded.to_string(),
+            Err(_) => "???".to_string(),
+        };
+
+        let v: Vec<String> = (0..16)
+            .map(|x| format!("{:02X}", registers.register(x).unwrap_or_default()))
+            .collect();
+
+        writeln!(
+            self.output,
+            "{:010} {:04X} {:04X} {:<20} V {} I {:04X} SP {:02X} DT {:02X} ST {:02X}",
+            index,
+            pc,
+            opcode,
+            mnemonic,
+            v.join(" "),
+            registers.i(),
+            registers.stack().len(),
+            registers.dt(),
+            registers.st(),
+        )
+    }
+}
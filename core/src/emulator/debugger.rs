@@ -0,0 +1,700 @@
+use crate::emulator::chip8::Chip8;
+use crate::emulator::cpu::opcode::Opcode;
+use crate::emulator::quirks::LoadStore;
+use crate::emulator::EmulatorError;
+use std::collections::BTreeSet;
+
+/// Storage watched by a watchpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    Memory(u16),
+    /// V register, 0x0 to 0xF.
+    Register(u8),
+    I,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl AccessKind {
+    fn includes(self, access: AccessKind) -> bool {
+        self == AccessKind::ReadWrite || self == access
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub location: Location,
+    pub kind: AccessKind,
+}
+
+/// Why the execution stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HaltReason {
+    /// The instruction at this address, not executed yet, has a breakpoint.
+    Breakpoint(u16),
+    /// The instruction at `pc` read or wrote a watched location.
+    Watchpoint {
+        pc: u16,
+        location: Location,
+        access: AccessKind,
+    },
+    /// A step into, over or out is complete.
+    Step,
+    /// Paused by the frontend.
+    Pause,
+    /// The program stopped the interpreter through `00FD`.
+    Exited,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Execution {
+    Running,
+    Paused,
+    StepInto,
+    // until the stack is back to this depth
+    StepOver { depth: usize },
+    // until the stack is below this depth
+    StepOut { depth: usize },
+}
+
+/// Drives a machine one instruction at a time, halting on breakpoints,
+/// watchpoints and completed steps. While the debugger is paused the
+/// machine is left untouched and can be inspected.
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+    watchpoints: Vec<Watchpoint>,
+    execution: Execution,
+    halt_reason: Option<HaltReason>,
+    // instructions executed in the current frame, the timers are refreshed
+    // once the whole frame has been executed
+    frame_instructions: usize,
+    // address the execution halted at, its breakpoint is ignored once so
+    // that resuming moves forward
+    reported_breakpoint: Option<u16>,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self {
+            breakpoints: BTreeSet::new(),
+            watchpoints: vec![],
+            execution: Execution::Running,
+            halt_reason: None,
+            frame_instructions: 0,
+            reported_breakpoint: None,
+        }
+    }
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns false when the breakpoint was already set.
+    pub fn add_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.insert(addr)
+    }
+
+    /// Returns false when there was no breakpoint at `addr`.
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().cloned()
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        if !self.watchpoints.contains(&watchpoint) {
+            self.watchpoints.push(watchpoint);
+        }
+    }
+
+    /// Returns false when the watchpoint was not set.
+    pub fn remove_watchpoint(&mut self, watchpoint: &Watchpoint) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints.retain(|other| other != watchpoint);
+        self.watchpoints.len() != len
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.execution == Execution::Paused
+    }
+
+    /// Why the execution last stopped, `None` while running.
+    pub fn halt_reason(&self) -> Option<HaltReason> {
+        self.halt_reason
+    }
+
+    pub fn pause(&mut self, chip8: &Chip8) {
+        self.halt(HaltReason::Pause, chip8.registers().pc());
+    }
+
+    pub fn resume(&mut self) {
+        self.start(Execution::Running);
+    }
+
+    /// Executes the next instruction.
+    pub fn step_into(&mut self) {
+        self.start(Execution::StepInto);
+    }
+
+    /// Executes the next instruction, running a called subroutine up to its return.
+    pub fn step_over(&mut self, chip8: &Chip8) {
+        let depth = chip8.registers().stack().len();
+        self.start(Execution::StepOver { depth });
+    }
+
+    /// Runs up to the return from the current subroutine, at the top level
+    /// it only stops on another event.
+    pub fn step_out(&mut self, chip8: &Chip8) {
+        let depth = chip8.registers().stack().len();
+        self.start(Execution::StepOut { depth });
+    }
+
+    /// Emulates the rest of the current 60Hz frame, unless paused. Returns the
+    /// reason when the execution halts before the end of the frame.
+    pub fn run_frame(&mut self, chip8: &mut Chip8) -> Result<Option<HaltReason>, EmulatorError> {
+        while !self.is_paused() {
+            if self.frame_instructions >= chip8.instructions_per_frame() {
+                self.frame_instructions = 0;
+                chip8.tick_timers()?;
+                return Ok(None);
+            }
+
+            if let Some(reason) = self.execute(chip8)? {
+                self.halt(reason, chip8.registers().pc());
+                return Ok(Some(reason));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn start(&mut self, execution: Execution) {
+        self.execution = execution;
+        self.halt_reason = None;
+    }
+
+    fn halt(&mut self, reason: HaltReason, pc: u16) {
+        self.execution = Execution::Paused;
+        self.halt_reason = Some(reason);
+        self.reported_breakpoint = Some(pc);
+    }
+
+    fn execute(&mut self, chip8: &mut Chip8) -> Result<Option<HaltReason>, EmulatorError> {
+        let pc = chip8.registers().pc();
+        if self.breakpoints.contains(&pc) && self.reported_breakpoint.take() != Some(pc) {
+            return Ok(Some(HaltReason::Breakpoint(pc)));
+        }
+        self.reported_breakpoint = None;
+
+        let accesses = if self.watchpoints.is_empty() {
+            vec![]
+        } else {
+            accesses(chip8)
+        };
+
+        chip8.step()?;
+        self.frame_instructions += 1;
+
+        if chip8.is_exited() {
+            return Ok(Some(HaltReason::Exited));
+        }
+
+        for (location, access) in accesses {
+            let is_watched = self.watchpoints.iter().any(|watchpoint| {
+                watchpoint.location == location && watchpoint.kind.includes(access)
+            });
+
+            if is_watched {
+                return Ok(Some(HaltReason::Watchpoint {
+                    pc,
+                    location,
+                    access,
+                }));
+            }
+        }
+
+        let depth = chip8.registers().stack().len();
+        let is_step_complete = match self.execution {
+            Execution::StepInto => true,
+            Execution::StepOver { depth: start } => depth <= start,
+            Execution::StepOut { depth: start } => depth < start,
+            Execution::Running | Execution::Paused => false,
+        };
+
+        Ok(if is_step_complete {
+            Some(HaltReason::Step)
+        } else {
+            None
+        })
+    }
+}
+
+// Locations the next instruction reads and writes, worked out from the
+// state of the machine before it is executed.
+fn accesses(chip8: &Chip8) -> Vec<(Location, AccessKind)> {
+    use AccessKind::{Read, Write};
+    use Location::{Register, I};
+
+    let cpu = chip8.cpu();
+    let registers = cpu.registers();
+    let mut accesses = vec![];
+
+    if cpu.is_exited() || cpu.is_waiting_display() {
+        return accesses;
+    }
+
+    if cpu.is_waiting_key() {
+        if chip8.keyboard_state().get_key_pressed().is_some() {
+            accesses.push((Register(cpu.waiting_key_register()), Write));
+        }
+        return accesses;
+    }
+
+    let memory = |count: u16, access: AccessKind| {
+        (0..count).map(move |offset| (Location::Memory(registers.i().wrapping_add(offset)), access))
+    };
+
+    let opcode = match Opcode::decode(cpu.memory().read_16(registers.pc())) {
+        Ok(opcode) => opcode,
+        // the cpu stops on unknown opcodes without touching anything
+        Err(_) => return accesses,
+    };
+
+    let quirks = cpu.quirks();
+    let batch_write_i = quirks.load_store != LoadStore::Unchanged;
+    let range = |x: u8, y: u8| -> Vec<u8> {
+        if x <= y {
+            (x..=y).collect()
+        } else {
+            (y..=x).rev().collect()
+        }
+    };
+
+    match opcode {
+        Opcode::Se { x, .. } | Opcode::Sne { x, .. } | Opcode::Skp(x) | Opcode::Sknp(x) => {
+            accesses.push((Register(x), Read))
+        }
+        Opcode::SeReg { x, y } | Opcode::SneReg { x, y } => {
+            accesses.extend(&[(Register(x), Read), (Register(y), Read)])
+        }
+        Opcode::LdRangeInto { x, y } => {
+            let range = range(x, y);
+            accesses.push((I, Read));
+            accesses.extend(range.iter().map(|x| (Register(*x), Read)));
+            accesses.extend(memory(range.len() as u16, Write));
+        }
+        Opcode::LdRangeFrom { x, y } => {
+            let range = range(x, y);
+            accesses.push((I, Read));
+            accesses.extend(memory(range.len() as u16, Read));
+            accesses.extend(range.iter().map(|x| (Register(*x), Write)));
+        }
+        Opcode::Ld { x, .. } | Opcode::Rnd { x, .. } => accesses.push((Register(x), Write)),
+        Opcode::Add { x, .. } => accesses.extend(&[(Register(x), Read), (Register(x), Write)]),
+        Opcode::LdReg { x, y } => accesses.extend(&[(Register(y), Read), (Register(x), Write)]),
+        Opcode::Or { x, y } | Opcode::And { x, y } | Opcode::Xor { x, y } => {
+            accesses.extend(&[
+                (Register(x), Read),
+                (Register(y), Read),
+                (Register(x), Write),
+            ]);
+            if quirks.vf_reset {
+                accesses.push((Register(0xf), Write));
+            }
+        }
+        Opcode::AddReg { x, y } | Opcode::Sub { x, y } | Opcode::Subn { x, y } => {
+            accesses.extend(&[(Register(x), Read), (Register(y), Read)]);
+            accesses.extend(&[(Register(x), Write), (Register(0xf), Write)]);
+        }
+        Opcode::Shr { x, y } | Opcode::Shl { x, y } => {
+            let operand = if quirks.shift_uses_vy { y } else { x };
+            accesses.push((Register(operand), Read));
+            accesses.extend(&[(Register(x), Write), (Register(0xf), Write)]);
+        }
+        Opcode::Ldi(_) | Opcode::LdILong => accesses.push((I, Write)),
+        Opcode::Jp0(addr) if quirks.jump_uses_vx => {
+            accesses.push((Register((addr >> 8) as u8), Read))
+        }
+        Opcode::Jp0(_) => accesses.push((Register(0x0), Read)),
+        Opcode::Draw { x, y, n } => {
+            let display = chip8.display();
+            let sprite_size = match n {
+                0 => 32,
+                rows => rows as u16,
+            };
+
+            accesses.extend(&[(Register(x), Read), (Register(y), Read), (I, Read)]);
+            accesses.extend(memory(
+                sprite_size * display.selected_planes().count() as u16,
+                Read,
+            ));
+            accesses.push((Register(0xf), Write));
+        }
+        Opcode::LdAudio => {
+            accesses.push((I, Read));
+            accesses.extend(memory(16, Read));
+        }
+        Opcode::LdFromDt(x) => accesses.push((Register(x), Write)),
+        Opcode::LdIntoDt(x) | Opcode::LdIntoSt(x) | Opcode::LdPitch(x) => {
+            accesses.push((Register(x), Read))
+        }
+        Opcode::AddI(x) => accesses.extend(&[(Register(x), Read), (I, Read), (I, Write)]),
+        Opcode::LdF(x) | Opcode::LdHf(x) => accesses.extend(&[(Register(x), Read), (I, Write)]),
+        Opcode::LdB(x) => {
+            accesses.extend(&[(Register(x), Read), (I, Read)]);
+            accesses.extend(memory(3, Write));
+        }
+        Opcode::LdBatchInto(x) => {
+            accesses.push((I, Read));
+            accesses.extend((0..=x).map(|x| (Register(x), Read)));
+            accesses.extend(memory(x as u16 + 1, Write));
+            if batch_write_i {
+                accesses.push((I, Write));
+            }
+        }
+        Opcode::LdBatchFrom(x) => {
+            accesses.push((I, Read));
+            accesses.extend(memory(x as u16 + 1, Read));
+            accesses.extend((0..=x).map(|x| (Register(x), Write)));
+            if batch_write_i {
+                accesses.push((I, Write));
+            }
+        }
+        Opcode::LdRInto(x) => accesses.extend((0..=x).map(|x| (Register(x), Read))),
+        Opcode::LdRFrom(x) => accesses.extend((0..=x).map(|x| (Register(x), Write))),
+        _ => {}
+    }
+
+    accesses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::keyboard::KeyboardState;
+    use crate::emulator::quirks::Quirks;
+
+    // 200: LD V0, 1
+    // 202: CALL 208
+    // 204: LD V1, 2
+    // 206: JP 206
+    // 208: LD V2, 3
+    // 20A: RET
+    const PROGRAM: &[u16] = &[0x6001, 0x2208, 0x6102, 0x1206, 0x6203, 0x00EE];
+
+    fn build(program: &[u16]) -> Chip8 {
+        let rom = program
+            .iter()
+            .flat_map(|word| word.to_be_bytes().to_vec())
+            .collect::<Vec<u8>>();
+        Chip8::builder().rom(&rom).build().unwrap()
+    }
+
+    fn register(chip8: &Chip8, x: u8) -> u8 {
+        chip8.registers().register(x).unwrap()
+    }
+
+    #[test]
+    fn breakpoint_halts_before_the_instruction_then_resumes() {
+        let mut chip8 = build(PROGRAM);
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x202);
+        debugger.add_breakpoint(0x204);
+
+        let reason = debugger.run_frame(&mut chip8).unwrap();
+        assert_eq!(reason, Some(HaltReason::Breakpoint(0x202)));
+        assert_eq!(debugger.halt_reason(), reason);
+        assert!(debugger.is_paused());
+        assert_eq!(chip8.registers().pc(), 0x202);
+        assert_eq!(register(&chip8, 0x0), 1);
+
+        // a paused machine is left untouched
+        assert_eq!(debugger.run_frame(&mut chip8).unwrap(), None);
+        assert_eq!(chip8.registers().pc(), 0x202);
+
+        debugger.resume();
+        assert_eq!(debugger.halt_reason(), None);
+        let reason = debugger.run_frame(&mut chip8).unwrap();
+        assert_eq!(reason, Some(HaltReason::Breakpoint(0x204)));
+        assert_eq!(register(&chip8, 0x2), 3);
+
+        debugger.resume();
+        assert_eq!(debugger.run_frame(&mut chip8).unwrap(), None);
+        assert_eq!(register(&chip8, 0x1), 2);
+        assert_eq!(chip8.registers().pc(), 0x206);
+    }
+
+    #[test]
+    fn resuming_after_a_step_onto_a_breakpoint_moves_forward() {
+        let mut chip8 = build(PROGRAM);
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x202);
+
+        debugger.step_into();
+        assert_eq!(
+            debugger.run_frame(&mut chip8).unwrap(),
+            Some(HaltReason::Step)
+        );
+        assert_eq!(chip8.registers().pc(), 0x202);
+
+        debugger.resume();
+        assert_eq!(debugger.run_frame(&mut chip8).unwrap(), None);
+        assert_eq!(chip8.registers().pc(), 0x206);
+    }
+
+    #[test]
+    fn resuming_after_a_pause_on_a_breakpoint_moves_forward() {
+        let mut chip8 = build(PROGRAM);
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x200);
+
+        debugger.pause(&chip8);
+        assert_eq!(debugger.halt_reason(), Some(HaltReason::Pause));
+
+        debugger.resume();
+        assert_eq!(debugger.run_frame(&mut chip8).unwrap(), None);
+        assert_eq!(chip8.registers().pc(), 0x206);
+    }
+
+    #[test]
+    fn step_over_runs_the_called_subroutine() {
+        let mut chip8 = build(PROGRAM);
+        let mut debugger = Debugger::new();
+
+        debugger.step_into();
+        debugger.run_frame(&mut chip8).unwrap();
+        assert_eq!(chip8.registers().pc(), 0x202);
+
+        debugger.step_over(&chip8);
+        assert_eq!(
+            debugger.run_frame(&mut chip8).unwrap(),
+            Some(HaltReason::Step)
+        );
+        assert_eq!(chip8.registers().pc(), 0x204);
+        assert_eq!(register(&chip8, 0x2), 3);
+        assert!(chip8.registers().stack().is_empty());
+    }
+
+    #[test]
+    fn step_over_stops_on_a_breakpoint_in_the_subroutine() {
+        let mut chip8 = build(PROGRAM);
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x20A);
+
+        debugger.step_into();
+        debugger.run_frame(&mut chip8).unwrap();
+        debugger.step_over(&chip8);
+        assert_eq!(
+            debugger.run_frame(&mut chip8).unwrap(),
+            Some(HaltReason::Breakpoint(0x20A))
+        );
+    }
+
+    #[test]
+    fn step_out_returns_to_the_caller() {
+        let mut chip8 = build(PROGRAM);
+        let mut debugger = Debugger::new();
+
+        for pc in &[0x202, 0x208, 0x20A] {
+            debugger.step_into();
+            debugger.run_frame(&mut chip8).unwrap();
+            assert_eq!(chip8.registers().pc(), *pc);
+        }
+
+        debugger.step_out(&chip8);
+        assert_eq!(
+            debugger.run_frame(&mut chip8).unwrap(),
+            Some(HaltReason::Step)
+        );
+        assert_eq!(chip8.registers().pc(), 0x204);
+    }
+
+    #[test]
+    fn step_out_at_the_top_level_runs_on() {
+        let mut chip8 = build(PROGRAM);
+        let mut debugger = Debugger::new();
+
+        debugger.step_out(&chip8);
+        assert_eq!(debugger.run_frame(&mut chip8).unwrap(), None);
+        assert!(!debugger.is_paused());
+        assert_eq!(chip8.registers().pc(), 0x206);
+    }
+
+    #[test]
+    fn watchpoints_halt_after_the_access() {
+        // 200: LD V0, 7
+        // 202: LD I, 300
+        // 204: LD [I], V3
+        // 206: LD V0, [I]
+        // 208: JP 208
+        let program = &[0x6007, 0xA300, 0xF355, 0xF065, 0x1208];
+        let watch = |location, kind| Watchpoint { location, kind };
+
+        let mut chip8 = build(program);
+        let mut debugger = Debugger::new();
+        debugger.add_watchpoint(watch(Location::Memory(0x302), AccessKind::Write));
+        // written by 204 first, only read by 206
+        debugger.add_watchpoint(watch(Location::Memory(0x300), AccessKind::Read));
+
+        let reason = debugger.run_frame(&mut chip8).unwrap();
+        assert_eq!(
+            reason,
+            Some(HaltReason::Watchpoint {
+                pc: 0x204,
+                location: Location::Memory(0x302),
+                access: AccessKind::Write,
+            })
+        );
+        assert_eq!(chip8.registers().pc(), 0x206);
+
+        debugger.resume();
+        let reason = debugger.run_frame(&mut chip8).unwrap();
+        assert_eq!(
+            reason,
+            Some(HaltReason::Watchpoint {
+                pc: 0x206,
+                location: Location::Memory(0x300),
+                access: AccessKind::Read,
+            })
+        );
+        assert_eq!(register(&chip8, 0x0), 7);
+
+        // reads only, the write of V0 by the next load goes unnoticed
+        let mut chip8 = build(program);
+        let mut debugger = Debugger::new();
+        debugger.add_watchpoint(watch(Location::Register(0x0), AccessKind::Read));
+        debugger.add_watchpoint(watch(Location::I, AccessKind::ReadWrite));
+        assert!(debugger.remove_watchpoint(&watch(Location::I, AccessKind::ReadWrite)));
+
+        let reason = debugger.run_frame(&mut chip8).unwrap();
+        assert_eq!(
+            reason,
+            Some(HaltReason::Watchpoint {
+                pc: 0x204,
+                location: Location::Register(0x0),
+                access: AccessKind::Read,
+            })
+        );
+
+        debugger.resume();
+        assert_eq!(debugger.run_frame(&mut chip8).unwrap(), None);
+        assert_eq!(register(&chip8, 0x0), 7);
+    }
+
+    #[test]
+    fn exit_halts_the_execution() {
+        let mut chip8 = build(&[0x6001, 0x00FD]);
+        let mut debugger = Debugger::new();
+
+        assert_eq!(
+            debugger.run_frame(&mut chip8).unwrap(),
+            Some(HaltReason::Exited)
+        );
+        assert!(chip8.is_exited());
+    }
+
+    // Instructions of every kind, with V0, V1 and VF as X and Y
+    fn words() -> Vec<u16> {
+        let registers = [0x0, 0x1, 0xf];
+        let mut words = vec![];
+        for op in 0..=0xf {
+            for x in &registers {
+                for y in &registers {
+                    words.extend((0..=0xf).map(|n| op << 12 | x << 8 | y << 4 | n));
+                }
+                // every byte operand of the 0 and F families
+                if op == 0x0 || op == 0xf {
+                    words.extend((0..=0xff).map(|nn| op << 12 | x << 8 | nn));
+                }
+            }
+        }
+
+        words
+    }
+
+    // Every instruction is executed from the same machine state, under every
+    // quirks preset: the locations it changes must all be predicted as
+    // written by `accesses`.
+    #[test]
+    fn predicted_writes_cover_every_change() {
+        let presets = [
+            Quirks::COWGOD,
+            Quirks::COSMAC_VIP,
+            Quirks::CHIP_48,
+            Quirks::SCHIP_1_1,
+            Quirks::XO_CHIP,
+        ];
+
+        for quirks in &presets {
+            for word in words() {
+                let mut chip8 = Chip8::builder()
+                    .rom(&word.to_be_bytes())
+                    .quirks(*quirks)
+                    .build()
+                    .unwrap();
+                for x in 0..16u8 {
+                    chip8
+                        .registers_mut()
+                        .set_register(x, x.wrapping_mul(37).wrapping_add(5))
+                        .unwrap();
+                }
+                chip8.registers_mut().set_i(0x300);
+                chip8.set_keyboard_state(KeyboardState::from_mask(1 << 0x5));
+
+                // the second instruction, 0000, only runs after a wait for a key
+                for _ in 0..2 {
+                    check_predicted_writes(&mut chip8, word);
+                }
+            }
+        }
+    }
+
+    fn check_predicted_writes(chip8: &mut Chip8, word: u16) {
+        let registers = (0..16)
+            .map(|x| chip8.registers().register(x).unwrap())
+            .collect::<Vec<u8>>();
+        let i = chip8.registers().i();
+        let memory = chip8.memory().bytes().to_vec();
+        let predicted = accesses(chip8);
+
+        if chip8.step().is_err() {
+            return;
+        }
+
+        let is_predicted = |location| predicted.contains(&(location, AccessKind::Write));
+        for x in 0..16 {
+            if chip8.registers().register(x).unwrap() != registers[x as usize] {
+                assert!(is_predicted(Location::Register(x)), "{:04X} V{:X}", word, x);
+            }
+        }
+        if chip8.registers().i() != i {
+            assert!(is_predicted(Location::I), "{:04X} I", word);
+        }
+        if chip8.memory().bytes() != memory.as_slice() {
+            let bytes = chip8.memory().bytes().iter().zip(&memory);
+            for (addr, (after, before)) in bytes.enumerate() {
+                if after != before {
+                    assert!(
+                        is_predicted(Location::Memory(addr as u16)),
+                        "{:04X} {:04X}",
+                        word,
+                        addr
+                    );
+                }
+            }
+        }
+    }
+}
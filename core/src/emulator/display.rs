@@ -1,43 +1,201 @@
-const WIDTH: usize = 64;
-const HEIGHT: usize = 32;
+const LORES_WIDTH: usize = 64;
+const LORES_HEIGHT: usize = 32;
+const HIRES_WIDTH: usize = 128;
+const HIRES_HEIGHT: usize = 64;
+// XO-CHIP bitplanes
+pub const PLANES: usize = 2;
 
-#[derive(Debug)]
+// bits of a row, the leftmost pixel being the most significant one
+type Row = u128;
+const ROW_BITS: usize = 128;
+
+#[derive(Debug, Clone)]
 pub struct Display {
-    buffer: Vec<bool>,
+    // a row of bits for every line of the high resolution, the low resolution
+    // only uses the leftmost 64 bits of the first 32 rows
+    planes: [[Row; HIRES_HEIGHT]; PLANES],
+    // bitmask of the planes affected by drawing, clearing and scrolling
+    selected_planes: u8,
+    hires: bool,
 }
 
 impl Default for Display {
     fn default() -> Self {
         Self {
-            buffer: vec![false; WIDTH * HEIGHT],
+            planes: [[0; HIRES_HEIGHT]; PLANES],
+            selected_planes: 0x1,
+            hires: false,
         }
     }
 }
 
+// bit of the pixel at column `x`
+fn column_bit(x: usize) -> Row {
+    1 << (ROW_BITS - 1 - x)
+}
+
 impl Display {
-    pub fn buffer(&mut self) -> &mut Vec<bool> {
-        &mut self.buffer
+    /// Color index of every pixel, row by row. Bit 0 is set when the pixel
+    /// is lit on the first plane, bit 1 when it is lit on the second one.
+    pub fn pixels(&self) -> impl Iterator<Item = u8> + '_ {
+        let width = self.width();
+        (0..self.height()).flat_map(move |y| (0..width).map(move |x| self.color(x, y)))
+    }
+
+    /// Color of every pixel, row by row, looked up in `palette` by index.
+    pub fn rgba(&self, palette: [[u8; 4]; 4]) -> impl Iterator<Item = [u8; 4]> + '_ {
+        self.pixels().map(move |color| palette[color as usize])
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.color(x, y) != 0
     }
 
-    pub fn pixel(&mut self, x: usize, y: usize) -> bool {
-        let index = x + (y * WIDTH);
-        self.buffer[index]
+    pub fn color(&self, x: usize, y: usize) -> u8 {
+        self.plane_pixel(0, x, y) as u8 | (self.plane_pixel(1, x, y) as u8) << 1
+    }
+
+    pub fn plane_pixel(&self, plane: usize, x: usize, y: usize) -> bool {
+        self.planes[plane][y] & column_bit(x) != 0
     }
 
     pub fn set_pixel(&mut self, x: usize, y: usize, value: bool) {
-        let index = x + (y * WIDTH);
-        self.buffer[index] ^= value;
+        self.set_plane_pixel(0, x, y, value)
+    }
+
+    pub fn set_plane_pixel(&mut self, plane: usize, x: usize, y: usize, value: bool) {
+        if value {
+            self.planes[plane][y] ^= column_bit(x);
+        }
+    }
+
+    /// XORs a row of 16 sprite pixels, most significant bit first, into row
+    /// `y` of `plane` from column `x`. The pixels past the right edge wrap
+    /// around to the left one, unless `clip`. Returns whether a lit pixel was
+    /// turned off.
+    pub fn xor_sprite_row(
+        &mut self,
+        plane: usize,
+        x: usize,
+        y: usize,
+        sprite: u16,
+        clip: bool,
+    ) -> bool {
+        let width = self.width();
+        let mask = self.row_mask();
+        let sprite = (sprite as Row) << (ROW_BITS - 16);
+
+        let mut bits = (sprite >> x) & mask;
+        if !clip {
+            // the pixels shifted out of the row on the right
+            bits |= sprite.checked_shl((width - x) as u32).unwrap_or(0) & mask;
+        }
+
+        let row = &mut self.planes[plane][y];
+        let collision = *row & bits != 0;
+        *row ^= bits;
+
+        collision
     }
 
     pub fn clear(&mut self) {
-        self.buffer = vec![false; WIDTH * HEIGHT]
+        for plane in self.selected_planes() {
+            self.planes[plane] = [0; HIRES_HEIGHT];
+        }
+    }
+
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    /// Switches between the 64x32 and the 128x64 (SUPER-CHIP) resolution,
+    /// the screen is cleared on every switch.
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.planes = [[0; HIRES_HEIGHT]; PLANES];
+    }
+
+    /// Planes drawing, clearing and scrolling operate on, in drawing order.
+    pub fn selected_planes(&self) -> impl Iterator<Item = usize> {
+        let mask = self.selected_planes;
+        (0..PLANES).filter(move |plane| mask & (1 << plane) != 0)
+    }
+
+    pub fn selected_planes_mask(&self) -> u8 {
+        self.selected_planes
+    }
+
+    pub fn select_planes(&mut self, mask: u8) {
+        self.selected_planes = mask & 0x3;
+    }
+
+    pub fn scroll_down(&mut self, rows: usize) {
+        let height = self.height();
+        let shift = rows.min(height);
+        for plane in self.selected_planes() {
+            let plane = &mut self.planes[plane];
+            plane.copy_within(..height - shift, shift);
+            plane[..shift].iter_mut().for_each(|row| *row = 0);
+        }
+    }
+
+    pub fn scroll_up(&mut self, rows: usize) {
+        let height = self.height();
+        let shift = rows.min(height);
+        for plane in self.selected_planes() {
+            let plane = &mut self.planes[plane];
+            plane.copy_within(shift..height, 0);
+            plane[height - shift..height]
+                .iter_mut()
+                .for_each(|row| *row = 0);
+        }
+    }
+
+    pub fn scroll_right(&mut self, columns: usize) {
+        let shift = columns.min(self.width()) as u32;
+        let mask = self.row_mask();
+        for plane in self.selected_planes() {
+            for row in self.planes[plane].iter_mut() {
+                *row = row.checked_shr(shift).unwrap_or(0) & mask;
+            }
+        }
+    }
+
+    pub fn scroll_left(&mut self, columns: usize) {
+        let shift = columns.min(self.width()) as u32;
+        for plane in self.selected_planes() {
+            for row in self.planes[plane].iter_mut() {
+                *row = row.checked_shl(shift).unwrap_or(0);
+            }
+        }
+    }
+
+    // bits of the pixels inside the current resolution
+    fn row_mask(&self) -> Row {
+        !0 << (ROW_BITS - self.width())
+    }
+
+    pub fn width(&self) -> usize {
+        if self.hires {
+            HIRES_WIDTH
+        } else {
+            LORES_WIDTH
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        if self.hires {
+            HIRES_HEIGHT
+        } else {
+            LORES_HEIGHT
+        }
     }
 
-    pub fn width() -> usize {
-        WIDTH
+    pub fn max_width() -> usize {
+        HIRES_WIDTH
     }
 
-    pub fn height() -> usize {
-        HEIGHT
+    pub fn max_height() -> usize {
+        HIRES_HEIGHT
     }
 }
@@ -5,30 +5,51 @@ where
     fn map_key(&self, key: K) -> Result<u8, Box<dyn std::error::Error>>;
 }
 
-#[derive(Debug)]
+/// Keys of the hexadecimal keypad held down, one bit for every key.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct KeyboardState {
-    keys_pressed: Vec<u8>,
+    mask: u16,
 }
 
 impl KeyboardState {
-    pub fn new<K>(keys: Vec<K>, key_mapper: &dyn KeyMapper<K>) -> Self
+    pub fn new<K>(keys: impl IntoIterator<Item = K>, key_mapper: &dyn KeyMapper<K>) -> Self
     where
         K: PartialEq + Eq,
     {
-        Self {
-            keys_pressed: keys
-                .into_iter()
-                .map(|key| key_mapper.map_key(key))
-                .filter_map(|result| result.ok())
-                .collect(),
+        let mut keyboard_state = Self::default();
+        keys.into_iter()
+            .filter_map(|key| key_mapper.map_key(key).ok())
+            .for_each(|key| keyboard_state.press(key));
+
+        keyboard_state
+    }
+
+    /// Keys whose bit is set in `mask`, bit 0 being key 0x0.
+    pub fn from_mask(mask: u16) -> Self {
+        Self { mask }
+    }
+
+    pub fn mask(&self) -> u16 {
+        self.mask
+    }
+
+    /// Holds `key` down, keys outside the keypad are ignored.
+    pub fn press(&mut self, key: u8) {
+        if key < 16 {
+            self.mask |= 1 << key;
         }
     }
 
     pub fn is_key_pressed(&self, key: u8) -> bool {
-        self.keys_pressed.contains(&key)
+        key < 16 && self.mask & (1 << key) != 0
     }
 
+    /// Lowest key held down.
     pub fn get_key_pressed(&self) -> Option<u8> {
-        self.keys_pressed.first().cloned()
+        if self.mask == 0 {
+            None
+        } else {
+            Some(self.mask.trailing_zeros() as u8)
+        }
     }
 }
@@ -1,3 +1,6 @@
+use crate::emulator::cpu::opcode::{Opcode, OpcodeError};
+use thiserror::Error;
+
 const FONTS: &[u8] = &[
     0xf0, 0x90, 0x90, 0x90, 0xf0, 0x20, 0x60, 0x20, 0x20, 0x70, 0xf0, 0x10, 0xf0, 0x80, 0xf0, 0xf0,
     0x10, 0xf0, 0x10, 0xf0, 0x90, 0x90, 0xf0, 0x10, 0x10, 0xf0, 0x80, 0xf0, 0x10, 0xf0, 0xf0, 0x80,
@@ -6,8 +9,41 @@ const FONTS: &[u8] = &[
     0xf0, 0xe0, 0x90, 0x90, 0x90, 0xe0, 0xf0, 0x80, 0xf0, 0x80, 0xf0, 0xf0, 0x80, 0xf0, 0x80, 0x80,
 ];
 
-#[derive(Debug)]
-pub struct Memory([u8; 4096]);
+const BIG_FONTS: &[u8] = &[
+    0xff, 0xff, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xff, 0xff, 0x18, 0x78, 0x78, 0x18, 0x18, 0x18,
+    0x18, 0x18, 0xff, 0xff, 0xff, 0xff, 0x03, 0x03, 0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0xff, 0xff,
+    0x03, 0x03, 0xff, 0xff, 0x03, 0x03, 0xff, 0xff, 0xc3, 0xc3, 0xc3, 0xc3, 0xff, 0xff, 0x03, 0x03,
+    0x03, 0x03, 0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0x03, 0x03, 0xff, 0xff, 0xff, 0xff, 0xc0, 0xc0,
+    0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff, 0xff, 0xff, 0x03, 0x03, 0x06, 0x0c, 0x18, 0x18, 0x18, 0x18,
+    0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff, 0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff,
+    0x03, 0x03, 0xff, 0xff, 0x7e, 0xff, 0xc3, 0xc3, 0xc3, 0xff, 0xff, 0xc3, 0xc3, 0xc3, 0xfc, 0xfc,
+    0xc3, 0xc3, 0xfc, 0xfc, 0xc3, 0xc3, 0xfc, 0xfc, 0x3c, 0xff, 0xc3, 0xc0, 0xc0, 0xc0, 0xc0, 0xc3,
+    0xff, 0x3c, 0xfc, 0xfe, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xfe, 0xfc, 0xff, 0xff, 0xc0, 0xc0,
+    0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0xc0, 0xc0, 0xc0, 0xc0,
+];
+
+// big fonts are stored right after the 80 bytes of the small ones
+const BIG_FONTS_ADDRESS: u16 = 0x50;
+
+// XO-CHIP extends the address space to the whole 16 bits
+pub const SIZE: usize = 0x10000;
+const ROM_ADDRESS: usize = 0x200;
+/// Largest rom fitting between its load address and the end of memory.
+pub const MAX_ROM_SIZE: usize = SIZE - ROM_ADDRESS;
+
+#[derive(Debug, Error)]
+pub enum MemoryError {
+    #[error("Rom too large: {0} bytes, at most {} fit in memory", MAX_ROM_SIZE)]
+    RomTooLarge(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Memory {
+    bytes: Vec<u8>,
+    // instruction decoded at every address the first time it is executed,
+    // when the cache is enabled
+    decoded: Option<Vec<Option<Opcode>>>,
+}
 
 impl Default for Memory {
     fn default() -> Self {
@@ -17,36 +53,104 @@ impl Default for Memory {
 
 impl Memory {
     pub fn new() -> Self {
-        let mut bytes = [0; 4096];
+        let mut bytes = vec![0; SIZE];
         initialize_fonts(&mut bytes);
-        Self(bytes)
+        Self {
+            bytes,
+            decoded: None,
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Overwrites the whole address space, fonts included.
+    pub(crate) fn set_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.copy_from_slice(bytes);
+        self.clear_decode_cache();
+    }
+
+    pub fn is_decode_cache_enabled(&self) -> bool {
+        self.decoded.is_some()
+    }
+
+    /// Keeps every instruction decoded by [`Memory::decode`] until one of its
+    /// bytes is written, or drops the cache when `enabled` is false.
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        if enabled != self.is_decode_cache_enabled() {
+            self.decoded = if enabled {
+                Some(vec![None; SIZE])
+            } else {
+                None
+            };
+        }
+    }
+
+    /// Decodes the instruction at `address`, from the cache when enabled and
+    /// the instruction was already decoded.
+    pub fn decode(&mut self, address: u16) -> Result<Opcode, OpcodeError> {
+        if let Some(Some(opcode)) = self
+            .decoded
+            .as_ref()
+            .map(|decoded| decoded[address as usize])
+        {
+            return Ok(opcode);
+        }
+
+        let opcode = Opcode::decode(self.read_16(address))?;
+        if let Some(decoded) = &mut self.decoded {
+            decoded[address as usize] = Some(opcode);
+        }
+
+        Ok(opcode)
     }
 
     pub fn read_8(&self, address: u16) -> u8 {
-        self.0[address as usize]
+        self.bytes[address as usize]
     }
 
     pub fn read_16(&self, address: u16) -> u16 {
-        u16::from_be_bytes([self.read_8(address), self.read_8(address + 1)])
+        u16::from_be_bytes([self.read_8(address), self.read_8(address.wrapping_add(1))])
     }
 
     pub fn write_8(&mut self, address: u16, byte: u8) {
-        self.0[address as usize] = byte
+        self.bytes[address as usize] = byte;
+
+        // self-modifying code: drop the instructions starting at this byte
+        // and at the one before, which ends here
+        if let Some(decoded) = &mut self.decoded {
+            decoded[address as usize] = None;
+            decoded[address.wrapping_sub(1) as usize] = None;
+        }
     }
 
     pub fn get_font_address(&mut self, font: u8) -> u16 {
         font as u16 * 5_u16
     }
 
-    pub fn load_rom(&mut self, rom: &[u8]) {
-        for (index, b) in rom.iter().enumerate() {
-            self.write_8((0x200 + index) as u16, *b);
+    pub fn get_big_font_address(&mut self, font: u8) -> u16 {
+        BIG_FONTS_ADDRESS + (font & 0xf) as u16 * 10_u16
+    }
+
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), MemoryError> {
+        if rom.len() > MAX_ROM_SIZE {
+            return Err(MemoryError::RomTooLarge(rom.len()));
+        }
+
+        self.bytes[ROM_ADDRESS..ROM_ADDRESS + rom.len()].copy_from_slice(rom);
+        self.clear_decode_cache();
+        Ok(())
+    }
+
+    fn clear_decode_cache(&mut self) {
+        if let Some(decoded) = &mut self.decoded {
+            decoded.iter_mut().for_each(|opcode| *opcode = None);
         }
     }
 }
 
-fn initialize_fonts(bytes: &mut [u8; 4096]) {
-    (0..80)
-        .into_iter()
-        .for_each(|index| bytes[index] = FONTS[index]);
+fn initialize_fonts(bytes: &mut [u8]) {
+    (0..80).for_each(|index| bytes[index] = FONTS[index]);
+    (0..160).for_each(|index| bytes[BIG_FONTS_ADDRESS as usize + index] = BIG_FONTS[index]);
 }
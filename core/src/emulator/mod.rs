@@ -1,15 +1,22 @@
 use thiserror::Error;
 
-use crate::emulator::audio::Audio;
-use crate::emulator::cpu::{Cpu, CpuError};
-use crate::emulator::display::Display;
-use crate::emulator::keyboard::{KeyMapper, KeyboardState};
+use crate::emulator::cpu::CpuError;
+use crate::emulator::memory::MemoryError;
 
 pub mod audio;
+pub mod checksum;
+pub mod chip8;
+mod compression;
 pub mod cpu;
+pub mod debugger;
 pub mod display;
 pub mod keyboard;
 pub mod memory;
+pub mod movie;
+pub mod platform;
+pub mod quirks;
+pub mod rewind;
+pub mod state;
 
 #[derive(Debug, Error)]
 pub enum EmulatorError {
@@ -17,17 +24,6 @@ pub enum EmulatorError {
     EmulatorError(#[from] Box<dyn std::error::Error>),
     #[error(transparent)]
     CpuError(#[from] CpuError),
-}
-
-pub fn tick<K>(
-    cpu: &mut Cpu,
-    display: &mut Display,
-    audio: &mut dyn Audio,
-    keys: Vec<K>,
-    key_mapper: &dyn KeyMapper<K>,
-) -> Result<(), EmulatorError>
-where
-    K: PartialEq + Eq,
-{
-    Ok(cpu.cycle(display, audio, KeyboardState::new(keys, key_mapper))?)
+    #[error(transparent)]
+    MemoryError(#[from] MemoryError),
 }
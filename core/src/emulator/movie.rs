@@ -0,0 +1,237 @@
+use crate::emulator::checksum::crc32;
+use crate::emulator::chip8::{Chip8, Chip8Builder};
+use crate::emulator::keyboard::KeyboardState;
+use crate::emulator::quirks::{LoadStore, Quirks};
+use thiserror::Error;
+
+// Movie file layout, numbers are big endian:
+//
+// | magic "C8MV" | version u8 | rom crc32 u32 | seed u64 | instructions per frame u32 |
+// | quirks flags u8 | load store quirk u8 | frames u32 | keys bitmask u16 per frame ... |
+// | crc32 of all the previous bytes u32 |
+const MAGIC: &[u8; 4] = b"C8MV";
+const VERSION: u8 = 1;
+const HEADER_SIZE: usize = 27;
+const CHECKSUM_SIZE: usize = 4;
+
+#[derive(Debug, Error)]
+pub enum MovieError {
+    #[error("Not a movie file")]
+    InvalidMagic,
+    #[error("Unsupported movie version: `{0}`")]
+    UnsupportedVersion(u8),
+    #[error("Movie file checksum mismatch")]
+    ChecksumMismatch,
+    #[error("Corrupted movie file")]
+    Corrupted,
+    #[error("Movie recorded with a different rom")]
+    RomMismatch,
+}
+
+/// Keys held down during every frame of a run, with everything needed to
+/// replay it exactly from power on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    rom_crc32: u32,
+    seed: u64,
+    instructions_per_frame: usize,
+    quirks: Quirks,
+    frames: Vec<u16>,
+}
+
+impl Movie {
+    pub fn rom_crc32(&self) -> u32 {
+        self.rom_crc32
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn instructions_per_frame(&self) -> usize {
+        self.instructions_per_frame
+    }
+
+    pub fn quirks(&self) -> &Quirks {
+        &self.quirks
+    }
+
+    /// Keys bitmask of every frame.
+    pub fn frames(&self) -> &[u16] {
+        &self.frames
+    }
+
+    /// Builder of a machine replaying the movie, `rom` must be the recorded one.
+    pub fn chip8_builder(&self, rom: &[u8]) -> Result<Chip8Builder, MovieError> {
+        if crc32(rom) != self.rom_crc32 {
+            return Err(MovieError::RomMismatch);
+        }
+
+        Ok(Chip8::builder()
+            .rom(rom)
+            .seed(self.seed)
+            .quirks(self.quirks)
+            .instructions_per_frame(self.instructions_per_frame))
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + self.frames.len() * 2 + CHECKSUM_SIZE);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&self.rom_crc32.to_be_bytes());
+        bytes.extend_from_slice(&self.seed.to_be_bytes());
+        bytes.extend_from_slice(&(self.instructions_per_frame as u32).to_be_bytes());
+        bytes.push(quirks_flags(&self.quirks));
+        bytes.push(load_store_to_byte(self.quirks.load_store));
+        bytes.extend_from_slice(&(self.frames.len() as u32).to_be_bytes());
+        for keys in &self.frames {
+            bytes.extend_from_slice(&keys.to_be_bytes());
+        }
+        bytes.extend_from_slice(&crc32(&bytes).to_be_bytes());
+
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, MovieError> {
+        if bytes.len() < HEADER_SIZE || &bytes[0..4] != MAGIC {
+            return Err(MovieError::InvalidMagic);
+        }
+
+        if bytes[4] != VERSION {
+            return Err(MovieError::UnsupportedVersion(bytes[4]));
+        }
+
+        if bytes.len() < HEADER_SIZE + CHECKSUM_SIZE {
+            return Err(MovieError::Corrupted);
+        }
+        let (bytes, checksum) = bytes.split_at(bytes.len() - CHECKSUM_SIZE);
+        if crc32(bytes).to_be_bytes() != checksum {
+            return Err(MovieError::ChecksumMismatch);
+        }
+
+        let u32_at = |index: usize| {
+            u32::from_be_bytes([
+                bytes[index],
+                bytes[index + 1],
+                bytes[index + 2],
+                bytes[index + 3],
+            ])
+        };
+
+        let rom_crc32 = u32_at(5);
+        let seed = (u32_at(9) as u64) << 32 | u32_at(13) as u64;
+        let instructions_per_frame = u32_at(17) as usize;
+        let quirks = quirks_from_bytes(bytes[21], bytes[22]).ok_or(MovieError::Corrupted)?;
+        let frame_count = u32_at(23) as usize;
+
+        let frames = &bytes[HEADER_SIZE..];
+        if frame_count.checked_mul(2) != Some(frames.len()) {
+            return Err(MovieError::Corrupted);
+        }
+
+        Ok(Self {
+            rom_crc32,
+            seed,
+            instructions_per_frame,
+            quirks,
+            frames: frames
+                .chunks(2)
+                .map(|keys| u16::from_be_bytes([keys[0], keys[1]]))
+                .collect(),
+        })
+    }
+}
+
+/// Records the keys of every frame of a machine, from its power on.
+pub struct MovieRecorder {
+    movie: Movie,
+}
+
+impl MovieRecorder {
+    pub fn new(chip8: &Chip8) -> Self {
+        Self {
+            movie: Movie {
+                rom_crc32: crc32(chip8.rom()),
+                seed: chip8.seed(),
+                instructions_per_frame: chip8.instructions_per_frame(),
+                quirks: *chip8.quirks(),
+                frames: vec![],
+            },
+        }
+    }
+
+    /// Keys held down during the next frame.
+    pub fn record(&mut self, keyboard_state: &KeyboardState) {
+        self.movie.frames.push(keyboard_state.mask());
+    }
+
+    pub fn finish(self) -> Movie {
+        self.movie
+    }
+}
+
+/// Plays back the keys of a movie, one frame at a time.
+pub struct MoviePlayer {
+    movie: Movie,
+    frame: usize,
+}
+
+impl MoviePlayer {
+    pub fn new(movie: Movie) -> Self {
+        Self { movie, frame: 0 }
+    }
+
+    /// Keys held down during the next frame, `None` when the movie is over.
+    pub fn next_frame(&mut self) -> Option<KeyboardState> {
+        let keys = *self.movie.frames.get(self.frame)?;
+        self.frame += 1;
+        Some(KeyboardState::from_mask(keys))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.movie.frames.len()
+    }
+
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+}
+
+fn quirks_flags(quirks: &Quirks) -> u8 {
+    [
+        quirks.shift_uses_vy,
+        quirks.clip_sprites,
+        quirks.jump_uses_vx,
+        quirks.vf_reset,
+        quirks.display_wait,
+    ]
+    .iter()
+    .enumerate()
+    .fold(0, |flags, (bit, set)| flags | (*set as u8) << bit)
+}
+
+fn load_store_to_byte(load_store: LoadStore) -> u8 {
+    match load_store {
+        LoadStore::Unchanged => 0,
+        LoadStore::IncrementByX => 1,
+        LoadStore::IncrementByXPlusOne => 2,
+    }
+}
+
+fn quirks_from_bytes(flags: u8, load_store: u8) -> Option<Quirks> {
+    let load_store = match load_store {
+        0 => LoadStore::Unchanged,
+        1 => LoadStore::IncrementByX,
+        2 => LoadStore::IncrementByXPlusOne,
+        _ => return None,
+    };
+
+    Some(Quirks {
+        shift_uses_vy: flags & 0x01 != 0,
+        load_store,
+        clip_sprites: flags & 0x02 != 0,
+        jump_uses_vx: flags & 0x04 != 0,
+        vf_reset: flags & 0x08 != 0,
+        display_wait: flags & 0x10 != 0,
+    })
+}
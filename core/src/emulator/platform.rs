@@ -0,0 +1,40 @@
+use crate::emulator::quirks::Quirks;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum PlatformError {
+    #[error("Unknown platform: `{0}`")]
+    UnknownPlatform(String),
+}
+
+/// Interpreter family a rom targets, selecting its default quirks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Platform {
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks::COSMAC_VIP,
+            Platform::SuperChip => Quirks::SCHIP_1_1,
+            Platform::XoChip => Quirks::XO_CHIP,
+        }
+    }
+}
+
+impl FromStr for Platform {
+    type Err = PlatformError;
+
+    fn from_str(platform: &str) -> Result<Self, Self::Err> {
+        match platform {
+            "chip8" => Ok(Platform::Chip8),
+            "schip" => Ok(Platform::SuperChip),
+            "xochip" => Ok(Platform::XoChip),
+            _ => Err(PlatformError::UnknownPlatform(platform.to_string())),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum QuirksError {
+    #[error("Unknown quirks preset: `{0}`")]
+    UnknownPreset(String),
+}
+
+/// Effect on I of the Fx55/Fx65 register batch load and store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadStore {
+    Unchanged,
+    /// CHIP-48 leaves I on the last register loaded or stored.
+    IncrementByX,
+    /// COSMAC VIP leaves I past the last register loaded or stored.
+    IncrementByXPlusOne,
+}
+
+/// Interpretation of the instructions whose behaviour differs between
+/// the CHIP-8 interpreters programs were written for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8xy6/8xyE shift VY and store the result in VX, instead of shifting VX in place.
+    pub shift_uses_vy: bool,
+    /// How Fx55/Fx65 change I.
+    pub load_store: LoadStore,
+    /// Sprites are clipped at the screen edges instead of wrapping around.
+    pub clip_sprites: bool,
+    /// Bnnn jumps to nnn plus VX, where X is the highest nibble of nnn, instead of V0.
+    pub jump_uses_vx: bool,
+    /// 8xy1/8xy2/8xy3 reset VF to 0.
+    pub vf_reset: bool,
+    /// Dxyn waits for the next display refresh, drawing at most one sprite per frame.
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    /// Cowgod's technical reference, the interpretation of this emulator
+    /// before the quirks were configurable.
+    pub const COWGOD: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store: LoadStore::Unchanged,
+        clip_sprites: false,
+        jump_uses_vx: false,
+        vf_reset: false,
+        display_wait: false,
+    };
+
+    pub const COSMAC_VIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        load_store: LoadStore::IncrementByXPlusOne,
+        clip_sprites: true,
+        jump_uses_vx: false,
+        vf_reset: true,
+        display_wait: true,
+    };
+
+    pub const CHIP_48: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store: LoadStore::IncrementByX,
+        clip_sprites: true,
+        jump_uses_vx: true,
+        vf_reset: false,
+        display_wait: false,
+    };
+
+    pub const SCHIP_1_1: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store: LoadStore::Unchanged,
+        clip_sprites: true,
+        jump_uses_vx: true,
+        vf_reset: false,
+        display_wait: false,
+    };
+
+    pub const XO_CHIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        load_store: LoadStore::IncrementByXPlusOne,
+        clip_sprites: false,
+        jump_uses_vx: false,
+        vf_reset: false,
+        display_wait: false,
+    };
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::COWGOD
+    }
+}
+
+impl FromStr for Quirks {
+    type Err = QuirksError;
+
+    fn from_str(preset: &str) -> Result<Self, Self::Err> {
+        match preset {
+            "cowgod" => Ok(Quirks::COWGOD),
+            "vip" => Ok(Quirks::COSMAC_VIP),
+            "chip48" => Ok(Quirks::CHIP_48),
+            "schip" => Ok(Quirks::SCHIP_1_1),
+            "xochip" => Ok(Quirks::XO_CHIP),
+            _ => Err(QuirksError::UnknownPreset(preset.to_string())),
+        }
+    }
+}
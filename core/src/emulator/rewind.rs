@@ -0,0 +1,227 @@
+use crate::emulator::compression::{compress, decompress};
+use crate::emulator::state::Snapshot;
+use std::collections::VecDeque;
+
+enum Frame {
+    // compressed payload of the snapshot
+    Key(Vec<u8>),
+    // runs of bytes differing from the payload of the previous keyframe
+    Delta { length: usize, changes: Vec<u8> },
+}
+
+impl Frame {
+    fn size(&self) -> usize {
+        match self {
+            Frame::Key(compressed) => compressed.len(),
+            Frame::Delta { changes, .. } => changes.len(),
+        }
+    }
+}
+
+/// Bounded history of the last emulated frames, played back from the most
+/// recent one. Every `keyframe_interval` frames a whole snapshot is stored,
+/// the frames in between only keep their difference from it; when the
+/// memory budget is exceeded the oldest keyframe is dropped with its deltas.
+pub struct RewindBuffer {
+    frames: VecDeque<Frame>,
+    capacity: usize,
+    keyframe_interval: usize,
+    size: usize,
+    // payload of the most recent keyframe, the base of new deltas
+    keyframe: Option<Vec<u8>>,
+    frames_since_keyframe: usize,
+    // delta of the frame being pushed, reused by every push
+    changes: Vec<u8>,
+}
+
+impl RewindBuffer {
+    /// `capacity` is the memory budget in bytes for the compressed frames.
+    pub fn new(capacity: usize, keyframe_interval: usize) -> Self {
+        Self {
+            frames: VecDeque::new(),
+            capacity,
+            keyframe_interval: keyframe_interval.max(1),
+            size: 0,
+            keyframe: None,
+            frames_since_keyframe: 0,
+            changes: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, snapshot: &Snapshot) {
+        let payload = snapshot.payload();
+
+        let frame = match &self.keyframe {
+            Some(keyframe) if self.frames_since_keyframe < self.keyframe_interval => {
+                self.frames_since_keyframe += 1;
+                diff(payload, keyframe, &mut self.changes);
+                // an exact fit for the budget, the scratch buffer keeps its capacity
+                Frame::Delta {
+                    length: payload.len(),
+                    changes: self.changes.as_slice().to_vec(),
+                }
+            }
+            _ => {
+                let keyframe = self.keyframe.get_or_insert_with(Vec::new);
+                keyframe.clear();
+                keyframe.extend_from_slice(payload);
+                self.frames_since_keyframe = 1;
+                Frame::Key(compress(payload))
+            }
+        };
+
+        self.size += frame.size();
+        self.frames.push_back(frame);
+
+        self.evict();
+    }
+
+    /// Removes the most recent frame, returning its snapshot.
+    pub fn pop(&mut self) -> Option<Snapshot> {
+        let frame = self.frames.pop_back()?;
+        self.size -= frame.size();
+
+        let payload = match frame {
+            Frame::Key(compressed) => decompress(&compressed)?,
+            Frame::Delta { length, changes } => patch(self.keyframe.as_ref()?, length, &changes)?,
+        };
+
+        if self.frames_since_keyframe > 1 {
+            self.frames_since_keyframe -= 1;
+        } else {
+            self.restore_previous_keyframe();
+        }
+
+        Some(Snapshot::from_payload(payload))
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Bytes used by the stored frames.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.size = 0;
+        self.keyframe = None;
+        self.frames_since_keyframe = 0;
+    }
+
+    fn restore_previous_keyframe(&mut self) {
+        let position = self
+            .frames
+            .iter()
+            .rposition(|frame| matches!(frame, Frame::Key(_)));
+
+        match position {
+            Some(position) => {
+                self.keyframe = match &self.frames[position] {
+                    Frame::Key(compressed) => decompress(compressed),
+                    Frame::Delta { .. } => None,
+                };
+                self.frames_since_keyframe = self.frames.len() - position;
+            }
+            None => {
+                self.keyframe = None;
+                self.frames_since_keyframe = 0;
+            }
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.size > self.capacity {
+            // the group of the most recent keyframe is never dropped
+            let next_keyframe = self
+                .frames
+                .iter()
+                .skip(1)
+                .position(|frame| matches!(frame, Frame::Key(_)));
+
+            let group = match next_keyframe {
+                Some(position) => position + 1,
+                None => return,
+            };
+
+            for frame in self.frames.drain(..group) {
+                self.size -= frame.size();
+            }
+        }
+    }
+}
+
+// Delta encoding: a sequence of (unchanged bytes to skip, changed bytes count,
+// changed bytes), with the counts as LEB128 varints, written over `changes`.
+fn diff(bytes: &[u8], base: &[u8], changes: &mut Vec<u8>) {
+    changes.clear();
+    let mut index = 0;
+    let mut last_change = 0;
+
+    while index < bytes.len() {
+        if base.get(index) == Some(&bytes[index]) {
+            index += 1;
+            continue;
+        }
+
+        let start = index;
+        while index < bytes.len() && base.get(index) != Some(&bytes[index]) {
+            index += 1;
+        }
+
+        write_varint(changes, start - last_change);
+        write_varint(changes, index - start);
+        changes.extend_from_slice(&bytes[start..index]);
+        last_change = index;
+    }
+}
+
+fn patch(base: &[u8], length: usize, changes: &[u8]) -> Option<Vec<u8>> {
+    let mut bytes = base.to_vec();
+    bytes.resize(length, 0);
+
+    let mut position = 0;
+    let mut index = 0;
+    while position < changes.len() {
+        index += read_varint(changes, &mut position)?;
+        let count = read_varint(changes, &mut position)?;
+
+        bytes
+            .get_mut(index..index + count)?
+            .copy_from_slice(changes.get(position..position + count)?);
+        index += count;
+        position += count;
+    }
+
+    Some(bytes)
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        bytes.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &[u8], position: &mut usize) -> Option<usize> {
+    let mut value = 0;
+    let mut shift = 0;
+
+    loop {
+        let byte = *bytes.get(*position)?;
+        *position += 1;
+        value |= ((byte & 0x7f) as usize) << shift;
+
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+    }
+}
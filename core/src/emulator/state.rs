@@ -0,0 +1,270 @@
+use crate::emulator::audio::{AudioPattern, PATTERN_SIZE};
+use crate::emulator::checksum::crc32;
+use crate::emulator::compression::{compress, decompress};
+use crate::emulator::cpu::rng::Rng;
+use crate::emulator::cpu::Cpu;
+use crate::emulator::display::{Display, PLANES};
+use crate::emulator::memory;
+use thiserror::Error;
+
+// Save state file layout, numbers are big endian:
+//
+// | magic "C8ST" | version u8 | payload length u32 | compressed length u32 | payload crc32 u32 |
+// | payload compressed with PackBits ...                                                      |
+const MAGIC: &[u8; 4] = b"C8ST";
+const VERSION: u8 = 1;
+const HEADER_SIZE: usize = 17;
+
+#[derive(Debug, Error)]
+pub enum StateError {
+    #[error("Not a save state")]
+    InvalidMagic,
+    #[error("Unsupported save state version: `{0}`")]
+    UnsupportedVersion(u8),
+    #[error("Save state checksum mismatch")]
+    ChecksumMismatch,
+    #[error("Corrupted save state")]
+    Corrupted,
+}
+
+/// Complete state of a machine at one point of the emulation.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Snapshot {
+    payload: Vec<u8>,
+}
+
+impl Snapshot {
+    pub fn capture(cpu: &Cpu, display: &Display) -> Self {
+        let mut snapshot = Self {
+            payload: Vec::with_capacity(memory::SIZE + 4096),
+        };
+        snapshot.capture_into(cpu, display);
+
+        snapshot
+    }
+
+    /// Captures the state over the previous one, reusing its buffer.
+    pub fn capture_into(&mut self, cpu: &Cpu, display: &Display) {
+        let payload = &mut self.payload;
+        payload.clear();
+
+        let registers = cpu.registers();
+        for x in 0..16 {
+            payload.push(registers.register(x).unwrap_or_default());
+        }
+        payload.extend_from_slice(&registers.i().to_be_bytes());
+        payload.push(registers.dt());
+        payload.push(registers.st());
+        payload.extend_from_slice(&registers.pc().to_be_bytes());
+        payload.push(registers.stack().len() as u8);
+        for addr in registers.stack() {
+            payload.extend_from_slice(&addr.to_be_bytes());
+        }
+
+        payload.push(cpu.is_waiting_key() as u8);
+        payload.push(cpu.waiting_key_register());
+        payload.push(cpu.is_exited() as u8);
+        payload.push(cpu.is_waiting_display() as u8);
+        payload.extend_from_slice(cpu.rpl_flags());
+        payload.extend_from_slice(cpu.audio_pattern().buffer());
+        payload.push(cpu.audio_pattern().pitch());
+        for word in cpu.rng().state().iter() {
+            payload.extend_from_slice(&word.to_be_bytes());
+        }
+
+        payload.extend_from_slice(cpu.memory().bytes());
+
+        payload.push(display.is_hires() as u8);
+        payload.push(display.selected_planes_mask());
+        for plane in 0..PLANES {
+            // 8 pixels a byte, the leftmost in the most significant bit
+            let mut byte = 0;
+            for y in 0..display.height() {
+                for x in 0..display.width() {
+                    byte = byte << 1 | display.plane_pixel(plane, x, y) as u8;
+                    if x % 8 == 7 {
+                        payload.push(byte);
+                        byte = 0;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Restores the state, leaving the machine untouched when the snapshot is corrupted.
+    pub fn restore(&self, cpu: &mut Cpu, display: &mut Display) -> Result<(), StateError> {
+        let mut reader = Reader::new(&self.payload);
+
+        let v = reader.bytes(16)?;
+        let i = reader.u16()?;
+        let dt = reader.u8()?;
+        let st = reader.u8()?;
+        let pc = reader.u16()?;
+        let stack_size = reader.u8()?;
+        let stack = (0..stack_size)
+            .map(|_| reader.u16())
+            .collect::<Result<Vec<u16>, StateError>>()?;
+
+        let is_waiting_key = reader.u8()? != 0;
+        let waiting_key_register = reader.u8()?;
+        let is_exited = reader.u8()? != 0;
+        let is_waiting_display = reader.u8()? != 0;
+        let mut rpl_flags = [0; 16];
+        rpl_flags.copy_from_slice(reader.bytes(16)?);
+        let mut buffer = [0; PATTERN_SIZE];
+        buffer.copy_from_slice(reader.bytes(PATTERN_SIZE)?);
+        let pitch = reader.u8()?;
+        let rng = Rng::new([reader.u64()?, reader.u64()?]);
+
+        let memory = reader.bytes(memory::SIZE)?;
+
+        let hires = reader.u8()? != 0;
+        let selected_planes = reader.u8()?;
+        let size = if hires {
+            Display::max_width() * Display::max_height()
+        } else {
+            Display::max_width() * Display::max_height() / 4
+        };
+        let planes = (0..PLANES)
+            .map(|_| reader.bytes(size / 8))
+            .collect::<Result<Vec<&[u8]>, StateError>>()?;
+
+        if !reader.is_at_end() || waiting_key_register > 0xf || stack.len() > 16 {
+            return Err(StateError::Corrupted);
+        }
+
+        let registers = cpu.registers_mut();
+        for (x, value) in v.iter().enumerate() {
+            registers
+                .set_register(x as u8, *value)
+                .map_err(|_| StateError::Corrupted)?;
+        }
+        registers.set_i(i);
+        registers.set_dt(dt);
+        registers.set_st(st);
+        registers.set_pc(pc);
+        registers.set_stack(stack);
+
+        cpu.set_waiting_key(is_waiting_key, waiting_key_register);
+        cpu.set_exited(is_exited);
+        cpu.set_waiting_display(is_waiting_display);
+        cpu.set_rpl_flags(rpl_flags);
+        cpu.set_audio_pattern(AudioPattern::new(buffer, pitch));
+        cpu.set_rng(rng);
+        cpu.memory_mut().set_bytes(memory);
+
+        display.set_hires(hires);
+        let width = display.width();
+        for (plane, pixels) in planes.iter().enumerate() {
+            for index in 0..size {
+                if (pixels[index / 8] >> (7 - index % 8)) & 0x1 == 1 {
+                    display.set_plane_pixel(plane, index % width, index / width, true);
+                }
+            }
+        }
+        display.select_planes(selected_planes);
+
+        Ok(())
+    }
+
+    /// Uncompressed state, as laid out in the save state payload.
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    pub fn from_payload(payload: Vec<u8>) -> Self {
+        Self { payload }
+    }
+
+    /// Serializes the snapshot in the versioned save state format.
+    pub fn encode(&self) -> Vec<u8> {
+        let compressed = compress(&self.payload);
+
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + compressed.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&(self.payload.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&(compressed.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&crc32(&self.payload).to_be_bytes());
+        bytes.extend_from_slice(&compressed);
+
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, StateError> {
+        let mut reader = Reader::new(bytes);
+
+        if reader
+            .bytes(MAGIC.len())
+            .map_err(|_| StateError::InvalidMagic)?
+            != MAGIC
+        {
+            return Err(StateError::InvalidMagic);
+        }
+
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+
+        let payload_length = reader.u32()? as usize;
+        let compressed_length = reader.u32()? as usize;
+        let checksum = reader.u32()?;
+
+        let payload = decompress(reader.bytes(compressed_length)?).ok_or(StateError::Corrupted)?;
+        if payload.len() != payload_length {
+            return Err(StateError::Corrupted);
+        }
+
+        if crc32(&payload) != checksum {
+            return Err(StateError::ChecksumMismatch);
+        }
+
+        Ok(Self { payload })
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], StateError> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + count)
+            .ok_or(StateError::Corrupted)?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.position == self.bytes.len()
+    }
+
+    fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, StateError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, StateError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.bytes(4)?);
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> Result<u64, StateError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_be_bytes(bytes))
+    }
+}
@@ -1 +1,4 @@
+pub mod decompiler;
+pub mod disassembler;
 pub mod emulator;
+pub mod source_map;
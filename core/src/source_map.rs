@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+// Source map text layout, a header line followed by one line per
+// instruction, fields separated by tabs:
+//
+// <address, hex> <line> <column> <source file>
+const HEADER: &str = "CHIP-8 source map v1";
+
+#[derive(Debug, Error)]
+pub enum SourceMapError {
+    #[error("Not a source map")]
+    InvalidHeader,
+    #[error("Invalid source map entry at line {0}")]
+    InvalidEntry(usize),
+}
+
+/// Position of an instruction in its source, lines and columns start at 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    file: String,
+    line: usize,
+    column: usize,
+}
+
+impl SourceLocation {
+    pub fn new(file: &str, line: usize, column: usize) -> Self {
+        Self {
+            file: file.to_string(),
+            line,
+            column,
+        }
+    }
+
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+/// Source location of the instructions of a rom, written by the assemblers
+/// for debuggers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    locations: BTreeMap<u16, SourceLocation>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, address: u16, location: SourceLocation) {
+        self.locations.insert(address, location);
+    }
+
+    pub fn location(&self, address: u16) -> Option<&SourceLocation> {
+        self.locations.get(&address)
+    }
+
+    /// Addresses of the instructions of a source line, in ascending order.
+    pub fn addresses<'a>(&'a self, file: &'a str, line: usize) -> impl Iterator<Item = u16> + 'a {
+        self.iter()
+            .filter(move |(_, location)| location.file == file && location.line == line)
+            .map(|(address, _)| address)
+    }
+
+    /// Source files with at least one instruction.
+    pub fn files(&self) -> Vec<&str> {
+        let mut files: Vec<&str> = self
+            .locations
+            .values()
+            .map(|location| location.file.as_str())
+            .collect();
+        files.sort_unstable();
+        files.dedup();
+        files
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &SourceLocation)> {
+        self.locations
+            .iter()
+            .map(|(address, location)| (*address, location))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+
+    pub fn encode(&self) -> String {
+        let mut text = format!("{}\n", HEADER);
+        for (address, location) in self.iter() {
+            text += &format!(
+                "{:04x}\t{}\t{}\t{}\n",
+                address, location.line, location.column, location.file
+            );
+        }
+
+        text
+    }
+
+    pub fn decode(text: &str) -> Result<Self, SourceMapError> {
+        let mut lines = text.lines();
+        if lines.next().map(str::trim_end) != Some(HEADER) {
+            return Err(SourceMapError::InvalidHeader);
+        }
+
+        let mut source_map = Self::new();
+        for (index, entry) in lines.enumerate().filter(|(_, entry)| !entry.is_empty()) {
+            let invalid = || SourceMapError::InvalidEntry(index + 2);
+
+            let mut fields = entry.splitn(4, '\t');
+            let address = u16::from_str_radix(fields.next().ok_or_else(invalid)?, 16)
+                .map_err(|_| invalid())?;
+            let line = fields
+                .next()
+                .and_then(|line| line.parse().ok())
+                .ok_or_else(invalid)?;
+            let column = fields
+                .next()
+                .and_then(|column| column.parse().ok())
+                .ok_or_else(invalid)?;
+            let file = fields.next().ok_or_else(invalid)?;
+
+            source_map.insert(address, SourceLocation::new(file, line, column));
+        }
+
+        Ok(source_map)
+    }
+}
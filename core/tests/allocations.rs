@@ -0,0 +1,115 @@
+//! Counts the heap allocations of the emulation once the machine is built,
+//! which must be none: a frontend runs frames for as long as the rom does.
+
+use core::emulator::chip8::Chip8;
+use core::emulator::keyboard::KeyboardState;
+use core::emulator::platform::Platform;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // only the allocations of the thread running the frames are counted
+    static IS_COUNTING: Cell<bool> = const { Cell::new(false) };
+}
+
+fn count() {
+    if IS_COUNTING.try_with(Cell::get).unwrap_or(false) {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const FRAMES: u64 = 600;
+
+// allocations made running `FRAMES` frames, pressing a different key every 8
+fn run(rom: &[u8], platform: Platform, decode_cache: bool) -> usize {
+    let mut chip8 = Chip8::builder()
+        .rom(rom)
+        .platform(platform)
+        .instructions_per_frame(100)
+        .decode_cache(decode_cache)
+        .build()
+        .unwrap();
+
+    ALLOCATIONS.store(0, Ordering::SeqCst);
+    IS_COUNTING.with(|is_counting| is_counting.set(true));
+    for frame in 0..FRAMES {
+        chip8.set_keyboard_state(KeyboardState::from_mask(1 << (frame / 8 % 16)));
+        if chip8.run_frame().is_err() || chip8.is_exited() {
+            break;
+        }
+    }
+    IS_COUNTING.with(|is_counting| is_counting.set(false));
+
+    ALLOCATIONS.load(Ordering::SeqCst)
+}
+
+#[test]
+fn frames_do_not_allocate() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+    let mut roms: Vec<(String, Vec<u8>)> = [
+        "test_opcode.ch8",
+        "Brix [Andreas Gustafsson, 1990].ch8",
+        "Pong (alt).ch8",
+        "Tetris [Fran Dachille, 1991].ch8",
+    ]
+    .iter()
+    .map(|rom| (rom.to_string(), fs::read(root.join(rom)).unwrap()))
+    .collect();
+
+    // switches resolution, clears, scrolls, draws and calls in a loop
+    #[rustfmt::skip]
+    let hires = vec![
+        0x00, 0xff, // 200: HIGH
+        0x00, 0xe0, // 202: CLS
+        0xa2, 0x00, // 204: LD I, 0x200
+        0xd0, 0x15, // 206: DRW V0, V1, 5
+        0x00, 0xfb, // 208: SCR
+        0x00, 0xc2, // 20A: SCD 2
+        0x00, 0xfe, // 20C: LOW
+        0x22, 0x14, // 20E: CALL 0x214
+        0x12, 0x00, // 210: JP 0x200
+        0x00, 0x00, // 212
+        0xf2, 0x65, // 214: LD V2, [I]
+        0x00, 0xee, // 216: RET
+    ];
+    roms.push(("hires".to_string(), hires));
+
+    for (name, rom) in &roms {
+        for platform in &[Platform::Chip8, Platform::SuperChip, Platform::XoChip] {
+            for decode_cache in &[false, true] {
+                let allocations = run(rom, *platform, *decode_cache);
+                assert_eq!(
+                    allocations, 0,
+                    "{} on {:?}, decode cache {}",
+                    name, platform, decode_cache
+                );
+            }
+        }
+    }
+}
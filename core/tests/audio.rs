@@ -0,0 +1,62 @@
+//! XO-CHIP audio patterns: the rate their bits are played at and the order
+//! they are read in.
+
+use core::emulator::audio::{AudioPattern, Waveform, PATTERN_SIZE};
+
+// pattern with only the first bit and the last one set
+fn edges() -> [u8; PATTERN_SIZE] {
+    let mut buffer = [0; PATTERN_SIZE];
+    buffer[0] = 0x80;
+    buffer[PATTERN_SIZE - 1] = 0x01;
+    buffer
+}
+
+#[test]
+fn sample_rate_doubles_every_48_pitch_steps() {
+    let cases = [(64, 4000.0), (112, 8000.0), (16, 2000.0), (88, 5656.854)];
+    for (pitch, rate) in &cases {
+        let pattern = AudioPattern::new([0; PATTERN_SIZE], *pitch);
+        assert!(
+            (pattern.sample_rate() - rate).abs() < 0.01,
+            "pitch {}: {}",
+            pitch,
+            pattern.sample_rate()
+        );
+    }
+
+    assert_eq!(AudioPattern::default().sample_rate(), 4000.0);
+}
+
+#[test]
+fn bits_are_played_from_the_most_significant_of_the_first_byte() {
+    let pattern = AudioPattern::new(edges(), 64);
+    let samples: Vec<f32> = (0..128).map(|index| pattern.sample(index)).collect();
+
+    assert_eq!(samples[0], 1.0);
+    assert_eq!(samples[127], 1.0);
+    assert!(samples[1..127].iter().all(|sample| *sample == -1.0));
+
+    // the pattern loops
+    assert_eq!(pattern.sample(128), 1.0);
+    assert_eq!(pattern.sample(129), -1.0);
+}
+
+#[test]
+fn waveform_resamples_the_pattern_to_the_output_rate() {
+    let mut waveform = Waveform::default();
+    waveform.set_pattern(AudioPattern::new([0xaa; PATTERN_SIZE], 64));
+
+    // one bit per sample at the pattern rate
+    let samples: Vec<f32> = (0..4).map(|_| waveform.next_sample(4000.0)).collect();
+    assert_eq!(samples, vec![1.0, -1.0, 1.0, -1.0]);
+
+    // each bit twice at double the rate
+    let samples: Vec<f32> = (0..4).map(|_| waveform.next_sample(8000.0)).collect();
+    assert_eq!(samples, vec![1.0, 1.0, -1.0, -1.0]);
+
+    // wrapping around past the last bit
+    let mut waveform = Waveform::default();
+    waveform.set_pattern(AudioPattern::new(edges(), 64));
+    let samples: Vec<f32> = (0..130).map(|_| waveform.next_sample(4000.0)).collect();
+    assert_eq!(samples[126..], [-1.0, 1.0, 1.0, -1.0]);
+}
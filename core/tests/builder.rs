@@ -0,0 +1,33 @@
+//! Machines built with or without a platform report the one whose quirks
+//! they run with.
+
+use core::emulator::chip8::Chip8;
+use core::emulator::platform::Platform;
+use core::emulator::quirks::Quirks;
+
+#[test]
+fn machine_without_platform_runs_cowgod_quirks() {
+    let chip8 = Chip8::builder().build().unwrap();
+    assert_eq!(chip8.platform(), None);
+    assert_eq!(*chip8.quirks(), Quirks::COWGOD);
+}
+
+#[test]
+fn machine_runs_the_quirks_of_its_platform() {
+    for platform in &[Platform::Chip8, Platform::SuperChip, Platform::XoChip] {
+        let chip8 = Chip8::builder().platform(*platform).build().unwrap();
+        assert_eq!(chip8.platform(), Some(*platform));
+        assert_eq!(*chip8.quirks(), platform.quirks());
+    }
+}
+
+#[test]
+fn quirks_override_those_of_the_platform() {
+    let chip8 = Chip8::builder()
+        .platform(Platform::XoChip)
+        .quirks(Quirks::CHIP_48)
+        .build()
+        .unwrap();
+    assert_eq!(chip8.platform(), Some(Platform::XoChip));
+    assert_eq!(*chip8.quirks(), Quirks::CHIP_48);
+}
@@ -0,0 +1,112 @@
+//! Runs the roms bundled in the root of the project and compares the final
+//! display with the golden one in `tests/golden`, drawn with a `.` for every
+//! pixel off and a `#` for every pixel on, as `chip8-headless --ascii` does.
+//!
+//! Run with `CHIP8_BLESS=1` to write the golden displays again after an
+//! intended change.
+
+use core::emulator::chip8::Chip8;
+use core::emulator::display::Display;
+use core::emulator::platform::Platform;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+// characters of the pixels lit on no plane, on the first, on the second and on both
+const PALETTE: [char; 4] = ['.', '#', '+', '@'];
+
+fn check(rom: &str, platform: Platform, frames: u64, golden: &str) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let rom = fs::read(root.join("..").join(rom)).unwrap();
+    let golden_file = root.join("tests").join("golden").join(golden);
+
+    let mut chip8 = Chip8::builder()
+        .rom(&rom)
+        .platform(platform)
+        .build()
+        .unwrap();
+    for _ in 0..frames {
+        if chip8.is_exited() || is_stuck(&chip8) {
+            break;
+        }
+        chip8.run_frame().unwrap();
+    }
+    let display = ascii(chip8.display());
+
+    if env::var_os("CHIP8_BLESS").is_some() {
+        fs::write(&golden_file, &display).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&golden_file).unwrap();
+    assert!(
+        display == expected,
+        "display differs from {}:\n{}",
+        golden_file.display(),
+        display
+    );
+}
+
+// The rom jumps to the jump it is executing, the usual way to end a test rom.
+fn is_stuck(chip8: &Chip8) -> bool {
+    let pc = chip8.registers().pc();
+    pc < 0x1000 && chip8.memory().read_16(pc) == 0x1000 | pc
+}
+
+fn ascii(display: &Display) -> String {
+    let pixels: Vec<u8> = display.pixels().collect();
+
+    pixels
+        .chunks(display.width())
+        .map(|row| {
+            let mut line: String = row.iter().map(|color| PALETTE[*color as usize]).collect();
+            line.push('\n');
+            line
+        })
+        .collect()
+}
+
+#[test]
+fn test_opcode_chip8() {
+    check("test_opcode.ch8", Platform::Chip8, 600, "test_opcode.txt");
+}
+
+#[test]
+fn test_opcode_schip() {
+    check(
+        "test_opcode.ch8",
+        Platform::SuperChip,
+        600,
+        "test_opcode.txt",
+    );
+}
+
+#[test]
+fn test_opcode_xochip() {
+    check("test_opcode.ch8", Platform::XoChip, 600, "test_opcode.txt");
+}
+
+#[test]
+fn brix() {
+    check(
+        "Brix [Andreas Gustafsson, 1990].ch8",
+        Platform::Chip8,
+        120,
+        "brix.txt",
+    );
+}
+
+#[test]
+fn pong() {
+    check("Pong (alt).ch8", Platform::Chip8, 120, "pong.txt");
+}
+
+#[test]
+fn tetris() {
+    check(
+        "Tetris [Fran Dachille, 1991].ch8",
+        Platform::Chip8,
+        120,
+        "tetris.txt",
+    );
+}
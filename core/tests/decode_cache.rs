@@ -0,0 +1,74 @@
+//! Runs the same roms with and without the decode cache, which must never
+//! change what the machine does, self-modifying code included.
+
+use core::emulator::chip8::Chip8;
+use core::emulator::platform::Platform;
+use std::fs;
+use std::path::Path;
+
+fn run(rom: &[u8], platform: Platform, frames: u64, decode_cache: bool) -> Chip8 {
+    let mut chip8 = Chip8::builder()
+        .rom(rom)
+        .platform(platform)
+        .decode_cache(decode_cache)
+        .build()
+        .unwrap();
+
+    for _ in 0..frames {
+        chip8.run_frame().unwrap();
+    }
+    chip8
+}
+
+fn check(rom: &[u8], platform: Platform, frames: u64) {
+    let decoding = run(rom, platform, frames, false);
+    let cached = run(rom, platform, frames, true);
+
+    assert!(cached.memory().is_decode_cache_enabled());
+    assert_eq!(cached.registers().pc(), decoding.registers().pc());
+    assert_eq!(cached.registers().i(), decoding.registers().i());
+    for x in 0..16 {
+        assert_eq!(
+            cached.registers().register(x).unwrap(),
+            decoding.registers().register(x).unwrap()
+        );
+    }
+    assert_eq!(cached.memory().bytes(), decoding.memory().bytes());
+    assert!(cached.display().pixels().eq(decoding.display().pixels()));
+}
+
+#[test]
+fn bundled_roms() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+    let roms = [
+        "test_opcode.ch8",
+        "Brix [Andreas Gustafsson, 1990].ch8",
+        "Pong (alt).ch8",
+        "Tetris [Fran Dachille, 1991].ch8",
+    ];
+    for rom in &roms {
+        let rom = fs::read(root.join(rom)).unwrap();
+        for platform in &[Platform::Chip8, Platform::SuperChip, Platform::XoChip] {
+            check(&rom, *platform, 120);
+        }
+    }
+}
+
+#[test]
+fn self_modifying_code() {
+    #[rustfmt::skip]
+    let rom = [
+        0x22, 0x0c, // 200: CALL 0x20C, adds 1 to V3
+        0x60, 0x05, // 202: LD V0, 0x05
+        0xa2, 0x0d, // 204: LD I, 0x20D
+        0xf0, 0x55, // 206: LD [I], V0, the subroutine now adds 5
+        0x22, 0x0c, // 208: CALL 0x20C, runs the rewritten subroutine
+        0x12, 0x0a, // 20A: JP 0x20A
+        0x73, 0x01, // 20C: ADD V3, 0x01
+        0x00, 0xee, // 20E: RET
+    ];
+
+    check(&rom, Platform::XoChip, 2);
+    let chip8 = run(&rom, Platform::XoChip, 2, true);
+    assert_eq!(chip8.registers().register(3).unwrap(), 6);
+}
@@ -0,0 +1,125 @@
+//! Movies: a recorded run replays to the exact same machine state, and
+//! damaged movie files are rejected.
+
+use core::emulator::checksum::crc32;
+use core::emulator::chip8::Chip8;
+use core::emulator::keyboard::KeyboardState;
+use core::emulator::movie::{Movie, MovieError, MoviePlayer, MovieRecorder};
+use core::emulator::quirks::Quirks;
+use std::fs;
+use std::path::Path;
+
+const FRAMES: u64 = 600;
+
+fn rom(name: &str) -> Vec<u8> {
+    fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("..").join(name)).unwrap()
+}
+
+// records `FRAMES` frames of Brix, which draws random numbers, pressing a
+// different key every 8 frames
+fn record() -> (Chip8, Movie) {
+    let mut chip8 = Chip8::builder()
+        .rom(&rom("Brix [Andreas Gustafsson, 1990].ch8"))
+        .quirks(Quirks::SCHIP_1_1)
+        .seed(0x1234_5678_9abc_def0)
+        .instructions_per_frame(15)
+        .build()
+        .unwrap();
+    let mut recorder = MovieRecorder::new(&chip8);
+
+    for frame in 0..FRAMES {
+        let keyboard_state = KeyboardState::from_mask(1 << (frame / 8 % 16));
+        recorder.record(&keyboard_state);
+        chip8.set_keyboard_state(keyboard_state);
+        chip8.run_frame().unwrap();
+    }
+
+    (chip8, recorder.finish())
+}
+
+#[test]
+fn replayed_movie_reaches_the_recorded_state() {
+    let (recorded, movie) = record();
+    let movie = Movie::decode(&movie.encode()).unwrap();
+    assert_eq!(movie.frames().len(), FRAMES as usize);
+
+    let mut replayed = movie
+        .chip8_builder(recorded.rom())
+        .unwrap()
+        .build()
+        .unwrap();
+    let mut player = MoviePlayer::new(movie);
+    while let Some(keyboard_state) = player.next_frame() {
+        replayed.set_keyboard_state(keyboard_state);
+        replayed.run_frame().unwrap();
+    }
+
+    assert!(player.is_finished());
+    assert_eq!(replayed.save_state(), recorded.save_state());
+}
+
+#[test]
+fn movie_of_another_rom_is_rejected() {
+    let (_, movie) = record();
+    assert!(matches!(
+        movie.chip8_builder(&rom("Pong (alt).ch8")),
+        Err(MovieError::RomMismatch)
+    ));
+}
+
+#[test]
+fn corrupted_checksum_is_rejected() {
+    let mut bytes = record().1.encode();
+    // keys of the first frame
+    bytes[27] ^= 0xff;
+    assert!(matches!(
+        Movie::decode(&bytes),
+        Err(MovieError::ChecksumMismatch)
+    ));
+}
+
+#[test]
+fn bad_magic_is_rejected() {
+    let mut bytes = record().1.encode();
+    bytes[0] = b'X';
+    assert!(matches!(
+        Movie::decode(&bytes),
+        Err(MovieError::InvalidMagic)
+    ));
+}
+
+#[test]
+fn unsupported_version_is_rejected() {
+    let mut bytes = record().1.encode();
+    bytes[4] = 2;
+    assert!(matches!(
+        Movie::decode(&bytes),
+        Err(MovieError::UnsupportedVersion(2))
+    ));
+}
+
+#[test]
+fn frame_count_other_than_the_keys_is_rejected() {
+    let mut bytes = record().1.encode();
+    for frame_count in &[FRAMES as u32 - 1, FRAMES as u32 + 1, u32::MAX] {
+        // keeps the checksum valid, so only the count is wrong
+        let length = bytes.len() - 4;
+        bytes[23..27].copy_from_slice(&frame_count.to_be_bytes());
+        let checksum = crc32(&bytes[..length]);
+        bytes[length..].copy_from_slice(&checksum.to_be_bytes());
+
+        assert!(
+            matches!(Movie::decode(&bytes), Err(MovieError::Corrupted)),
+            "{}",
+            frame_count
+        );
+    }
+}
+
+#[test]
+fn truncated_movie_is_rejected() {
+    let bytes = record().1.encode();
+    for length in 0..bytes.len() {
+        assert!(Movie::decode(&bytes[..length]).is_err(), "{}", length);
+    }
+}
@@ -0,0 +1,92 @@
+//! Checks that every valid opcode is decoded and encoded again to the same
+//! 16 bits, and that the display of the decoded instruction matches the
+//! mnemonics read by the assembler.
+
+use core::emulator::cpu::opcode::{Opcode, OpcodeError};
+
+#[test]
+fn decode_encode_round_trip() {
+    let mut valid = 0;
+    for opcode in 0..=0xffff {
+        if let Ok(decoded) = Opcode::decode(opcode) {
+            assert_eq!(
+                decoded.encode().unwrap(),
+                opcode,
+                "{:04X} ({})",
+                opcode,
+                decoded
+            );
+            valid += 1;
+        }
+    }
+
+    // 10 prefixes with any operand, 5xy0/2/3, the 9 8xyN, 9xy0, 37 opcodes
+    // starting with 0, ExNN and FxNN
+    let expected = 10 * 0x1000 + 3 * 0x100 + 9 * 0x100 + 0x100 + 37 + 2 * 0x10 + 226;
+    assert_eq!(valid, expected);
+}
+
+#[test]
+fn invalid_opcodes() {
+    for opcode in &[
+        0x0000, 0x00c0, 0x00d0, 0x0123, 0x9121, 0xe1a0, 0xf1ff, 0xf100, 0xf102,
+    ] {
+        assert!(Opcode::decode(*opcode).is_err(), "{:04X}", opcode);
+    }
+}
+
+#[test]
+fn operands_out_of_range_are_not_encoded() {
+    for opcode in &[
+        Opcode::Scd(0),
+        Opcode::Scd(0x10),
+        Opcode::Scu(0),
+        Opcode::Scu(0x10),
+        Opcode::Jp(0x1000),
+        Opcode::Ldi(0xffff),
+        Opcode::Se { x: 0x10, byte: 0 },
+        Opcode::Draw {
+            x: 0,
+            y: 0x10,
+            n: 0,
+        },
+        Opcode::Draw {
+            x: 0,
+            y: 0,
+            n: 0x10,
+        },
+        Opcode::Plane(0x10),
+        Opcode::LdB(0x10),
+    ] {
+        assert!(
+            matches!(opcode.encode(), Err(OpcodeError::InvalidOperands(invalid)) if invalid == *opcode),
+            "{:?}",
+            opcode
+        );
+    }
+
+    assert_eq!(Opcode::Scd(1).encode().unwrap(), 0x00c1);
+    assert_eq!(Opcode::Jp(0xfff).encode().unwrap(), 0x1fff);
+}
+
+#[test]
+fn display() {
+    let cases = [
+        (0x00e0, "CLS"),
+        (0x00c4, "SCD 4"),
+        (0x1228, "JP 0x228"),
+        (0x2020, "CALL 0x020"),
+        (0x3a7f, "SE VA, 0x7F"),
+        (0x5120, "SE V1, V2"),
+        (0x5122, "SAVE V1, V2"),
+        (0x8ab6, "SHR VA, VB"),
+        (0xb300, "JP V0, 0x300"),
+        (0xd125, "DRW V1, V2, 5"),
+        (0xf000, "LD I, LONG"),
+        (0xf201, "PLANE 2"),
+        (0xf365, "LD V3, [I]"),
+    ];
+    for (opcode, mnemonic) in &cases {
+        assert_eq!(Opcode::decode(*opcode).unwrap().to_string(), *mnemonic);
+    }
+}
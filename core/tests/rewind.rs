@@ -0,0 +1,106 @@
+//! Rewind history: frames come back most recent first across keyframe
+//! boundaries, and the oldest ones are dropped to stay within the byte budget.
+
+use core::emulator::rewind::RewindBuffer;
+use core::emulator::state::Snapshot;
+
+const LENGTH: usize = 4096;
+
+// noise that barely compresses, with the frame number written every 512 bytes
+// so that deltas stay small next to keyframes
+fn frame(number: usize) -> Snapshot {
+    let mut seed = 0x2545_f491_u32;
+    let mut payload = (0..LENGTH)
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as u8
+        })
+        .collect::<Vec<u8>>();
+    for chunk in payload.chunks_mut(512) {
+        chunk[..8].copy_from_slice(&(number as u64).to_be_bytes());
+    }
+
+    Snapshot::from_payload(payload)
+}
+
+fn pop_all(buffer: &mut RewindBuffer) -> Vec<Snapshot> {
+    std::iter::from_fn(|| buffer.pop()).collect()
+}
+
+#[test]
+fn frames_come_back_most_recent_first() {
+    let mut buffer = RewindBuffer::new(usize::MAX, 4);
+    for number in 0..10 {
+        buffer.push(&frame(number));
+    }
+    assert_eq!(buffer.len(), 10);
+
+    let popped = pop_all(&mut buffer);
+    let expected = (0..10).rev().map(frame).collect::<Vec<_>>();
+    assert_eq!(popped, expected);
+    assert!(buffer.is_empty());
+    assert_eq!(buffer.size(), 0);
+}
+
+#[test]
+fn pushes_after_pops_continue_from_the_popped_frame() {
+    let mut buffer = RewindBuffer::new(usize::MAX, 4);
+    for number in 0..6 {
+        buffer.push(&frame(number));
+    }
+
+    // back into the first keyframe group, then forward again past the next
+    for number in (2..6).rev() {
+        assert_eq!(buffer.pop(), Some(frame(number)));
+    }
+    for number in 10..16 {
+        buffer.push(&frame(number));
+    }
+
+    let popped = pop_all(&mut buffer);
+    let expected = (10..16)
+        .rev()
+        .chain((0..2).rev())
+        .map(frame)
+        .collect::<Vec<_>>();
+    assert_eq!(popped, expected);
+}
+
+#[test]
+fn oldest_frames_are_evicted_within_the_byte_budget() {
+    // room for a little more than two keyframe groups
+    let group = {
+        let mut buffer = RewindBuffer::new(usize::MAX, 4);
+        for number in 0..4 {
+            buffer.push(&frame(number));
+        }
+        buffer.size()
+    };
+    let capacity = group * 5 / 2;
+
+    let mut buffer = RewindBuffer::new(capacity, 4);
+    for number in 0..40 {
+        buffer.push(&frame(number));
+        assert!(buffer.size() <= capacity, "frame {}", number);
+    }
+
+    // whole groups are dropped, the kept frames are the most recent ones
+    assert_eq!(buffer.len(), 8);
+    let popped = pop_all(&mut buffer);
+    let expected = (32..40).rev().map(frame).collect::<Vec<_>>();
+    assert_eq!(popped, expected);
+}
+
+#[test]
+fn budget_smaller_than_a_group_keeps_the_latest_group() {
+    let mut buffer = RewindBuffer::new(1, 4);
+    for number in 0..10 {
+        buffer.push(&frame(number));
+    }
+
+    let popped = pop_all(&mut buffer);
+    let expected = (8..10).rev().map(frame).collect::<Vec<_>>();
+    assert_eq!(popped, expected);
+}
@@ -0,0 +1,138 @@
+//! Save states: a snapshot encoded, decoded and restored into another machine
+//! continues exactly as the original one, and damaged save states are
+//! rejected without touching the machine.
+
+use core::emulator::chip8::Chip8;
+use core::emulator::keyboard::KeyboardState;
+use core::emulator::platform::Platform;
+use core::emulator::state::{Snapshot, StateError};
+use std::fs;
+use std::path::Path;
+
+const ROMS: &[&str] = &[
+    "test_opcode.ch8",
+    "Brix [Andreas Gustafsson, 1990].ch8",
+    "Pong (alt).ch8",
+    "Tetris [Fran Dachille, 1991].ch8",
+];
+
+fn rom(name: &str) -> Vec<u8> {
+    fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("..").join(name)).unwrap()
+}
+
+fn build(rom: &[u8], platform: Platform) -> Chip8 {
+    Chip8::builder()
+        .rom(rom)
+        .platform(platform)
+        .build()
+        .unwrap()
+}
+
+// runs `frames` frames from `first`, pressing a different key every 8
+fn run(chip8: &mut Chip8, first: u64, frames: u64) {
+    for frame in first..first + frames {
+        chip8.set_keyboard_state(KeyboardState::from_mask(1 << (frame / 8 % 16)));
+        chip8.run_frame().unwrap();
+    }
+}
+
+fn encoded_state() -> Vec<u8> {
+    let mut chip8 = build(&rom(ROMS[1]), Platform::Chip8);
+    run(&mut chip8, 0, 30);
+    chip8.save_state().encode()
+}
+
+#[test]
+fn restored_machine_continues_identically() {
+    for name in ROMS {
+        let rom = rom(name);
+        for platform in &[Platform::Chip8, Platform::SuperChip, Platform::XoChip] {
+            let mut original = build(&rom, *platform);
+            run(&mut original, 0, 60);
+            let encoded = original.save_state().encode();
+
+            // a machine in another state, overwritten by the save state
+            let mut restored = build(&rom, *platform);
+            run(&mut restored, 100, 17);
+            restored
+                .load_state(&Snapshot::decode(&encoded).unwrap())
+                .unwrap();
+            assert_eq!(restored.save_state(), original.save_state(), "{}", name);
+
+            run(&mut original, 60, 60);
+            run(&mut restored, 60, 60);
+            assert_eq!(restored.save_state(), original.save_state(), "{}", name);
+            assert!(restored.display().pixels().eq(original.display().pixels()));
+        }
+    }
+}
+
+#[test]
+fn corrupted_checksum_is_rejected() {
+    let mut bytes = encoded_state();
+    // last byte of the payload crc32 in the header
+    bytes[16] ^= 0xff;
+    assert!(matches!(
+        Snapshot::decode(&bytes),
+        Err(StateError::ChecksumMismatch)
+    ));
+}
+
+#[test]
+fn bad_magic_is_rejected() {
+    let mut bytes = encoded_state();
+    bytes[0] = b'X';
+    assert!(matches!(
+        Snapshot::decode(&bytes),
+        Err(StateError::InvalidMagic)
+    ));
+    assert!(matches!(
+        Snapshot::decode(b"C8"),
+        Err(StateError::InvalidMagic)
+    ));
+}
+
+#[test]
+fn unsupported_version_is_rejected() {
+    let mut bytes = encoded_state();
+    bytes[4] = 2;
+    assert!(matches!(
+        Snapshot::decode(&bytes),
+        Err(StateError::UnsupportedVersion(2))
+    ));
+}
+
+#[test]
+fn truncated_save_state_is_rejected() {
+    let bytes = encoded_state();
+    for length in 0..bytes.len() {
+        assert!(
+            Snapshot::decode(&bytes[..length]).is_err(),
+            "{} bytes",
+            length
+        );
+    }
+}
+
+#[test]
+fn failed_restore_leaves_the_machine_untouched() {
+    let snapshot = Snapshot::decode(&encoded_state()).unwrap();
+    let payload = snapshot.payload();
+
+    let mut chip8 = build(&rom(ROMS[0]), Platform::XoChip);
+    run(&mut chip8, 0, 10);
+    let before = chip8.save_state();
+
+    let mut too_long = payload.to_vec();
+    too_long.push(0);
+    let damaged = [
+        payload[..payload.len() - 1].to_vec(),
+        payload[..40].to_vec(),
+        too_long,
+    ];
+    for payload in damaged.iter() {
+        let result = chip8.load_state(&Snapshot::from_payload(payload.clone()));
+        assert!(matches!(result, Err(StateError::Corrupted)));
+        assert_eq!(chip8.save_state(), before);
+    }
+}
@@ -0,0 +1,97 @@
+//! Traces of the first instructions of `test_opcode.ch8` compared with the
+//! golden ones in `tests/golden`, pinning the format of the lines and the
+//! filters on addresses and instruction indexes.
+//!
+//! Run with `CHIP8_BLESS=1` to write the golden traces again after an
+//! intended change.
+
+use core::emulator::chip8::Chip8;
+use core::emulator::cpu::tracer::Tracer;
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+
+const INSTRUCTIONS: u64 = 240;
+
+// lines written by the tracer, kept after it is dropped
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buffer)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// executes `INSTRUCTIONS` instructions through the tracer made by `tracer`
+fn trace(tracer: impl FnOnce(Tracer) -> Tracer) -> String {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let rom = fs::read(root.join("..").join("test_opcode.ch8")).unwrap();
+    let output = Output::default();
+
+    let mut chip8 = Chip8::builder().rom(&rom).build().unwrap();
+    chip8.set_tracer(Some(tracer(Tracer::new(Box::new(output.clone())))));
+    for _ in 0..INSTRUCTIONS {
+        chip8.step().unwrap();
+    }
+    assert_eq!(chip8.tracer_mut().unwrap().count(), INSTRUCTIONS);
+
+    let lines = output.0.borrow();
+    String::from_utf8(lines.clone()).unwrap()
+}
+
+fn check(trace: &str, golden: &str) {
+    let golden_file = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(golden);
+
+    if env::var_os("CHIP8_BLESS").is_some() {
+        fs::write(&golden_file, trace).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&golden_file).unwrap();
+    assert!(
+        trace == expected,
+        "trace differs from {}:\n{}",
+        golden_file.display(),
+        trace
+    );
+}
+
+#[test]
+fn first_instructions() {
+    check(
+        &trace(|tracer| tracer.instructions(0..64)),
+        "test_opcode_trace.txt",
+    );
+}
+
+#[test]
+fn filtered_instructions() {
+    let filtered = trace(|tracer| tracer.addresses(0x300..=0x3db).instructions(100..220));
+    check(&filtered, "test_opcode_trace_filtered.txt");
+
+    // the same lines as the full trace, indexes counting every instruction
+    let full = trace(|tracer| tracer);
+    let expected: String = full
+        .lines()
+        .filter(|line| {
+            let index: u64 = line[..10].parse().unwrap();
+            let address = u16::from_str_radix(&line[11..15], 16).unwrap();
+            (100..220).contains(&index) && (0x300..=0x3db).contains(&address)
+        })
+        .map(|line| format!("{}\n", line))
+        .collect();
+    assert!(!expected.is_empty());
+    assert_eq!(filtered, expected);
+}
@@ -0,0 +1,55 @@
+use crate::protocol::{read_message, Writer};
+use crate::session::Session;
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+mod protocol;
+mod session;
+
+const FRAME_DURATION: Duration = Duration::from_micros(16666);
+
+// Debug Adapter Protocol server speaking to the editor through stdin and stdout.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let (sender, receiver) = mpsc::channel();
+
+    // requests keep coming while the rom runs, they are read on their own thread
+    thread::spawn(move || {
+        let stdin = std::io::stdin();
+        let mut reader = stdin.lock();
+
+        while let Ok(Some(message)) = read_message(&mut reader) {
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut session = Session::new(Writer::new(std::io::stdout()));
+    loop {
+        let message = if session.is_running() {
+            match receiver.try_recv() {
+                Ok(message) => Some(message),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => return Ok(()),
+            }
+        } else {
+            match receiver.recv() {
+                Ok(message) => Some(message),
+                Err(_) => return Ok(()),
+            }
+        };
+
+        if let Some(message) = message {
+            if !session.handle(message)? {
+                return Ok(());
+            }
+            continue;
+        }
+
+        // one frame per 60th of a second, like on the real hardware
+        let frame_start = Instant::now();
+        session.run_frame()?;
+        thread::sleep(FRAME_DURATION.saturating_sub(frame_start.elapsed()));
+    }
+}
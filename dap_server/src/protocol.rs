@@ -0,0 +1,87 @@
+use serde_json::{json, Value};
+use std::io::{BufRead, Error, ErrorKind, Write};
+
+/// Reads the next message sent by the editor, `None` once the input is closed.
+pub fn read_message(reader: &mut impl BufRead) -> std::io::Result<Option<Value>> {
+    // Content-Length: <bytes>\r\n\r\n<json>
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some(length) = header.strip_prefix("Content-Length:") {
+            content_length = length.trim().parse().ok();
+        }
+    }
+
+    let content_length: usize = content_length
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Missing Content-Length header"))?;
+
+    let mut content = vec![0; content_length];
+    reader.read_exact(&mut content)?;
+
+    serde_json::from_slice(&content)
+        .map(Some)
+        .map_err(|err| Error::new(ErrorKind::InvalidData, err))
+}
+
+/// Sends responses and events to the editor.
+pub struct Writer<W> {
+    output: W,
+    seq: u64,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(output: W) -> Self {
+        Self { output, seq: 0 }
+    }
+
+    pub fn response(&mut self, request: &Value, body: Value) -> std::io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": true,
+            "body": body,
+        }))
+    }
+
+    pub fn error_response(&mut self, request: &Value, message: &str) -> std::io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": false,
+            "message": message,
+        }))
+    }
+
+    pub fn event(&mut self, event: &str, body: Value) -> std::io::Result<()> {
+        self.send(json!({
+            "type": "event",
+            "event": event,
+            "body": body,
+        }))
+    }
+
+    fn send(&mut self, mut message: Value) -> std::io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+
+        let content = message.to_string();
+        write!(
+            self.output,
+            "Content-Length: {}\r\n\r\n{}",
+            content.len(),
+            content
+        )?;
+        self.output.flush()
+    }
+}
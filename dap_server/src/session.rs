@@ -0,0 +1,767 @@
+use crate::protocol::Writer;
+use chip8_core::emulator::chip8::Chip8;
+use chip8_core::emulator::debugger::{Debugger, HaltReason};
+use chip8_core::emulator::platform::Platform;
+use chip8_core::emulator::quirks::Quirks;
+use chip8_core::source_map::{SourceLocation, SourceMap};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const THREAD_ID: u64 = 1;
+
+// variables references of the scopes
+const REGISTERS_SCOPE: u64 = 1;
+const TIMERS_SCOPE: u64 = 2;
+const STACK_SCOPE: u64 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StepKind {
+    Into,
+    Over,
+    Out,
+}
+
+// source line step, made of instruction steps until the line changes
+struct Step {
+    kind: StepKind,
+    // file and line the step started from
+    start: Option<(PathBuf, usize)>,
+}
+
+/// State of a debugging session of a single rom.
+pub struct Session<W> {
+    writer: Writer<W>,
+    chip8: Option<Chip8>,
+    debugger: Debugger,
+    source_map: Option<SourceMap>,
+    // the source files of the source map are relative to its directory
+    source_root: PathBuf,
+    // breakpoints set by the editor, replaced as a whole for each source
+    source_breakpoints: HashMap<PathBuf, Vec<u16>>,
+    instruction_breakpoints: Vec<u16>,
+    stop_on_entry: bool,
+    is_configured: bool,
+    is_terminated: bool,
+    step: Option<Step>,
+}
+
+impl<W: Write> Session<W> {
+    pub fn new(writer: Writer<W>) -> Self {
+        Self {
+            writer,
+            chip8: None,
+            debugger: Debugger::new(),
+            source_map: None,
+            source_root: PathBuf::new(),
+            source_breakpoints: HashMap::new(),
+            instruction_breakpoints: vec![],
+            stop_on_entry: false,
+            is_configured: false,
+            is_terminated: false,
+            step: None,
+        }
+    }
+
+    /// Whether the rom is being emulated, as opposed to waiting for requests.
+    pub fn is_running(&self) -> bool {
+        self.chip8.is_some()
+            && self.is_configured
+            && !self.is_terminated
+            && !self.debugger.is_paused()
+    }
+
+    /// Handles a message of the editor, returns false when the session is over.
+    pub fn handle(&mut self, message: Value) -> std::io::Result<bool> {
+        if message["type"] != "request" {
+            return Ok(true);
+        }
+
+        let command = message["command"].as_str().unwrap_or_default().to_string();
+        let arguments = &message["arguments"];
+
+        let result = match command.as_str() {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsInstructionBreakpoints": true,
+                "supportsSteppingGranularity": true,
+                "supportsTerminateRequest": true,
+            })),
+            "launch" => self.launch(arguments),
+            "setBreakpoints" => self.set_breakpoints(arguments),
+            "setInstructionBreakpoints" => self.set_instruction_breakpoints(arguments),
+            "setExceptionBreakpoints" => Ok(json!({ "breakpoints": [] })),
+            "configurationDone" => {
+                self.is_configured = true;
+                Ok(json!({}))
+            }
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "CHIP-8" }] })),
+            "stackTrace" => self.stack_trace(),
+            "scopes" => Ok(json!({
+                "scopes": [
+                    { "name": "Registers", "variablesReference": REGISTERS_SCOPE, "expensive": false },
+                    { "name": "Timers", "variablesReference": TIMERS_SCOPE, "expensive": false },
+                    { "name": "Stack", "variablesReference": STACK_SCOPE, "expensive": false },
+                ]
+            })),
+            "variables" => self.variables(arguments),
+            "continue" => {
+                self.step = None;
+                self.debugger.resume();
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "next" => self.start_step(StepKind::Over, arguments),
+            "stepIn" => self.start_step(StepKind::Into, arguments),
+            "stepOut" => self.start_step(StepKind::Out, arguments),
+            "pause" => self.pause(),
+            "disconnect" | "terminate" => Ok(json!({})),
+            _ => Err(format!("Unsupported request: `{}`", command)),
+        };
+
+        match result {
+            Ok(body) => self.writer.response(&message, body)?,
+            Err(err) => {
+                self.writer.error_response(&message, &err)?;
+                return Ok(true);
+            }
+        }
+
+        // events following the response
+        match command.as_str() {
+            "launch" => self.writer.event("initialized", json!({}))?,
+            "configurationDone" if self.stop_on_entry => {
+                if let Some(chip8) = &self.chip8 {
+                    self.debugger.pause(chip8);
+                }
+                self.stopped("entry")?;
+            }
+            "pause" => self.stopped("pause")?,
+            "terminate" => self.writer.event("terminated", json!({}))?,
+            "disconnect" => return Ok(false),
+            _ => {}
+        }
+
+        Ok(true)
+    }
+
+    /// Emulates one 60Hz frame, reporting to the editor why it halted.
+    pub fn run_frame(&mut self) -> std::io::Result<()> {
+        let chip8 = match &mut self.chip8 {
+            Some(chip8) => chip8,
+            None => return Ok(()),
+        };
+
+        match self.debugger.run_frame(chip8) {
+            Ok(None) => Ok(()),
+            Ok(Some(reason)) => self.halted(reason),
+            Err(err) => {
+                self.writer.event(
+                    "output",
+                    json!({ "category": "stderr", "output": format!("{}\n", err) }),
+                )?;
+                self.terminate(1)
+            }
+        }
+    }
+
+    fn halted(&mut self, reason: HaltReason) -> std::io::Result<()> {
+        let reason = match reason {
+            HaltReason::Step => {
+                if let Some(step) = &self.step {
+                    if !self.is_step_complete(step) {
+                        let kind = step.kind;
+                        self.continue_step(kind);
+                        return Ok(());
+                    }
+                }
+                "step"
+            }
+            HaltReason::Breakpoint(_) => "breakpoint",
+            HaltReason::Watchpoint { .. } => "data breakpoint",
+            HaltReason::Pause => "pause",
+            HaltReason::Exited => return self.terminate(0),
+        };
+
+        self.step = None;
+        self.stopped(reason)
+    }
+
+    fn stopped(&mut self, reason: &str) -> std::io::Result<()> {
+        self.writer.event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        )
+    }
+
+    fn terminate(&mut self, exit_code: i32) -> std::io::Result<()> {
+        self.is_terminated = true;
+        self.writer
+            .event("exited", json!({ "exitCode": exit_code }))?;
+        self.writer.event("terminated", json!({}))
+    }
+
+    fn launch(&mut self, arguments: &Value) -> Result<Value, String> {
+        let program = arguments["program"]
+            .as_str()
+            .ok_or("Missing `program` launch argument")?;
+        let rom = fs::read(program).map_err(|err| format!("Cannot read `{}`: {}", program, err))?;
+
+        let mut builder = Chip8::builder().rom(&rom);
+        if let Some(platform) = arguments["platform"].as_str() {
+            builder = builder.platform(
+                platform
+                    .parse::<Platform>()
+                    .map_err(|err| err.to_string())?,
+            );
+        }
+        if let Some(quirks) = arguments["quirks"].as_str() {
+            builder = builder.quirks(quirks.parse::<Quirks>().map_err(|err| err.to_string())?);
+        }
+        if let Some(instructions_per_frame) = arguments["instructionsPerFrame"].as_u64() {
+            builder = builder.instructions_per_frame(instructions_per_frame as usize);
+        }
+
+        if let Some(source_map) = arguments["sourceMap"].as_str() {
+            let text = fs::read_to_string(source_map)
+                .map_err(|err| format!("Cannot read `{}`: {}", source_map, err))?;
+
+            self.source_map = Some(SourceMap::decode(&text).map_err(|err| err.to_string())?);
+            self.source_root = Path::new(source_map)
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default();
+        }
+
+        self.stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or_default();
+        self.chip8 = Some(builder.build().map_err(|err| err.to_string())?);
+
+        Ok(json!({}))
+    }
+
+    fn set_breakpoints(&mut self, arguments: &Value) -> Result<Value, String> {
+        let path = arguments["source"]["path"]
+            .as_str()
+            .ok_or("Missing source path")?;
+        let lines = arguments["breakpoints"]
+            .as_array()
+            .map(|breakpoints| {
+                breakpoints
+                    .iter()
+                    .filter_map(|breakpoint| breakpoint["line"].as_u64())
+                    .collect::<Vec<u64>>()
+            })
+            .unwrap_or_default();
+
+        let file = self.source_file(Path::new(path));
+
+        let mut addresses = vec![];
+        let mut breakpoints = vec![];
+        for line in lines {
+            match file
+                .as_deref()
+                .and_then(|file| self.resolve_line(file, line as usize))
+            {
+                Some((line, address)) => {
+                    addresses.push(address);
+                    breakpoints.push(json!({ "verified": true, "line": line }));
+                }
+                None => breakpoints.push(json!({
+                    "verified": false,
+                    "line": line,
+                    "message": "No instruction at this line",
+                })),
+            }
+        }
+
+        self.source_breakpoints
+            .insert(PathBuf::from(path), addresses);
+        self.update_breakpoints();
+
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    fn set_instruction_breakpoints(&mut self, arguments: &Value) -> Result<Value, String> {
+        let mut breakpoints = vec![];
+        self.instruction_breakpoints.clear();
+
+        for breakpoint in arguments["breakpoints"].as_array().into_iter().flatten() {
+            let address = breakpoint["instructionReference"]
+                .as_str()
+                .and_then(parse_address)
+                .map(|address| {
+                    let offset = breakpoint["offset"].as_i64().unwrap_or_default();
+                    (address as i64 + offset) as u16
+                });
+
+            match address {
+                Some(address) => {
+                    self.instruction_breakpoints.push(address);
+                    breakpoints.push(json!({
+                        "verified": true,
+                        "instructionReference": format_address(address),
+                    }));
+                }
+                None => breakpoints.push(json!({ "verified": false })),
+            }
+        }
+
+        self.update_breakpoints();
+
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    fn update_breakpoints(&mut self) {
+        for address in self.debugger.breakpoints().collect::<Vec<u16>>() {
+            self.debugger.remove_breakpoint(address);
+        }
+
+        let addresses = self
+            .source_breakpoints
+            .values()
+            .flatten()
+            .chain(self.instruction_breakpoints.iter());
+        for address in addresses {
+            self.debugger.add_breakpoint(*address);
+        }
+    }
+
+    fn stack_trace(&self) -> Result<Value, String> {
+        let chip8 = self.chip8.as_ref().ok_or("No program launched")?;
+        let registers = chip8.registers();
+
+        // the current instruction, then the calls from the innermost one,
+        // two bytes before their return address
+        let addresses = std::iter::once(registers.pc()).chain(
+            registers
+                .stack()
+                .iter()
+                .rev()
+                .map(|address| address.wrapping_sub(2)),
+        );
+
+        let frames: Vec<Value> = addresses
+            .enumerate()
+            .map(|(id, address)| {
+                let mut frame = json!({
+                    "id": id,
+                    "name": format_address(address),
+                    "line": 0,
+                    "column": 0,
+                    "instructionPointerReference": format_address(address),
+                });
+
+                if let Some(location) = self.location(address) {
+                    frame["line"] = json!(location.line());
+                    frame["column"] = json!(location.column());
+                    frame["source"] = self.source(location);
+                }
+
+                frame
+            })
+            .collect();
+
+        Ok(json!({ "stackFrames": frames, "totalFrames": frames.len() }))
+    }
+
+    fn variables(&self, arguments: &Value) -> Result<Value, String> {
+        let registers = self
+            .chip8
+            .as_ref()
+            .ok_or("No program launched")?
+            .registers();
+
+        let variables: Vec<(String, String)> = match arguments["variablesReference"].as_u64() {
+            Some(REGISTERS_SCOPE) => (0..16)
+                .map(|x| {
+                    let value = registers.register(x).unwrap_or_default();
+                    (format!("V{:X}", x), format!("{:#04x} ({})", value, value))
+                })
+                .chain(std::iter::once((
+                    "I".to_string(),
+                    format_address(registers.i()),
+                )))
+                .collect(),
+            Some(TIMERS_SCOPE) => vec![
+                ("DT".to_string(), registers.dt().to_string()),
+                ("ST".to_string(), registers.st().to_string()),
+            ],
+            Some(STACK_SCOPE) => registers
+                .stack()
+                .iter()
+                .enumerate()
+                .map(|(index, address)| (format!("[{}]", index), format_address(*address)))
+                .collect(),
+            _ => return Err("Unknown variables reference".to_string()),
+        };
+
+        Ok(json!({
+            "variables": variables
+                .into_iter()
+                .map(|(name, value)| json!({ "name": name, "value": value, "variablesReference": 0 }))
+                .collect::<Vec<Value>>()
+        }))
+    }
+
+    fn start_step(&mut self, kind: StepKind, arguments: &Value) -> Result<Value, String> {
+        let chip8 = self.chip8.as_ref().ok_or("No program launched")?;
+
+        // without a source map, or when asked to, steps go one instruction at a time
+        let is_line_step = self.source_map.is_some() && arguments["granularity"] != "instruction";
+        self.step = if is_line_step {
+            Some(Step {
+                kind,
+                start: self
+                    .location(chip8.registers().pc())
+                    .map(|location| (self.source_path(location), location.line())),
+            })
+        } else {
+            None
+        };
+
+        self.continue_step(kind);
+
+        Ok(json!({}))
+    }
+
+    fn pause(&mut self) -> Result<Value, String> {
+        let chip8 = self.chip8.as_ref().ok_or("No program launched")?;
+        self.step = None;
+        self.debugger.pause(chip8);
+
+        Ok(json!({}))
+    }
+
+    fn continue_step(&mut self, kind: StepKind) {
+        let chip8 = match &self.chip8 {
+            Some(chip8) => chip8,
+            None => return,
+        };
+
+        match kind {
+            StepKind::Into => self.debugger.step_into(),
+            StepKind::Over => self.debugger.step_over(chip8),
+            StepKind::Out => self.debugger.step_out(chip8),
+        }
+    }
+
+    // a line step is over on the first instruction of another source line
+    fn is_step_complete(&self, step: &Step) -> bool {
+        let pc = match &self.chip8 {
+            Some(chip8) => chip8.registers().pc(),
+            None => return true,
+        };
+
+        match self.location(pc) {
+            Some(location) => {
+                step.kind == StepKind::Out
+                    || step.start != Some((self.source_path(location), location.line()))
+            }
+            None => false,
+        }
+    }
+
+    fn location(&self, address: u16) -> Option<&SourceLocation> {
+        self.source_map.as_ref()?.location(address)
+    }
+
+    fn source_path(&self, location: &SourceLocation) -> PathBuf {
+        let path = self.source_root.join(location.file());
+        path.canonicalize().unwrap_or(path)
+    }
+
+    fn source(&self, location: &SourceLocation) -> Value {
+        let path = self.source_path(location);
+        json!({
+            "name": path.file_name().map(|name| name.to_string_lossy()),
+            "path": path.to_string_lossy(),
+        })
+    }
+
+    // file of the source map the editor refers to with `path`
+    fn source_file(&self, path: &Path) -> Option<String> {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+        self.source_map
+            .as_ref()?
+            .files()
+            .into_iter()
+            .find(|file| self.source_path(&SourceLocation::new(file, 0, 0)) == path)
+            .map(str::to_string)
+    }
+
+    // first line from `line` on with instructions, and the address of its first one
+    fn resolve_line(&self, file: &str, line: usize) -> Option<(usize, u16)> {
+        self.source_map
+            .as_ref()?
+            .iter()
+            .filter(|(_, location)| location.file() == file && location.line() >= line)
+            .min_by_key(|(address, location)| (location.line(), *address))
+            .map(|(address, location)| (location.line(), address))
+    }
+}
+
+fn format_address(address: u16) -> String {
+    format!("{:#06x}", address)
+}
+
+fn parse_address(reference: &str) -> Option<u16> {
+    let reference = reference.trim();
+    match reference.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => reference.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::read_message;
+    use std::cell::RefCell;
+    use std::io::Cursor;
+    use std::rc::Rc;
+
+    // 200: LD V0, 1
+    // 202: CALL 208
+    // 204: LD V1, 2
+    // 206: JP 206
+    // 208: LD V2, 3
+    // 20A: RET
+    const ROM: &[u8] = &[
+        0x60, 0x01, 0x22, 0x08, 0x61, 0x02, 0x12, 0x06, 0x62, 0x03, 0x00, 0xEE,
+    ];
+    const SOURCE: &str = "game.src";
+
+    // output shared with the test, to read what the session sent
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(bytes)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct Editor {
+        session: Session<Output>,
+        output: Output,
+        directory: PathBuf,
+        seq: u64,
+        // events sent after the last response
+        events: Vec<Value>,
+    }
+
+    impl Editor {
+        // a rom with one instruction per source line, from line 1 on
+        fn launch(name: &str, arguments: Value) -> Self {
+            let directory =
+                std::env::temp_dir().join(format!("chip8-dap-{}-{}", std::process::id(), name));
+            fs::create_dir_all(&directory).unwrap();
+
+            let mut source_map = SourceMap::new();
+            for line in 1..=ROM.len() / 2 {
+                let address = 0x200 + (line as u16 - 1) * 2;
+                source_map.insert(address, SourceLocation::new(SOURCE, line, 1));
+            }
+            fs::write(directory.join("game.ch8"), ROM).unwrap();
+            fs::write(directory.join("game.map"), source_map.encode()).unwrap();
+            fs::write(directory.join(SOURCE), "").unwrap();
+
+            let output = Output::default();
+            let mut editor = Self {
+                session: Session::new(Writer::new(output.clone())),
+                output,
+                directory,
+                seq: 0,
+                events: vec![],
+            };
+
+            editor.request("initialize", json!({}));
+            let mut launch = json!({
+                "program": editor.directory.join("game.ch8"),
+                "sourceMap": editor.directory.join("game.map"),
+            });
+            launch
+                .as_object_mut()
+                .unwrap()
+                .extend(arguments.as_object().unwrap().clone());
+            assert_eq!(editor.request("launch", launch)["success"], true);
+            assert_eq!(editor.events(), ["initialized"]);
+
+            editor
+        }
+
+        fn request(&mut self, command: &str, arguments: Value) -> Value {
+            self.seq += 1;
+            let request = json!({
+                "seq": self.seq,
+                "type": "request",
+                "command": command,
+                "arguments": arguments,
+            });
+            assert!(self.session.handle(request).unwrap());
+
+            let mut messages = self.messages();
+            let response = messages.remove(0);
+            self.events = messages;
+            assert_eq!(response["type"], "response");
+            assert_eq!(response["request_seq"], self.seq);
+            response
+        }
+
+        fn set_breakpoints(&mut self, lines: &[u64]) -> Value {
+            let breakpoints = lines
+                .iter()
+                .map(|line| json!({ "line": line }))
+                .collect::<Vec<Value>>();
+            let arguments = json!({
+                "source": { "path": self.directory.join(SOURCE) },
+                "breakpoints": breakpoints,
+            });
+
+            self.request("setBreakpoints", arguments)["body"]["breakpoints"].clone()
+        }
+
+        // runs frames until the session stops, returns the reason
+        fn run(&mut self) -> Option<String> {
+            for _ in 0..10 {
+                if !self.session.is_running() {
+                    break;
+                }
+                self.session.run_frame().unwrap();
+            }
+
+            self.stopped()
+        }
+
+        fn stopped(&mut self) -> Option<String> {
+            let events = self.messages();
+            let stopped = events.iter().find(|event| event["event"] == "stopped")?;
+            Some(stopped["body"]["reason"].as_str().unwrap().to_string())
+        }
+
+        fn pc(&self) -> u16 {
+            self.session.chip8.as_ref().unwrap().registers().pc()
+        }
+
+        fn events(&mut self) -> Vec<String> {
+            self.messages()
+                .iter()
+                .map(|event| event["event"].as_str().unwrap().to_string())
+                .collect()
+        }
+
+        // messages not looked at yet
+        fn messages(&mut self) -> Vec<Value> {
+            let bytes = std::mem::take(&mut *self.output.0.borrow_mut());
+            let mut reader = Cursor::new(bytes);
+            let messages = std::iter::from_fn(|| read_message(&mut reader).unwrap());
+
+            std::mem::take(&mut self.events)
+                .into_iter()
+                .chain(messages)
+                .collect()
+        }
+    }
+
+    impl Drop for Editor {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.directory).ok();
+        }
+    }
+
+    #[test]
+    fn breakpoints_are_set_on_source_lines() {
+        let mut editor = Editor::launch("set_breakpoints", json!({}));
+
+        let breakpoints = editor.set_breakpoints(&[3, 9]);
+        assert_eq!(breakpoints[0]["verified"], true);
+        assert_eq!(breakpoints[0]["line"], 3);
+        assert_eq!(breakpoints[1]["verified"], false);
+        assert_eq!(
+            editor.session.debugger.breakpoints().collect::<Vec<_>>(),
+            [0x204]
+        );
+
+        // replaced as a whole
+        editor.set_breakpoints(&[5]);
+        assert_eq!(
+            editor.session.debugger.breakpoints().collect::<Vec<_>>(),
+            [0x208]
+        );
+    }
+
+    #[test]
+    fn breakpoint_stops_then_continue_runs_on() {
+        let mut editor = Editor::launch("breakpoint", json!({}));
+        editor.set_breakpoints(&[5]);
+        editor.request("configurationDone", json!({}));
+
+        assert_eq!(editor.run().as_deref(), Some("breakpoint"));
+        assert_eq!(editor.pc(), 0x208);
+
+        let response = editor.request("continue", json!({ "threadId": THREAD_ID }));
+        assert_eq!(response["success"], true);
+        assert_eq!(editor.run(), None);
+        assert_eq!(editor.pc(), 0x206);
+    }
+
+    #[test]
+    fn next_steps_over_calls_then_continue_leaves_the_breakpoint() {
+        let mut editor = Editor::launch("next", json!({ "stopOnEntry": true }));
+        editor.set_breakpoints(&[3]);
+        editor.request("configurationDone", json!({}));
+        assert_eq!(editor.stopped().as_deref(), Some("entry"));
+
+        editor.request("next", json!({ "threadId": THREAD_ID }));
+        assert_eq!(editor.run().as_deref(), Some("step"));
+        assert_eq!(editor.pc(), 0x202);
+
+        // the call runs whole, the step ends on the breakpoint of line 3
+        editor.request("next", json!({ "threadId": THREAD_ID }));
+        assert_eq!(editor.run().as_deref(), Some("step"));
+        assert_eq!(editor.pc(), 0x204);
+
+        editor.request("continue", json!({ "threadId": THREAD_ID }));
+        assert_eq!(editor.run(), None);
+        assert_eq!(editor.pc(), 0x206);
+    }
+
+    #[test]
+    fn variables_show_the_registers() {
+        let mut editor = Editor::launch("variables", json!({}));
+        editor.set_breakpoints(&[6]);
+        editor.request("configurationDone", json!({}));
+        assert_eq!(editor.run().as_deref(), Some("breakpoint"));
+
+        let arguments = json!({ "variablesReference": REGISTERS_SCOPE });
+        let variables = editor.request("variables", arguments)["body"]["variables"].clone();
+        let value = |name: &str| {
+            variables
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|variable| variable["name"] == name)
+                .map(|variable| variable["value"].as_str().unwrap().to_string())
+        };
+        assert_eq!(value("V0").as_deref(), Some("0x01 (1)"));
+        assert_eq!(value("V1").as_deref(), Some("0x00 (0)"));
+        assert_eq!(value("V2").as_deref(), Some("0x03 (3)"));
+        assert_eq!(value("I").as_deref(), Some("0x0000"));
+
+        let arguments = json!({ "variablesReference": STACK_SCOPE });
+        let stack = editor.request("variables", arguments)["body"]["variables"].clone();
+        assert_eq!(
+            stack,
+            json!([{ "name": "[0]", "value": "0x0204", "variablesReference": 0 }])
+        );
+
+        let arguments = json!({ "variablesReference": 42 });
+        assert_eq!(editor.request("variables", arguments)["success"], false);
+    }
+}
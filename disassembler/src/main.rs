@@ -0,0 +1,44 @@
+use chip8_core::decompiler::Decompiler;
+use chip8_core::disassembler::Disassembler;
+use chip8_core::emulator::platform::Platform;
+use std::fs;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct Opt {
+    /// Rom to disassemble
+    file: String,
+    /// Platform whose instructions are recognized: chip8, schip or xochip
+    #[structopt(short, long, default_value = "chip8")]
+    platform: Platform,
+    /// Comments every line with its address and bytes
+    #[structopt(short, long)]
+    addresses: bool,
+    /// Decompiles to Octo source, with its loops and conditional blocks
+    #[structopt(long, conflicts_with = "addresses")]
+    octo: bool,
+    /// Writes the listing to this file instead of the standard output
+    #[structopt(short, long)]
+    output: Option<String>,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let opt: Opt = Opt::from_args();
+
+    let rom = fs::read(&opt.file)?;
+    let listing = if opt.octo {
+        Decompiler::new(opt.platform).decompile(&rom).to_string()
+    } else {
+        Disassembler::new(opt.platform)
+            .show_addresses(opt.addresses)
+            .disassemble(&rom)
+            .to_string()
+    };
+
+    match &opt.output {
+        Some(output) => fs::write(output, listing)?,
+        None => print!("{}", listing),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use chip8_core::emulator::checksum::crc32;
+use chip8_core::emulator::chip8::Chip8;
+use chip8_core::emulator::display::Display;
+use serde_json::{json, Value};
+
+// characters of the pixels lit on no plane, on the first, on the second and on both
+const ASCII_PALETTE: [char; 4] = ['.', '#', '+', '@'];
+// gray levels of the same pixels, as drawn by the native frontend
+const GRAY_PALETTE: [u8; 4] = [0x00, 0xff, 0xaa, 0x55];
+// largest block of a deflate stream stored without compression
+const STORED_BLOCK_SIZE: usize = 0xffff;
+
+/// A line of characters for every row of the display.
+pub fn ascii(display: &Display) -> String {
+    let mut text = String::new();
+    let pixels: Vec<u8> = display.pixels().collect();
+
+    for row in pixels.chunks(display.width()) {
+        text.extend(row.iter().map(|color| ASCII_PALETTE[*color as usize]));
+        text.push('\n');
+    }
+
+    text
+}
+
+/// Grayscale PNG image of the display, a pixel for every pixel.
+pub fn png(display: &Display) -> Vec<u8> {
+    let (width, height) = (display.width(), display.height());
+
+    // every row starts with its filter type, none
+    let mut image = Vec::with_capacity((width + 1) * height);
+    let pixels: Vec<u8> = display.pixels().collect();
+    for row in pixels.chunks(width) {
+        image.push(0);
+        image.extend(row.iter().map(|color| GRAY_PALETTE[*color as usize]));
+    }
+
+    let mut header = vec![];
+    header.extend(&(width as u32).to_be_bytes());
+    header.extend(&(height as u32).to_be_bytes());
+    // 8 bit grayscale, deflate, no filtering, not interlaced
+    header.extend(&[8, 0, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib(&image));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+/// Registers of the machine, with the frames run and why the run stopped.
+pub fn registers(chip8: &Chip8, frames: u64, halt: &str) -> Value {
+    let registers = chip8.registers();
+    let v: Vec<u8> = (0..16)
+        .map(|x| registers.register(x).unwrap_or_default())
+        .collect();
+
+    json!({
+        "frames": frames,
+        "halt": halt,
+        "pc": registers.pc(),
+        "i": registers.i(),
+        "v": v,
+        "dt": registers.dt(),
+        "st": registers.st(),
+        "stack": registers.stack(),
+    })
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend(&(data.len() as u32).to_be_bytes());
+
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start..]);
+    png.extend(&crc.to_be_bytes());
+}
+
+// zlib stream of stored deflate blocks, the images are too small to bother
+// compressing them
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+
+    let blocks: Vec<&[u8]> = data.chunks(STORED_BLOCK_SIZE).collect();
+    for (index, block) in blocks.iter().enumerate() {
+        let is_final = index == blocks.len() - 1;
+        stream.push(is_final as u8);
+        stream.extend(&(block.len() as u16).to_le_bytes());
+        stream.extend(&(!(block.len() as u16)).to_le_bytes());
+        stream.extend(*block);
+    }
+
+    stream.extend(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    b << 16 | a
+}
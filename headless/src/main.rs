@@ -0,0 +1,228 @@
+use crate::script::KeyScript;
+use chip8_core::emulator::chip8::Chip8;
+use chip8_core::emulator::cpu::tracer::Tracer;
+use chip8_core::emulator::debugger::{Debugger, HaltReason};
+use chip8_core::emulator::platform::Platform;
+use chip8_core::emulator::quirks::Quirks;
+use chip8_core::emulator::EmulatorError;
+use std::fs;
+use std::fs::File;
+use std::io::BufWriter;
+use std::num::ParseIntError;
+use std::process;
+use structopt::StructOpt;
+
+mod dump;
+mod script;
+
+// exit codes of a run stopped by an emulator error and of a run that could
+// not start, or whose results could not be written
+const EXIT_EMULATOR_ERROR: i32 = 1;
+const EXIT_FAILURE: i32 = 2;
+
+/// Runs a rom without window or audio device and dumps the machine at the end.
+#[derive(Debug, StructOpt)]
+pub struct Opt {
+    /// Rom to run, or Octo source when its extension is .8o
+    file: String,
+    /// Platform the rom was written for: chip8, schip or xochip, every
+    /// instruction with Cowgod's interpretation when not set
+    #[structopt(short, long)]
+    platform: Option<Platform>,
+    /// Quirks preset overriding the platform ones: cowgod, vip, chip48, schip or xochip
+    #[structopt(short, long)]
+    quirks: Option<Quirks>,
+    /// Instructions executed for every 60Hz frame
+    #[structopt(short, long, default_value = "10")]
+    instructions_per_frame: usize,
+    /// Runs every instruction decoded once from a cache, faster on long runs
+    #[structopt(long)]
+    decode_cache: bool,
+    /// Frames run before stopping, unless the rom halts first
+    #[structopt(long, default_value = "600")]
+    frames: u64,
+    /// Key presses to apply, as `<frame> <keys>` lines
+    #[structopt(long)]
+    keys: Option<String>,
+    /// Stops before executing the instruction at this hexadecimal address
+    #[structopt(long = "break", parse(try_from_str = parse_address), number_of_values = 1)]
+    breakpoints: Vec<u16>,
+    /// Writes the display as ASCII art to this file, `-` for the standard output
+    #[structopt(long)]
+    ascii: Option<String>,
+    /// Writes the display as a PNG image to this file
+    #[structopt(long)]
+    image: Option<String>,
+    /// Writes the registers as JSON to this file, `-` for the standard output
+    #[structopt(long)]
+    registers: Option<String>,
+    /// Writes every instruction executed, with the registers, to this file
+    #[structopt(long)]
+    trace: Option<String>,
+}
+
+fn main() {
+    let opt: Opt = Opt::from_args();
+
+    match run(&opt) {
+        Ok(None) => {}
+        Ok(Some(error)) => {
+            eprintln!("{}", error);
+            process::exit(EXIT_EMULATOR_ERROR);
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(EXIT_FAILURE);
+        }
+    }
+}
+
+// Runs the rom and writes the dumps, returning the emulator error that
+// stopped the run if any. The dumps are written in both cases.
+fn run(opt: &Opt) -> Result<Option<EmulatorError>, Box<dyn std::error::Error>> {
+    let mut rom = fs::read(&opt.file)?;
+
+    // Octo sources are compiled before running
+    if opt.file.ends_with(".8o") {
+        let source = String::from_utf8(rom)?;
+        rom = octo::compile(&source).map_err(|error| format!("{}:{}", opt.file, error))?;
+    }
+
+    let script = match &opt.keys {
+        Some(keys_file) => KeyScript::parse(&fs::read_to_string(keys_file)?)
+            .map_err(|error| format!("{}: {}", keys_file, error))?,
+        None => KeyScript::default(),
+    };
+
+    let mut builder = Chip8::builder()
+        .rom(&rom)
+        .instructions_per_frame(opt.instructions_per_frame)
+        .decode_cache(opt.decode_cache);
+    if let Some(platform) = opt.platform {
+        builder = builder.platform(platform);
+    }
+    let mut chip8 = match opt.quirks {
+        Some(quirks) => builder.quirks(quirks),
+        None => builder,
+    }
+    .build()?;
+
+    if let Some(trace_file) = &opt.trace {
+        let output = BufWriter::new(File::create(trace_file)?);
+        chip8.set_tracer(Some(Tracer::new(Box::new(output))));
+    }
+
+    let mut debugger = Debugger::new();
+    for addr in &opt.breakpoints {
+        debugger.add_breakpoint(*addr);
+    }
+
+    let mut frames = 0;
+    let mut error = None;
+    let halt = loop {
+        if frames >= opt.frames {
+            break "frames";
+        }
+        if is_stuck(&chip8) {
+            break "loop";
+        }
+
+        chip8.set_keyboard_state(script.keys_at(frames));
+        match debugger.run_frame(&mut chip8) {
+            Ok(None) => frames += 1,
+            Ok(Some(HaltReason::Exited)) => break "exit",
+            Ok(Some(_)) => break "breakpoint",
+            Err(emulator_error) => {
+                error = Some(emulator_error);
+                break "error";
+            }
+        }
+    };
+
+    if let Some(tracer) = chip8.tracer_mut() {
+        tracer.flush()?;
+    }
+
+    // the display goes to the standard output when no dump is asked for
+    let ascii_file = match (&opt.ascii, &opt.image, &opt.registers) {
+        (None, None, None) => Some("-"),
+        (ascii_file, _, _) => ascii_file.as_deref(),
+    };
+    if let Some(ascii_file) = ascii_file {
+        write_output(ascii_file, dump::ascii(chip8.display()).as_bytes())?;
+    }
+    if let Some(image_file) = &opt.image {
+        fs::write(image_file, dump::png(chip8.display()))?;
+    }
+    if let Some(registers_file) = &opt.registers {
+        let registers = dump::registers(&chip8, frames, halt);
+        write_output(registers_file, format!("{:#}\n", registers).as_bytes())?;
+    }
+
+    Ok(error)
+}
+
+// The rom jumps to the jump it is executing, the usual way to end a test rom.
+fn is_stuck(chip8: &Chip8) -> bool {
+    let pc = chip8.registers().pc();
+    pc < 0x1000 && chip8.memory().read_16(pc) == 0x1000 | pc
+}
+
+fn write_output(file: &str, contents: &[u8]) -> std::io::Result<()> {
+    match file {
+        "-" => {
+            print!("{}", String::from_utf8_lossy(contents));
+            Ok(())
+        }
+        _ => fs::write(file, contents),
+    }
+}
+
+fn parse_address(text: &str) -> Result<u16, ParseIntError> {
+    u16::from_str_radix(text.trim_start_matches("0x"), 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(rom: &[u8]) -> Chip8 {
+        Chip8::builder()
+            .rom(rom)
+            .platform(Platform::XoChip)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn jump_to_itself_is_stuck() {
+        // 200: JP 202
+        // 202: JP 202
+        let mut chip8 = build(&[0x12, 0x02, 0x12, 0x02]);
+        assert!(!is_stuck(&chip8));
+
+        chip8.step().unwrap();
+        assert!(is_stuck(&chip8));
+        chip8.step().unwrap();
+        assert!(is_stuck(&chip8));
+    }
+
+    #[test]
+    fn other_instructions_are_not_stuck() {
+        // 200: CALL 200, jumps to itself through the stack
+        // 202: JP 202 in the low byte only
+        let chip8 = build(&[0x22, 0x00, 0x02, 0x12]);
+        assert!(!is_stuck(&chip8));
+    }
+
+    #[test]
+    fn addresses_out_of_reach_of_jumps_are_not_stuck() {
+        // 1000 jumps to 000, not to itself
+        let mut chip8 = build(&[]);
+        chip8.memory_mut().write_8(0x1000, 0x10);
+        chip8.memory_mut().write_8(0x1001, 0x00);
+        chip8.registers_mut().set_pc(0x1000);
+
+        assert!(!is_stuck(&chip8));
+    }
+}
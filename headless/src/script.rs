@@ -0,0 +1,161 @@
+use chip8_core::emulator::keyboard::KeyboardState;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ScriptError {
+    #[error("Line {0}: expected `<frame> <keys>`")]
+    MissingKeys(usize),
+    #[error("Line {0}: invalid frame `{1}`")]
+    InvalidFrame(usize, String),
+    #[error("Line {0}: invalid key `{1}`, keys go from 0 to F")]
+    InvalidKey(usize, String),
+}
+
+/// Keys held down during the run, from the frame of every line of the
+/// script to the frame of the next one:
+///
+/// ```text
+/// # frame keys
+/// 60 5
+/// 64 4 6
+/// 90 -
+/// ```
+///
+/// Keys are hexadecimal digits, `-` releases them all and `#` starts a comment.
+#[derive(Debug, Default)]
+pub struct KeyScript {
+    // key bitmask by the frame it starts at
+    masks: BTreeMap<u64, u16>,
+}
+
+impl KeyScript {
+    pub fn parse(text: &str) -> Result<Self, ScriptError> {
+        let mut masks = BTreeMap::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let number = index + 1;
+            let line = line.split('#').next().unwrap_or_default();
+            let mut words = line.split_whitespace();
+
+            let frame = match words.next() {
+                Some(frame) => frame
+                    .parse()
+                    .map_err(|_| ScriptError::InvalidFrame(number, frame.to_string()))?,
+                None => continue,
+            };
+
+            let mut mask = 0;
+            let mut has_keys = false;
+            for word in words {
+                has_keys = true;
+                if word == "-" {
+                    continue;
+                }
+
+                match u8::from_str_radix(word, 16) {
+                    Ok(key) if word.len() == 1 => mask |= 1 << key,
+                    _ => return Err(ScriptError::InvalidKey(number, word.to_string())),
+                }
+            }
+
+            if !has_keys {
+                return Err(ScriptError::MissingKeys(number));
+            }
+            masks.insert(frame, mask);
+        }
+
+        Ok(Self { masks })
+    }
+
+    /// Keys held down during `frame`.
+    pub fn keys_at(&self, frame: u64) -> KeyboardState {
+        let mask = self
+            .masks
+            .range(..=frame)
+            .next_back()
+            .map_or(0, |(_, mask)| *mask);
+
+        KeyboardState::from_mask(mask)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mask(script: &KeyScript, frame: u64) -> u16 {
+        script.keys_at(frame).mask()
+    }
+
+    #[test]
+    fn keys_are_held_until_the_next_line() {
+        let script = KeyScript::parse(
+            "\
+# frame keys
+60 5      # start
+64 4 6
+  # indented comment
+
+90 -
+120 a F
+",
+        )
+        .unwrap();
+
+        assert_eq!(mask(&script, 0), 0);
+        assert_eq!(mask(&script, 59), 0);
+        assert_eq!(mask(&script, 60), 1 << 5);
+        assert_eq!(mask(&script, 63), 1 << 5);
+        assert_eq!(mask(&script, 64), 1 << 4 | 1 << 6);
+        assert_eq!(mask(&script, 89), 1 << 4 | 1 << 6);
+        assert_eq!(mask(&script, 90), 0);
+        assert_eq!(mask(&script, 120), 1 << 0xa | 1 << 0xf);
+        assert_eq!(mask(&script, u64::MAX), 1 << 0xa | 1 << 0xf);
+    }
+
+    #[test]
+    fn lines_out_of_order_are_sorted_by_frame() {
+        let script = KeyScript::parse("10 2\n5 1\n10 3").unwrap();
+
+        assert_eq!(mask(&script, 4), 0);
+        assert_eq!(mask(&script, 5), 1 << 1);
+        // the last line for a frame wins
+        assert_eq!(mask(&script, 10), 1 << 3);
+    }
+
+    #[test]
+    fn empty_script_presses_nothing() {
+        let script = KeyScript::parse("# nothing\n\n").unwrap();
+        assert_eq!(mask(&script, 0), 0);
+        assert_eq!(mask(&script, 1000), 0);
+    }
+
+    #[test]
+    fn invalid_lines_are_reported_with_their_number() {
+        let error = |text| KeyScript::parse(text).unwrap_err();
+
+        assert!(matches!(error("1 1\n2"), ScriptError::MissingKeys(2)));
+        assert!(matches!(error("2 # comment"), ScriptError::MissingKeys(1)));
+        assert!(matches!(
+            error("0 1\n-1 2"),
+            ScriptError::InvalidFrame(2, ref frame) if frame == "-1"
+        ));
+        assert!(matches!(
+            error("x 1"),
+            ScriptError::InvalidFrame(1, ref frame) if frame == "x"
+        ));
+        assert!(matches!(
+            error("\n\n3 G"),
+            ScriptError::InvalidKey(3, ref key) if key == "G"
+        ));
+        assert!(matches!(
+            error("3 10"),
+            ScriptError::InvalidKey(1, ref key) if key == "10"
+        ));
+        assert_eq!(
+            error("3 1 +").to_string(),
+            "Line 1: invalid key `+`, keys go from 0 to F"
+        );
+    }
+}
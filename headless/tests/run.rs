@@ -0,0 +1,102 @@
+//! Runs the `chip8-headless` binary on the roms bundled in the root of the
+//! project and on a rom which faults, checking its dumps and exit code.
+
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Output};
+
+fn headless(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_chip8-headless"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn bundled(rom: &str) -> String {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    root.join("..").join(rom).to_string_lossy().into_owned()
+}
+
+// file in the temporary directory, distinct for every test process
+fn temporary(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("chip8-headless-{}-{}", process::id(), name))
+}
+
+#[test]
+fn ascii_dump_goes_to_the_standard_output() {
+    let output = headless(&[&bundled("test_opcode.ch8")]);
+    assert!(output.status.success());
+
+    let display = String::from_utf8(output.stdout).unwrap();
+    let rows: Vec<&str> = display.lines().collect();
+    assert_eq!(rows.len(), 32);
+    assert!(rows.iter().all(|row| row.len() == 64));
+    assert_eq!(
+        rows[..3],
+        [
+            "................................................................",
+            ".###.#.#..###.#.#......###.###..###.#.#.....###..##.###.#.#.....",
+            "..##..#...#.#.##.......#.#.##...#.#.##......###..#..#.#.##......",
+        ]
+    );
+}
+
+#[test]
+fn image_and_registers_are_written_to_files() {
+    let image_file = temporary("test_opcode.png");
+    let registers_file = temporary("test_opcode.json");
+    let output = headless(&[
+        &bundled("test_opcode.ch8"),
+        "--image",
+        image_file.to_str().unwrap(),
+        "--registers",
+        registers_file.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    // the display only goes to the standard output when nothing else is dumped
+    assert!(output.stdout.is_empty());
+
+    let png = fs::read(&image_file).unwrap();
+    fs::remove_file(&image_file).unwrap();
+    assert_eq!(png[..8], b"\x89PNG\r\n\x1a\n"[..]);
+    assert_eq!(png[12..16], b"IHDR"[..]);
+    // 64x32, 8 bit grayscale
+    assert_eq!(png[16..25], [0, 0, 0, 64, 0, 0, 0, 32, 8]);
+    assert_eq!(png[png.len() - 8..png.len() - 4], b"IEND"[..]);
+
+    let registers = fs::read_to_string(&registers_file).unwrap();
+    fs::remove_file(&registers_file).unwrap();
+    let registers: Value = serde_json::from_str(&registers).unwrap();
+    assert_eq!(registers["halt"], "loop");
+    assert_eq!(registers["pc"], 0x3dc);
+    assert_eq!(registers["v"].as_array().unwrap().len(), 16);
+    assert_eq!(registers["stack"], Value::Array(vec![]));
+}
+
+#[test]
+fn cpu_errors_stop_the_run_with_a_non_zero_exit_code() {
+    // 200: 5121, no such instruction
+    let rom_file = temporary("fault.ch8");
+    fs::write(&rom_file, [0x51, 0x21]).unwrap();
+    let output = headless(&[rom_file.to_str().unwrap(), "--registers", "-"]);
+    fs::remove_file(&rom_file).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    let error = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(error.trim_end(), "Unhandled instruction: 5121");
+
+    // the machine is still dumped, stopped past the faulting instruction
+    let registers: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(registers["halt"], "error");
+    assert_eq!(registers["frames"], 0);
+    assert_eq!(registers["pc"], 0x202);
+}
+
+#[test]
+fn missing_roms_fail_with_another_exit_code() {
+    let output = headless(&[temporary("missing.ch8").to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+}
@@ -1,12 +1,14 @@
 use crate::emulator;
+use crate::emulator::audio::{AudioPattern, Waveform};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::{BuildStreamError, Device, PlayStreamError, Sample, SampleFormat, Stream, StreamConfig};
+use std::sync::{Arc, Mutex};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum AudioError {
     #[error("No available audio output device")]
-    NoOutputDeviceError,
+    NoOutputDevice,
     #[error(transparent)]
     PlayStreamError(#[from] PlayStreamError),
     #[error(transparent)]
@@ -16,32 +18,43 @@ pub enum AudioError {
 pub struct Audio {
     device: Device,
     stream: Option<Stream>,
+    // shared with the stream callback, which plays it
+    waveform: Arc<Mutex<Waveform>>,
 }
 
 impl Audio {
     pub fn new() -> Result<Audio, AudioError> {
         let device = cpal::default_host()
             .default_output_device()
-            .ok_or(AudioError::NoOutputDeviceError)?;
+            .ok_or(AudioError::NoOutputDevice)?;
 
         Ok(Self {
             device,
             stream: None,
+            waveform: Arc::new(Mutex::new(Waveform::default())),
         })
     }
 }
 
 impl emulator::audio::Audio for Audio {
-    fn beep(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    fn beep(&mut self, pattern: &AudioPattern) -> Result<(), Box<dyn std::error::Error>> {
+        {
+            let mut waveform = self.waveform.lock().unwrap();
+            if waveform.pattern() != pattern {
+                waveform.set_pattern(*pattern);
+            }
+        }
+
         if self.stream.is_some() {
             return Ok(());
         }
 
         let config = self.device.default_output_config()?;
+        let waveform = self.waveform.clone();
         let stream = match config.sample_format() {
-            SampleFormat::F32 => beep_stream::<f32>(&self.device, &config.into()),
-            SampleFormat::I16 => beep_stream::<i16>(&self.device, &config.into()),
-            SampleFormat::U16 => beep_stream::<u16>(&self.device, &config.into()),
+            SampleFormat::F32 => beep_stream::<f32>(&self.device, &config.into(), waveform),
+            SampleFormat::I16 => beep_stream::<i16>(&self.device, &config.into(), waveform),
+            SampleFormat::U16 => beep_stream::<u16>(&self.device, &config.into(), waveform),
         }?;
 
         stream.play()?;
@@ -57,25 +70,24 @@ impl emulator::audio::Audio for Audio {
     }
 }
 
-fn beep_stream<T>(device: &Device, config: &StreamConfig) -> Result<Stream, AudioError>
+fn beep_stream<T>(
+    device: &Device,
+    config: &StreamConfig,
+    waveform: Arc<Mutex<Waveform>>,
+) -> Result<Stream, AudioError>
 where
     T: Sample,
 {
     let sample_rate = config.sample_rate.0 as f32;
     let channels = config.channels as usize;
 
-    let mut sample_clock = 0f32;
-    let mut next_value = move || {
-        sample_clock = (sample_clock + 1.0) % sample_rate;
-        (sample_clock * 440.0 * 2.0 * std::f32::consts::PI / sample_rate).sin()
-    };
-
     let err_fn = |err| eprintln!("an error occurred on stream: {}", err);
 
     let stream = device.build_output_stream(
         config,
         move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
-            write_data(data, channels, &mut next_value)
+            let mut waveform = waveform.lock().unwrap();
+            write_data(data, channels, &mut || waveform.next_sample(sample_rate))
         },
         err_fn,
     )?;
@@ -0,0 +1,694 @@
+use chip8_core::emulator::chip8::Chip8;
+use chip8_core::emulator::debugger::{AccessKind, Debugger, HaltReason, Location, Watchpoint};
+use chip8_core::emulator::memory;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+
+// Registers as numbered by the target description: V0-VF, I, PC, DT, ST,
+// the stack depth SP and the 16 stack entries. Values are sent big endian,
+// like the CHIP-8 stores them in memory.
+const I: usize = 16;
+const PC: usize = 17;
+const DT: usize = 18;
+const ST: usize = 19;
+const SP: usize = 20;
+const STACK: usize = 21;
+const STACK_SIZE: usize = 16;
+const REGISTERS: usize = STACK + STACK_SIZE;
+
+const PACKET_SIZE: usize = 0x4000;
+const INTERRUPT: u8 = 0x03;
+
+/// GDB remote serial protocol server, driving the emulation through the core
+/// debugger. The emulation runs freely until a client attaches and pauses it.
+pub struct GdbStub {
+    listener: TcpListener,
+    stream: Option<TcpStream>,
+    // bytes received and not handled yet
+    buffer: Vec<u8>,
+    debugger: Debugger,
+}
+
+impl GdbStub {
+    /// Listens on `port` of the loopback interface.
+    pub fn new(port: u16) -> std::io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self {
+            listener,
+            stream: None,
+            buffer: vec![],
+            debugger: Debugger::new(),
+        })
+    }
+
+    /// Handles the pending requests of the client, then emulates the rest of
+    /// the frame unless the client halted the execution.
+    pub fn run_frame(&mut self, chip8: &mut Chip8) -> Result<(), Box<dyn std::error::Error>> {
+        self.accept(chip8)?;
+
+        if let Err(err) = self.receive(chip8) {
+            eprintln!("GDB client disconnected: {}", err);
+            self.detach();
+        }
+
+        if let Some(reason) = self.debugger.run_frame(chip8)? {
+            self.report(reason);
+        }
+
+        Ok(())
+    }
+
+    fn accept(&mut self, chip8: &Chip8) -> std::io::Result<()> {
+        if self.stream.is_some() {
+            return Ok(());
+        }
+
+        match self.listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(true)?;
+                stream.set_nodelay(true)?;
+                self.stream = Some(stream);
+                self.buffer.clear();
+                self.debugger.pause(chip8);
+                Ok(())
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    // leaves the program running without the breakpoints of the client
+    fn detach(&mut self) {
+        self.stream = None;
+        for addr in self.debugger.breakpoints().collect::<Vec<u16>>() {
+            self.debugger.remove_breakpoint(addr);
+        }
+        for watchpoint in self.debugger.watchpoints().to_vec() {
+            self.debugger.remove_watchpoint(&watchpoint);
+        }
+        self.debugger.resume();
+    }
+
+    fn receive(&mut self, chip8: &mut Chip8) -> std::io::Result<()> {
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => return Ok(()),
+        };
+
+        let mut bytes = [0; 1024];
+        loop {
+            match stream.read(&mut bytes) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(count) => self.buffer.extend_from_slice(&bytes[..count]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        while let Some(incoming) = next_incoming(&mut self.buffer) {
+            match incoming {
+                Incoming::Packet(packet) => {
+                    self.send_raw(b"+")?;
+                    if let Some(reply) = self.handle_packet(&packet, chip8) {
+                        self.send(&reply)?;
+                    }
+
+                    if self.stream.is_none() {
+                        return Ok(());
+                    }
+                }
+                Incoming::Corrupted => self.send_raw(b"-")?,
+                Incoming::Interrupt => {
+                    if !self.debugger.is_paused() {
+                        self.debugger.pause(chip8);
+                        self.report(HaltReason::Pause);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Returns the reply, `None` when it is sent once the execution halts.
+    fn handle_packet(&mut self, packet: &str, chip8: &mut Chip8) -> Option<String> {
+        // any character, the packet may not be ASCII
+        let (command, arguments) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+
+        let reply = match command {
+            "?" => self.stop_reply(self.debugger.halt_reason().unwrap_or(HaltReason::Pause)),
+            "g" => (0..REGISTERS)
+                .map(|register| hex(&read_register(chip8, register)))
+                .collect(),
+            "G" => ok_or_error(write_registers(chip8, arguments)),
+            "p" => usize::from_str_radix(arguments, 16)
+                .ok()
+                .filter(|register| *register < REGISTERS)
+                .map(|register| hex(&read_register(chip8, register)))
+                .unwrap_or_else(|| "E01".to_string()),
+            "P" => ok_or_error(arguments.split_once('=').and_then(|(register, value)| {
+                write_register(
+                    chip8,
+                    usize::from_str_radix(register, 16).ok()?,
+                    &unhex(value)?,
+                )
+            })),
+            "m" => read_memory(chip8, arguments)
+                .map(|bytes| hex(&bytes))
+                .unwrap_or_else(|| "E01".to_string()),
+            "M" => ok_or_error(write_memory(chip8, arguments)),
+            "Z" | "z" => ok_or_error(self.set_point(arguments, command == "Z")),
+            "c" | "s" => {
+                if let Ok(addr) = u16::from_str_radix(arguments, 16) {
+                    chip8.registers_mut().set_pc(addr);
+                }
+
+                self.start(command == "s", chip8);
+                return None;
+            }
+            "v" if arguments == "Cont?" => "vCont;c;C;s;S".to_string(),
+            "v" if arguments.starts_with("Cont;") => {
+                // a single thread, only the first action matters
+                let step = matches!(arguments.as_bytes().get(5), Some(b's') | Some(b'S'));
+
+                self.start(step, chip8);
+                return None;
+            }
+            "D" => {
+                // replied before the connection is dropped
+                self.send("OK").ok();
+                self.detach();
+                return None;
+            }
+            "k" => {
+                self.detach();
+                return None;
+            }
+            "H" | "T" => "OK".to_string(),
+            "q" => self.query(arguments),
+            _ => String::new(),
+        };
+
+        Some(reply)
+    }
+
+    fn query(&self, query: &str) -> String {
+        if query.starts_with("Supported") {
+            return format!(
+                "PacketSize={:x};qXfer:features:read+;swbreak+;hwbreak+",
+                PACKET_SIZE
+            );
+        }
+
+        if let Some(range) = query.strip_prefix("Xfer:features:read:target.xml:") {
+            return xfer(&target_xml(), range).unwrap_or_else(|| "E01".to_string());
+        }
+
+        match query {
+            "Attached" => "1",
+            "C" => "QC1",
+            "fThreadInfo" => "m1",
+            "sThreadInfo" => "l",
+            _ => "",
+        }
+        .to_string()
+    }
+
+    fn start(&mut self, step: bool, chip8: &Chip8) {
+        if step {
+            self.debugger.step_into();
+        } else {
+            self.debugger.resume();
+        }
+
+        // the client waits for the stop reply of a program already exited
+        if chip8.is_exited() {
+            self.debugger.pause(chip8);
+            self.report(HaltReason::Exited);
+        }
+    }
+
+    // Z/z<type>,<addr>,<kind>: types 0 and 1 are breakpoints, 2, 3 and 4
+    // write, read and access watchpoints on `kind` bytes.
+    fn set_point(&mut self, arguments: &str, insert: bool) -> Option<()> {
+        let mut fields = arguments.split(',');
+        let point_type = fields.next()?;
+        let addr = u16::from_str_radix(fields.next()?, 16).ok()?;
+        let length = u16::from_str_radix(fields.next()?.split(';').next()?, 16).ok()?;
+
+        let kind = match point_type {
+            "0" | "1" => {
+                if insert {
+                    self.debugger.add_breakpoint(addr);
+                } else {
+                    self.debugger.remove_breakpoint(addr);
+                }
+                return Some(());
+            }
+            "2" => AccessKind::Write,
+            "3" => AccessKind::Read,
+            "4" => AccessKind::ReadWrite,
+            _ => return None,
+        };
+
+        for offset in 0..length.max(1) {
+            let watchpoint = Watchpoint {
+                location: Location::Memory(addr.wrapping_add(offset)),
+                kind,
+            };
+
+            if insert {
+                self.debugger.add_watchpoint(watchpoint);
+            } else {
+                self.debugger.remove_watchpoint(&watchpoint);
+            }
+        }
+
+        Some(())
+    }
+
+    fn report(&mut self, reason: HaltReason) {
+        if self.stream.is_some() {
+            let reply = self.stop_reply(reason);
+            if let Err(err) = self.send(&reply) {
+                eprintln!("GDB client disconnected: {}", err);
+                self.detach();
+            }
+        }
+    }
+
+    fn stop_reply(&self, reason: HaltReason) -> String {
+        match reason {
+            HaltReason::Breakpoint(_) => "T05swbreak:;".to_string(),
+            HaltReason::Watchpoint {
+                location: Location::Memory(addr),
+                access,
+                ..
+            } => {
+                let kind = self
+                    .debugger
+                    .watchpoints()
+                    .iter()
+                    .find(|watchpoint| watchpoint.location == Location::Memory(addr))
+                    .map_or(access, |watchpoint| watchpoint.kind);
+
+                let name = match kind {
+                    AccessKind::Write => "watch",
+                    AccessKind::Read => "rwatch",
+                    AccessKind::ReadWrite => "awatch",
+                };
+
+                format!("T05{}:{:x};", name, addr)
+            }
+            HaltReason::Watchpoint { .. } | HaltReason::Step => "T05".to_string(),
+            HaltReason::Pause => "T02".to_string(),
+            HaltReason::Exited => "W00".to_string(),
+        }
+    }
+
+    fn send(&mut self, packet: &str) -> std::io::Result<()> {
+        let frame = format!("${}#{:02x}", packet, checksum_of(packet.as_bytes()));
+        self.send_raw(frame.as_bytes())
+    }
+
+    fn send_raw(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        if let Some(stream) = &mut self.stream {
+            // replies are small, waiting for them to be sent is fine
+            stream.set_nonblocking(false)?;
+            stream.write_all(bytes)?;
+            stream.set_nonblocking(true)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Message from the client.
+#[derive(Debug, PartialEq, Eq)]
+enum Incoming {
+    Packet(String),
+    // packet whose checksum does not match, the client sends it again
+    Corrupted,
+    Interrupt,
+}
+
+// Takes the next message from the front of `buffer`, skipping acknowledgments
+// and noise. `None` until a whole message is received.
+fn next_incoming(buffer: &mut Vec<u8>) -> Option<Incoming> {
+    loop {
+        match *buffer.first()? {
+            b'$' => {
+                // $<packet>#<checksum>
+                let end = buffer.iter().position(|byte| *byte == b'#')?;
+                if buffer.len() < end + 3 {
+                    return None;
+                }
+
+                let frame: Vec<u8> = buffer.drain(..end + 3).collect();
+                let packet = &frame[1..end];
+                let checksum = std::str::from_utf8(&frame[end + 1..])
+                    .ok()
+                    .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+
+                return Some(if checksum == Some(checksum_of(packet)) {
+                    Incoming::Packet(String::from_utf8_lossy(packet).into_owned())
+                } else {
+                    Incoming::Corrupted
+                });
+            }
+            INTERRUPT => {
+                buffer.remove(0);
+                return Some(Incoming::Interrupt);
+            }
+            // acknowledgments and noise
+            _ => {
+                buffer.remove(0);
+            }
+        }
+    }
+}
+
+fn target_xml() -> String {
+    let mut registers = String::new();
+    for x in 0..16 {
+        registers += &format!(
+            "<reg name=\"v{:x}\" bitsize=\"8\" type=\"uint8\" regnum=\"{}\"/>",
+            x, x
+        );
+    }
+    registers += &format!(
+        "<reg name=\"i\" bitsize=\"16\" type=\"data_ptr\" regnum=\"{}\"/>\
+         <reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\" regnum=\"{}\"/>\
+         <reg name=\"dt\" bitsize=\"8\" type=\"uint8\" regnum=\"{}\"/>\
+         <reg name=\"st\" bitsize=\"8\" type=\"uint8\" regnum=\"{}\"/>\
+         <reg name=\"sp\" bitsize=\"8\" type=\"uint8\" regnum=\"{}\" group=\"stack\"/>",
+        I, PC, DT, ST, SP
+    );
+    for entry in 0..STACK_SIZE {
+        registers += &format!(
+            "<reg name=\"stack{}\" bitsize=\"16\" type=\"code_ptr\" regnum=\"{}\" group=\"stack\"/>",
+            entry,
+            STACK + entry
+        );
+    }
+
+    format!(
+        "<?xml version=\"1.0\"?>\
+         <!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+         <target version=\"1.0\"><feature name=\"org.chip8.core\">{}</feature></target>",
+        registers
+    )
+}
+
+// qXfer reply for the `offset,length` part of `document`
+fn xfer(document: &str, range: &str) -> Option<String> {
+    let (offset, length) = range.split_once(',')?;
+    let offset = usize::from_str_radix(offset, 16).ok()?;
+    let length = usize::from_str_radix(length, 16).ok()?;
+
+    let bytes = document.as_bytes();
+    let start = offset.min(bytes.len());
+    let end = offset.saturating_add(length).min(bytes.len());
+    let marker = if end == bytes.len() { 'l' } else { 'm' };
+
+    Some(format!("{}{}", marker, &document[start..end]))
+}
+
+fn read_register(chip8: &Chip8, register: usize) -> Vec<u8> {
+    let registers = chip8.registers();
+
+    match register {
+        0..=15 => vec![registers.register(register as u8).unwrap_or_default()],
+        I => registers.i().to_be_bytes().to_vec(),
+        PC => registers.pc().to_be_bytes().to_vec(),
+        DT => vec![registers.dt()],
+        ST => vec![registers.st()],
+        SP => vec![registers.stack().len() as u8],
+        _ => registers
+            .stack()
+            .get(register - STACK)
+            .cloned()
+            .unwrap_or_default()
+            .to_be_bytes()
+            .to_vec(),
+    }
+}
+
+fn register_size(register: usize) -> usize {
+    match register {
+        I | PC => 2,
+        STACK..=usize::MAX => 2,
+        _ => 1,
+    }
+}
+
+fn write_register(chip8: &mut Chip8, register: usize, bytes: &[u8]) -> Option<()> {
+    if register >= REGISTERS || bytes.len() != register_size(register) {
+        return None;
+    }
+
+    let registers = chip8.registers_mut();
+    let word = || u16::from_be_bytes([bytes[0], bytes[1]]);
+
+    match register {
+        0..=15 => registers.set_register(register as u8, bytes[0]).ok()?,
+        I => registers.set_i(word()),
+        PC => registers.set_pc(word()),
+        DT => registers.set_dt(bytes[0]),
+        ST => registers.set_st(bytes[0]),
+        SP => {
+            let mut stack = registers.stack().to_vec();
+            stack.resize((bytes[0] as usize).min(STACK_SIZE), 0);
+            registers.set_stack(stack);
+        }
+        _ => {
+            // entries past the stack depth are not stored
+            let mut stack = registers.stack().to_vec();
+            if let Some(entry) = stack.get_mut(register - STACK) {
+                *entry = word();
+            }
+            registers.set_stack(stack);
+        }
+    }
+
+    Some(())
+}
+
+fn write_registers(chip8: &mut Chip8, values: &str) -> Option<()> {
+    let bytes = unhex(values)?;
+
+    let mut position = 0;
+    for register in 0..REGISTERS {
+        let size = register_size(register);
+        write_register(chip8, register, bytes.get(position..position + size)?)?;
+        position += size;
+    }
+
+    Some(())
+}
+
+// <addr>,<length>
+fn memory_range(range: &str) -> Option<(usize, usize)> {
+    let (addr, length) = range.split_once(',')?;
+    let addr = usize::from_str_radix(addr, 16).ok()?;
+    let length = usize::from_str_radix(length, 16).ok()?;
+
+    if addr.checked_add(length)? > memory::SIZE {
+        return None;
+    }
+
+    Some((addr, length))
+}
+
+fn read_memory(chip8: &Chip8, range: &str) -> Option<Vec<u8>> {
+    let (addr, length) = memory_range(range)?;
+    Some(chip8.memory().bytes()[addr..addr + length].to_vec())
+}
+
+// <addr>,<length>:<bytes>
+fn write_memory(chip8: &mut Chip8, arguments: &str) -> Option<()> {
+    let (range, values) = arguments.split_once(':')?;
+    let (addr, length) = memory_range(range)?;
+    let bytes = unhex(values)?;
+
+    if bytes.len() != length {
+        return None;
+    }
+
+    for (offset, byte) in bytes.into_iter().enumerate() {
+        chip8.memory_mut().write_8((addr + offset) as u16, byte);
+    }
+
+    Some(())
+}
+
+fn ok_or_error(result: Option<()>) -> String {
+    match result {
+        Some(()) => "OK",
+        None => "E01",
+    }
+    .to_string()
+}
+
+fn checksum_of(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(packet: &str) -> Vec<u8> {
+        format!("${}#{:02x}", packet, checksum_of(packet.as_bytes())).into_bytes()
+    }
+
+    fn packet(packet: &str) -> Option<Incoming> {
+        Some(Incoming::Packet(packet.to_string()))
+    }
+
+    fn setup() -> (GdbStub, Chip8) {
+        let chip8 = Chip8::builder()
+            .rom(&[0x60, 0x01, 0x12, 0x02])
+            .build()
+            .unwrap();
+
+        // any free port, no client attaches
+        (GdbStub::new(0).unwrap(), chip8)
+    }
+
+    #[test]
+    fn packets_are_framed_and_checked() {
+        let mut buffer = b"+".to_vec();
+        buffer.extend(frame("g"));
+        buffer.extend(frame("m200,4"));
+        buffer.extend(b"$g#00");
+        buffer.push(INTERRUPT);
+
+        assert_eq!(next_incoming(&mut buffer), packet("g"));
+        assert_eq!(next_incoming(&mut buffer), packet("m200,4"));
+        assert_eq!(next_incoming(&mut buffer), Some(Incoming::Corrupted));
+        assert_eq!(next_incoming(&mut buffer), Some(Incoming::Interrupt));
+        assert_eq!(next_incoming(&mut buffer), None);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn partial_packets_wait_for_the_rest() {
+        let whole = frame("qSupported");
+
+        for length in 1..whole.len() {
+            let mut buffer = whole[..length].to_vec();
+            assert_eq!(next_incoming(&mut buffer), None, "{}", length);
+            assert_eq!(buffer, &whole[..length]);
+
+            buffer.extend(&whole[length..]);
+            assert_eq!(next_incoming(&mut buffer), packet("qSupported"));
+        }
+    }
+
+    #[test]
+    fn non_ascii_packets_are_framed() {
+        let mut buffer = b"$\xff\xfe#".to_vec();
+        buffer.extend(format!("{:02x}", checksum_of(b"\xff\xfe")).as_bytes());
+
+        assert_eq!(next_incoming(&mut buffer), packet("\u{fffd}\u{fffd}"));
+    }
+
+    #[test]
+    fn g_reads_every_register() {
+        let (mut gdb_stub, mut chip8) = setup();
+        chip8.registers_mut().set_register(0x1, 0xab).unwrap();
+        chip8.registers_mut().set_i(0x0123);
+        chip8.registers_mut().set_dt(0x3c);
+        chip8.registers_mut().set_stack(vec![0x0456]);
+
+        let reply = gdb_stub.handle_packet("g", &mut chip8).unwrap();
+        let expected = [
+            // V0 to VF
+            "00ab",
+            &"00".repeat(14),
+            // I, PC, DT and ST
+            "0123",
+            "0200",
+            "3c",
+            "00",
+            // SP and the stack
+            "01",
+            "0456",
+            &"0000".repeat(STACK_SIZE - 1),
+        ]
+        .concat();
+        assert_eq!(reply, expected);
+    }
+
+    #[test]
+    fn m_reads_memory() {
+        let (mut gdb_stub, mut chip8) = setup();
+
+        let reply = gdb_stub.handle_packet("m200,4", &mut chip8);
+        assert_eq!(reply.as_deref(), Some("60011202"));
+
+        // past the end of memory
+        let reply = gdb_stub.handle_packet("mffff,2", &mut chip8);
+        assert_eq!(reply.as_deref(), Some("E01"));
+        let reply = gdb_stub.handle_packet("m200", &mut chip8);
+        assert_eq!(reply.as_deref(), Some("E01"));
+    }
+
+    #[test]
+    fn z0_sets_and_clears_breakpoints() {
+        let (mut gdb_stub, mut chip8) = setup();
+
+        let reply = gdb_stub.handle_packet("Z0,202,2", &mut chip8);
+        assert_eq!(reply.as_deref(), Some("OK"));
+        assert_eq!(gdb_stub.debugger.breakpoints().collect::<Vec<_>>(), [0x202]);
+
+        assert_eq!(gdb_stub.handle_packet("c", &mut chip8), None);
+        gdb_stub.debugger.run_frame(&mut chip8).unwrap();
+        assert_eq!(
+            gdb_stub.debugger.halt_reason(),
+            Some(HaltReason::Breakpoint(0x202))
+        );
+
+        let reply = gdb_stub.handle_packet("z0,202,2", &mut chip8);
+        assert_eq!(reply.as_deref(), Some("OK"));
+        assert_eq!(gdb_stub.debugger.breakpoints().count(), 0);
+
+        let reply = gdb_stub.handle_packet("Z0,zzz,2", &mut chip8);
+        assert_eq!(reply.as_deref(), Some("E01"));
+    }
+
+    #[test]
+    fn unknown_packets_get_an_empty_reply() {
+        let (mut gdb_stub, mut chip8) = setup();
+
+        for packet in &[
+            "",
+            "X200,0:",
+            "vMustReplyEmpty",
+            "qUnknown",
+            "é",
+            "\u{fffd}g",
+        ] {
+            let reply = gdb_stub.handle_packet(packet, &mut chip8);
+            assert_eq!(reply.as_deref(), Some(""), "{}", packet);
+        }
+    }
+}
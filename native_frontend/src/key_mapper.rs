@@ -10,6 +10,26 @@ pub enum KeyboardError {
 
 pub struct KeyMapper;
 
+/// Keys of the keyboard mapped to the keypad, polled every frame.
+pub const KEYS: [Key; 16] = [
+    Key::Key0,
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::Key5,
+    Key::Key6,
+    Key::Key7,
+    Key::Key8,
+    Key::Key9,
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+];
+
 impl emulator::keyboard::KeyMapper<Key> for KeyMapper {
     fn map_key(&self, key: Key) -> Result<u8, Box<dyn std::error::Error>> {
         match key {
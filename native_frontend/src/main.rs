@@ -1,34 +1,129 @@
-use crate::native_frontend::NativeWindowFrontend;
-use core::emulator;
-use core::emulator::cpu::Cpu;
-use core::emulator::display::Display;
-use core::emulator::memory::Memory;
+use crate::audio::Audio;
+use crate::gdb_stub::GdbStub;
+use crate::native_frontend::{Input, NativeWindowFrontend};
+use crate::save_slots::SaveSlots;
+use chip8_core::emulator;
+use chip8_core::emulator::chip8::Chip8;
+use chip8_core::emulator::cpu::tracer::Tracer;
+use chip8_core::emulator::movie::{Movie, MoviePlayer, MovieRecorder};
+use chip8_core::emulator::platform::Platform;
+use chip8_core::emulator::quirks::Quirks;
+use chip8_core::emulator::rewind::RewindBuffer;
+use std::fs;
 use std::fs::File;
-use std::io::Read;
+use std::io::{BufWriter, Read};
 use std::path::Path;
 use structopt::StructOpt;
 
 mod audio;
+mod gdb_stub;
 mod key_mapper;
 mod native_frontend;
+mod save_slots;
 
 #[derive(Debug, StructOpt)]
 pub struct Opt {
+    /// Rom to run, or Octo source when its extension is .8o
     #[structopt(short)]
     file: String,
+    /// Platform the rom was written for: chip8, schip or xochip, every
+    /// instruction with Cowgod's interpretation when not set
+    #[structopt(short, long)]
+    platform: Option<Platform>,
+    /// Quirks preset overriding the platform ones: cowgod, vip, chip48, schip or xochip
+    #[structopt(short, long)]
+    quirks: Option<Quirks>,
+    /// Instructions executed for every 60Hz frame
+    #[structopt(short, long, default_value = "10")]
+    instructions_per_frame: usize,
+    /// Memory in MiB kept to rewind the emulation, counted in bytes of compressed frames
+    #[structopt(short, long, default_value = "16")]
+    rewind_buffer: usize,
+    /// Records the keys of every frame into a movie file
+    #[structopt(long, conflicts_with = "play")]
+    record: Option<String>,
+    /// Plays back a movie file recorded on the same rom, ignoring platform and quirks
+    #[structopt(long)]
+    play: Option<String>,
+    /// Writes every instruction executed, with the registers, to this file
+    #[structopt(long)]
+    trace: Option<String>,
+    /// Serves the GDB remote protocol on this local TCP port
+    #[structopt(long, conflicts_with_all = &["record", "play"])]
+    gdb: Option<u16>,
 }
 
+// a whole snapshot is kept every second of emulation, deltas for the other frames
+const REWIND_KEYFRAME_INTERVAL: usize = 60;
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let opt: Opt = Opt::from_args();
 
     let mut rom = Vec::new();
-    let mut file = File::open(Path::new(opt.file.as_str()))?;
+    let rom_file = Path::new(opt.file.as_str());
+    let mut file = File::open(rom_file)?;
     file.read_to_end(&mut rom)?;
 
-    let mut memory = Memory::default();
-    memory.load_rom(&rom);
+    // Octo sources are compiled before running
+    if opt.file.ends_with(".8o") {
+        let source = String::from_utf8(rom)?;
+        rom = octo::compile(&source).map_err(|error| format!("{}:{}", opt.file, error))?;
+    }
+
+    let movie = match &opt.play {
+        Some(movie_file) => Some(Movie::decode(&fs::read(movie_file)?)?),
+        None => None,
+    };
+
+    let mut builder = match &movie {
+        Some(movie) => movie.chip8_builder(&rom)?,
+        None => {
+            let mut builder = Chip8::builder()
+                .rom(&rom)
+                .instructions_per_frame(opt.instructions_per_frame);
+            if let Some(platform) = opt.platform {
+                builder = builder.platform(platform);
+            }
+            match opt.quirks {
+                Some(quirks) => builder.quirks(quirks),
+                None => builder,
+            }
+        }
+    };
+    builder = builder.audio(Box::new(Audio::new()?));
+    let mut chip8 = builder.build()?;
+
+    if let Some(trace_file) = &opt.trace {
+        let output = BufWriter::new(File::create(trace_file)?);
+        chip8.set_tracer(Some(Tracer::new(Box::new(output))));
+    }
+
+    let mut input = match (movie, &opt.record) {
+        (Some(movie), _) => Input::Playing(MoviePlayer::new(movie)),
+        (None, Some(_)) => Input::Recording(MovieRecorder::new(&chip8)),
+        (None, None) => Input::Live,
+    };
+
+    let mut gdb_stub = match opt.gdb {
+        Some(port) => Some(GdbStub::new(port)?),
+        None => None,
+    };
+
+    NativeWindowFrontend::new()?.run(
+        &mut chip8,
+        &SaveSlots::new(rom_file),
+        &mut RewindBuffer::new(opt.rewind_buffer * 1024 * 1024, REWIND_KEYFRAME_INTERVAL),
+        &mut input,
+        gdb_stub.as_mut(),
+    )?;
+
+    if let Some(tracer) = chip8.tracer_mut() {
+        tracer.flush()?;
+    }
 
-    NativeWindowFrontend::new()?.run(&mut Cpu::new(memory), &mut Display::default())?;
+    if let (Some(movie_file), Input::Recording(recorder)) = (&opt.record, input) {
+        fs::write(movie_file, recorder.finish().encode())?;
+    }
 
     Ok(())
 }
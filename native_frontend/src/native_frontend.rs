@@ -1,33 +1,61 @@
-use crate::audio::Audio;
-use crate::key_mapper::KeyMapper;
-use core::emulator;
-use minifb::{Key, Scale, ScaleMode, Window, WindowOptions};
+use crate::gdb_stub::GdbStub;
+use crate::key_mapper::{KeyMapper, KEYS};
+use crate::save_slots::SaveSlots;
+use chip8_core::emulator;
+use chip8_core::emulator::chip8::Chip8;
+use chip8_core::emulator::keyboard::KeyboardState;
+use chip8_core::emulator::movie::{MoviePlayer, MovieRecorder};
+use chip8_core::emulator::rewind::RewindBuffer;
+use chip8_core::emulator::state::Snapshot;
+use minifb::{Key, KeyRepeat, Scale, ScaleMode, Window, WindowOptions};
+
+// colors of the pixels lit on no plane, on the first, on the second and on both
+const PALETTE: [u32; 4] = [0x00000000, 0xFFFFFFFF, 0xFFAAAAAA, 0xFF555555];
+
+// hotkeys saving to and loading from the slot at the same position
+const SAVE_KEYS: [Key; 4] = [Key::F1, Key::F2, Key::F3, Key::F4];
+const LOAD_KEYS: [Key; 4] = [Key::F5, Key::F6, Key::F7, Key::F8];
+const REWIND_KEY: Key = Key::Backspace;
+
+/// Where the keys of every frame come from.
+pub enum Input {
+    Live,
+    Recording(MovieRecorder),
+    Playing(MoviePlayer),
+}
 
 pub struct NativeWindowFrontend {
-    audio: Audio,
     window: Window,
     key_mapper: KeyMapper,
+    // colors of the display, reused by every update
+    buffer: Vec<u32>,
+    // state pushed to the rewind history, reused by every update
+    snapshot: Snapshot,
 }
 
 impl NativeWindowFrontend {
     pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
         let mut window = Window::new(
             "Chip-8 - ESC to exit",
-            emulator::display::Display::width(),
-            emulator::display::Display::height(),
+            emulator::display::Display::max_width(),
+            emulator::display::Display::max_height(),
             WindowOptions {
-                scale: Scale::X16,
+                scale: Scale::X8,
                 scale_mode: ScaleMode::Stretch,
                 ..WindowOptions::default()
             },
         )?;
 
-        window.limit_update_rate(Some(std::time::Duration::from_millis(2)));
+        // one emulated frame per update, at 60Hz
+        window.limit_update_rate(Some(std::time::Duration::from_micros(16666)));
 
         Ok(Self {
-            audio: Audio::new()?,
             window,
             key_mapper: KeyMapper,
+            buffer: Vec::with_capacity(
+                emulator::display::Display::max_width() * emulator::display::Display::max_height(),
+            ),
+            snapshot: Snapshot::default(),
         })
     }
 }
@@ -35,32 +63,87 @@ impl NativeWindowFrontend {
 impl NativeWindowFrontend {
     pub fn run(
         &mut self,
-        cpu: &mut emulator::cpu::Cpu,
-        display: &mut emulator::display::Display,
+        chip8: &mut Chip8,
+        save_slots: &SaveSlots,
+        rewind_buffer: &mut RewindBuffer,
+        input: &mut Input,
+        mut gdb_stub: Option<&mut GdbStub>,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        while self.window.is_open() && !self.window.is_key_down(Key::Escape) {
-            let keys = self.window.get_keys().unwrap_or_default();
-
-            emulator::tick(cpu, display, &mut self.audio, keys, &self.key_mapper)?;
-
-            self.window.update_with_buffer(
-                display
-                    .buffer()
-                    .iter()
-                    .map(|bit| {
-                        if *bit {
-                            0xFFFFFFFF as u32
-                        } else {
-                            0x00000000 as u32
-                        }
-                    })
-                    .collect::<Vec<u32>>()
-                    .as_slice(),
-                emulator::display::Display::width(),
-                emulator::display::Display::height(),
-            )?
+        while self.window.is_open() && !self.window.is_key_down(Key::Escape) && !chip8.is_exited() {
+            // jumping around in time would desync movies
+            let is_live = matches!(input, Input::Live);
+            // a debugger pauses the emulation in the middle of frames
+            let can_rewind = is_live && gdb_stub.is_none();
+
+            self.handle_save_slots(chip8, save_slots, is_live);
+
+            if can_rewind && self.window.is_key_down(REWIND_KEY) {
+                // plays the game backwards, one frame per update
+                if let Some(snapshot) = rewind_buffer.pop() {
+                    chip8.load_state(&snapshot)?;
+                }
+            } else {
+                chip8.set_keyboard_state(self.next_keyboard_state(input));
+                match gdb_stub.as_mut() {
+                    Some(gdb_stub) => gdb_stub.run_frame(chip8)?,
+                    None => chip8.run_frame()?,
+                }
+
+                if can_rewind {
+                    chip8.save_state_into(&mut self.snapshot);
+                    rewind_buffer.push(&self.snapshot);
+                }
+            }
+
+            let display = chip8.display();
+            self.buffer.clear();
+            self.buffer
+                .extend(display.pixels().map(|color| PALETTE[color as usize]));
+            self.window
+                .update_with_buffer(&self.buffer, display.width(), display.height())?
         }
 
         Ok(())
     }
+
+    fn next_keyboard_state(&self, input: &mut Input) -> KeyboardState {
+        if let Input::Playing(player) = input {
+            if let Some(keyboard_state) = player.next_frame() {
+                return keyboard_state;
+            }
+
+            // the player takes control when the movie is over
+            *input = Input::Live;
+        }
+
+        let keys = KEYS.iter().filter(|key| self.window.is_key_down(**key));
+        let keyboard_state = KeyboardState::new(keys.copied(), &self.key_mapper);
+
+        if let Input::Recording(recorder) = input {
+            recorder.record(&keyboard_state);
+        }
+
+        keyboard_state
+    }
+
+    fn handle_save_slots(&self, chip8: &mut Chip8, save_slots: &SaveSlots, can_load: bool) {
+        for (slot, key) in SAVE_KEYS.iter().enumerate() {
+            if self.window.is_key_pressed(*key, KeyRepeat::No) {
+                if let Err(err) = save_slots.save(slot + 1, &chip8.save_state()) {
+                    eprintln!("Error saving slot {}: {}", slot + 1, err);
+                }
+            }
+        }
+
+        for (slot, key) in LOAD_KEYS.iter().enumerate() {
+            if can_load && self.window.is_key_pressed(*key, KeyRepeat::No) {
+                if let Err(err) = save_slots
+                    .load(slot + 1)
+                    .and_then(|snapshot| Ok(chip8.load_state(&snapshot)?))
+                {
+                    eprintln!("Error loading slot {}: {}", slot + 1, err);
+                }
+            }
+        }
+    }
 }
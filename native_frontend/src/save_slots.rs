@@ -0,0 +1,30 @@
+use chip8_core::emulator::state::Snapshot;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Save states of a rom, stored next to it as `<rom file>.state<slot>`.
+pub struct SaveSlots {
+    rom_file: PathBuf,
+}
+
+impl SaveSlots {
+    pub fn new(rom_file: &Path) -> Self {
+        Self {
+            rom_file: rom_file.to_path_buf(),
+        }
+    }
+
+    pub fn save(&self, slot: usize, snapshot: &Snapshot) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(fs::write(self.path(slot), snapshot.encode())?)
+    }
+
+    pub fn load(&self, slot: usize) -> Result<Snapshot, Box<dyn std::error::Error>> {
+        Ok(Snapshot::decode(&fs::read(self.path(slot))?)?)
+    }
+
+    fn path(&self, slot: usize) -> PathBuf {
+        let mut path = self.rom_file.clone().into_os_string();
+        path.push(format!(".state{}", slot));
+        path.into()
+    }
+}
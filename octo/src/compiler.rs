@@ -0,0 +1,912 @@
+use crate::error::{CompilerError, ErrorKind};
+use crate::lexer::{number, Token};
+use chip8_core::emulator::memory;
+use std::collections::{HashMap, VecDeque};
+
+const START_ADDRESS: usize = 0x200;
+
+// macros expanding into calls to themselves would never end
+const MAX_MACRO_DEPTH: usize = 64;
+
+// words which can not name labels, constants, aliases or macros
+const KEYWORDS: &str = "\
+    : := += -= =- |= &= ^= >>= <<= == != < > <= >= - { } ; return clear bcd save load sprite jump \
+    jump0 native delay buzzer key -key random hex bighex long if then begin else end loop again \
+    while saveflags loadflags hires lores exit scroll-down scroll-up scroll-left scroll-right plane \
+    audio pitch";
+
+type Result<T> = std::result::Result<T, CompilerError>;
+
+struct Macro {
+    arguments: Vec<String>,
+    body: Vec<Token>,
+    calls: usize,
+}
+
+// how an address is stored in the instructions referring to it
+#[derive(Clone, Copy, PartialEq)]
+enum AddressKind {
+    // the 12 bits of nnn
+    Short,
+    // the 16 bits following `i := long`
+    Long,
+    // split between v0 and v1 by `:unpack`
+    Unpack,
+}
+
+// an address used before its label is defined
+struct Fixup {
+    address: usize,
+    kind: AddressKind,
+    token: Token,
+}
+
+// blocks waiting for their end, with the jumps to patch
+enum Control {
+    Loop { start: usize, whiles: Vec<usize> },
+    Begin { jump: usize, token: Token },
+    Else { jump: usize, token: Token },
+}
+
+// condition of an `if` or `while`, skipping the next instruction when false
+struct Condition {
+    register: u8,
+    comparison: String,
+    operand: Option<Token>,
+}
+
+pub struct Compiler {
+    tokens: VecDeque<Token>,
+    last_token: Option<Token>,
+    rom: Vec<u8>,
+    here: usize,
+    labels: HashMap<String, usize>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<Fixup>,
+    control: Vec<Control>,
+    // 0x200 holds a jump to main unless main comes first
+    has_main_jump: bool,
+}
+
+impl Compiler {
+    pub fn new(tokens: VecDeque<Token>) -> Self {
+        Compiler {
+            tokens,
+            last_token: None,
+            rom: vec![],
+            here: START_ADDRESS,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: vec![],
+            control: vec![],
+            has_main_jump: true,
+        }
+    }
+
+    pub fn compile(mut self) -> Result<Vec<u8>> {
+        self.emit(&[0x00, 0x00])?;
+
+        while let Some(token) = self.tokens.pop_front() {
+            self.last_token = Some(token.clone());
+            self.statement(token)?;
+        }
+
+        if let Some(control) = self.control.last() {
+            let (name, token) = match control {
+                Control::Loop { .. } => ("loop", None),
+                Control::Begin { token, .. } => ("begin", Some(token)),
+                Control::Else { token, .. } => ("else", Some(token)),
+            };
+            return Err(self.error_at(token, ErrorKind::Unclosed(name)));
+        }
+
+        if self.has_main_jump {
+            let main = match self.labels.get("main") {
+                Some(main) => *main,
+                None => return Err(self.error_at(None, ErrorKind::MissingMain)),
+            };
+            self.patch(START_ADDRESS, 0x1000 | main as u16);
+        }
+
+        for fixup in std::mem::take(&mut self.fixups) {
+            let address = match self.labels.get(&fixup.token.text) {
+                Some(address) => *address,
+                None => {
+                    let kind = ErrorKind::UndefinedName(fixup.token.text.clone());
+                    return Err(self.error_at(Some(&fixup.token), kind));
+                }
+            };
+
+            self.patch_address(fixup.address, fixup.kind, address as i64, &fixup.token)?;
+        }
+
+        Ok(self.rom)
+    }
+
+    fn statement(&mut self, token: Token) -> Result<()> {
+        if self.aliases.contains_key(&token.text) || is_register(&token.text) {
+            return self.register_statement(&token);
+        }
+
+        match token.text.as_str() {
+            ":" => self.label(),
+            ":const" => {
+                let name = self.name()?;
+                let value = self.next()?;
+                let value = self.value(&value)?;
+                self.constants.insert(name, value);
+                Ok(())
+            }
+            ":alias" => {
+                let name = self.name()?;
+                let register = self.register()?;
+                self.aliases.insert(name, register);
+                Ok(())
+            }
+            ":unpack" => {
+                let nibble = self.next()?;
+                let nibble = self.integer(&nibble, 0, 0xf)?;
+                let address = self.next()?;
+                self.address(&address, AddressKind::Unpack, 0x6000 | nibble << 4)
+            }
+            ":next" => {
+                // names the byte after the opcode of the next instruction
+                let name = self.name()?;
+                self.define_label(name, self.here + 1)
+            }
+            ":org" => {
+                let address = self.next()?;
+                self.here =
+                    self.integer(&address, START_ADDRESS as i64, memory::SIZE as i64 - 1)? as usize;
+                Ok(())
+            }
+            ":macro" => self.macro_definition(),
+            ":calc" => {
+                let name = self.name()?;
+                let value = self.calc_block()?;
+                self.constants.insert(name, value);
+                Ok(())
+            }
+            ":byte" => {
+                let value = if self.peek_is("{") {
+                    let value = self.calc_block()?;
+                    check_range(&token, value.floor() as i64, -0x80, 0xff)? as u8
+                } else {
+                    let value = self.next()?;
+                    self.integer(&value, -0x80, 0xff)? as u8
+                };
+                self.emit(&[value])
+            }
+            ":pointer" => {
+                let address = self.next()?;
+                self.address(&address, AddressKind::Long, 0)
+            }
+            ":call" => {
+                let address = self.next()?;
+                self.address(&address, AddressKind::Short, 0x2000)
+            }
+            ":breakpoint" => self.name().map(|_| ()),
+            ":monitor" => {
+                self.next()?;
+                self.next().map(|_| ())
+            }
+            ";" | "return" => self.emit_opcode(0x00ee),
+            "clear" => self.emit_opcode(0x00e0),
+            "scroll-right" => self.emit_opcode(0x00fb),
+            "scroll-left" => self.emit_opcode(0x00fc),
+            "exit" => self.emit_opcode(0x00fd),
+            "lores" => self.emit_opcode(0x00fe),
+            "hires" => self.emit_opcode(0x00ff),
+            "audio" => self.emit_opcode(0xf002),
+            "scroll-down" => {
+                let n = self.next()?;
+                let n = self.integer(&n, 1, 0xf)?;
+                self.emit_opcode(0x00c0 | n)
+            }
+            "scroll-up" => {
+                let n = self.next()?;
+                let n = self.integer(&n, 1, 0xf)?;
+                self.emit_opcode(0x00d0 | n)
+            }
+            "plane" => {
+                let n = self.next()?;
+                let n = self.integer(&n, 0, 0xf)?;
+                self.emit_opcode(0xf001 | n << 8)
+            }
+            "bcd" => self.register_opcode(0xf033),
+            "saveflags" => self.register_opcode(0xf075),
+            "loadflags" => self.register_opcode(0xf085),
+            "save" | "load" => {
+                let x = self.register()? as u16;
+                if self.peek_is("-") {
+                    self.next()?;
+                    let y = self.register()? as u16;
+                    let opcode = if token.text == "save" { 0x5002 } else { 0x5003 };
+                    self.emit_opcode(opcode | x << 8 | y << 4)
+                } else {
+                    let opcode = if token.text == "save" { 0xf055 } else { 0xf065 };
+                    self.emit_opcode(opcode | x << 8)
+                }
+            }
+            "sprite" => {
+                let x = self.register()? as u16;
+                let y = self.register()? as u16;
+                let n = self.next()?;
+                let n = self.integer(&n, 0, 0xf)?;
+                self.emit_opcode(0xd000 | x << 8 | y << 4 | n)
+            }
+            "jump" => {
+                let address = self.next()?;
+                self.address(&address, AddressKind::Short, 0x1000)
+            }
+            "jump0" => {
+                let address = self.next()?;
+                self.address(&address, AddressKind::Short, 0xb000)
+            }
+            "native" => {
+                let address = self.next()?;
+                self.address(&address, AddressKind::Short, 0x0000)
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let opcode = match token.text.as_str() {
+                    "delay" => 0xf015,
+                    "buzzer" => 0xf018,
+                    _ => 0xf03a,
+                };
+                self.register_opcode(opcode)
+            }
+            "i" => self.i_statement(),
+            "if" => self.if_statement(),
+            "else" => match self.control.pop() {
+                Some(Control::Begin { jump, .. }) => {
+                    let else_jump = self.here;
+                    self.emit_opcode(0x1000)?;
+                    self.patch_jump(jump, &token)?;
+                    self.control.push(Control::Else {
+                        jump: else_jump,
+                        token,
+                    });
+                    Ok(())
+                }
+                _ => Err(error(&token, ErrorKind::Unmatched("else", "begin"))),
+            },
+            "end" => match self.control.pop() {
+                Some(Control::Begin { jump, .. }) | Some(Control::Else { jump, .. }) => {
+                    self.patch_jump(jump, &token)
+                }
+                _ => Err(error(&token, ErrorKind::Unmatched("end", "begin"))),
+            },
+            "loop" => {
+                self.control.push(Control::Loop {
+                    start: self.here,
+                    whiles: vec![],
+                });
+                Ok(())
+            }
+            "while" => {
+                let condition = self.condition()?;
+                // skips the jump out of the loop when the condition holds
+                self.emit_condition(&condition, true)?;
+
+                let jump = self.here;
+                match self
+                    .control
+                    .iter_mut()
+                    .rev()
+                    .find_map(|control| match control {
+                        Control::Loop { whiles, .. } => Some(whiles),
+                        _ => None,
+                    }) {
+                    Some(whiles) => whiles.push(jump),
+                    None => return Err(error(&token, ErrorKind::Unmatched("while", "loop"))),
+                }
+                self.emit_opcode(0x1000)
+            }
+            "again" => match self.control.pop() {
+                Some(Control::Loop { start, whiles }) => {
+                    check_range(&token, start as i64, 0, 0xfff)?;
+                    self.emit_opcode(0x1000 | start as u16)?;
+                    for jump in whiles {
+                        self.patch_jump(jump, &token)?;
+                    }
+                    Ok(())
+                }
+                _ => Err(error(&token, ErrorKind::Unmatched("again", "loop"))),
+            },
+            text if self.macros.contains_key(text) => self.macro_call(&token),
+            text if number(text).is_some() || self.constants.contains_key(text) => {
+                let value = self.integer(&token, -0x80, 0xff)?;
+                self.emit(&[value as u8])
+            }
+            // a bare label calls its subroutine
+            _ => {
+                validate_name(&token)?;
+                self.address(&token, AddressKind::Short, 0x2000)
+            }
+        }
+    }
+
+    // statements starting with a register
+    fn register_statement(&mut self, token: &Token) -> Result<()> {
+        let x = self.register_of(token)? as u16;
+        let operator = self.next()?;
+
+        match operator.text.as_str() {
+            ":=" => {
+                let operand = self.next()?;
+                match operand.text.as_str() {
+                    "key" => self.emit_opcode(0xf00a | x << 8),
+                    "delay" => self.emit_opcode(0xf007 | x << 8),
+                    "random" => {
+                        let mask = self.next()?;
+                        let mask = self.integer(&mask, -0x80, 0xff)? & 0xff;
+                        self.emit_opcode(0xc000 | x << 8 | mask)
+                    }
+                    _ if self.is_register_token(&operand) => {
+                        let y = self.register_of(&operand)? as u16;
+                        self.emit_opcode(0x8000 | x << 8 | y << 4)
+                    }
+                    _ => {
+                        let value = self.integer(&operand, -0x80, 0xff)? & 0xff;
+                        self.emit_opcode(0x6000 | x << 8 | value)
+                    }
+                }
+            }
+            "+=" | "-=" => {
+                let operand = self.next()?;
+                if self.is_register_token(&operand) {
+                    let y = self.register_of(&operand)? as u16;
+                    let opcode = if operator.text == "+=" {
+                        0x8004
+                    } else {
+                        0x8005
+                    };
+                    self.emit_opcode(opcode | x << 8 | y << 4)
+                } else {
+                    let value = self.integer(&operand, -0x80, 0xff)?;
+                    let value = if operator.text == "+=" {
+                        value
+                    } else {
+                        value.wrapping_neg()
+                    };
+                    self.emit_opcode(0x7000 | x << 8 | (value & 0xff))
+                }
+            }
+            "=-" | "|=" | "&=" | "^=" | ">>=" | "<<=" => {
+                let y = self.register()? as u16;
+                let opcode = match operator.text.as_str() {
+                    "|=" => 0x8001,
+                    "&=" => 0x8002,
+                    "^=" => 0x8003,
+                    ">>=" => 0x8006,
+                    "=-" => 0x8007,
+                    _ => 0x800e,
+                };
+                self.emit_opcode(opcode | x << 8 | y << 4)
+            }
+            _ => Err(error(&operator, expected("an assignment", &operator))),
+        }
+    }
+
+    fn i_statement(&mut self) -> Result<()> {
+        let operator = self.next()?;
+        match operator.text.as_str() {
+            ":=" => {
+                let operand = self.next()?;
+                match operand.text.as_str() {
+                    "hex" => self.register_opcode(0xf029),
+                    "bighex" => self.register_opcode(0xf030),
+                    "long" => {
+                        self.emit_opcode(0xf000)?;
+                        let address = self.next()?;
+                        self.address(&address, AddressKind::Long, 0)
+                    }
+                    _ => self.address(&operand, AddressKind::Short, 0xa000),
+                }
+            }
+            "+=" => self.register_opcode(0xf01e),
+            _ => Err(error(&operator, expected("`:=` or `+=`", &operator))),
+        }
+    }
+
+    fn if_statement(&mut self) -> Result<()> {
+        let condition = self.condition()?;
+        let block = self.next()?;
+
+        match block.text.as_str() {
+            "then" => self.emit_condition(&condition, false),
+            "begin" => {
+                // skips the jump over the block when the condition holds
+                self.emit_condition(&condition, true)?;
+                self.control.push(Control::Begin {
+                    jump: self.here,
+                    token: block,
+                });
+                self.emit_opcode(0x1000)
+            }
+            _ => Err(error(&block, expected("`then` or `begin`", &block))),
+        }
+    }
+
+    fn condition(&mut self) -> Result<Condition> {
+        let register = self.register()?;
+        let comparison = self.next()?;
+
+        let operand = match comparison.text.as_str() {
+            "key" | "-key" => None,
+            "==" | "!=" | "<" | ">" | "<=" | ">=" => Some(self.next()?),
+            _ => return Err(error(&comparison, expected("a comparison", &comparison))),
+        };
+
+        Ok(Condition {
+            register,
+            comparison: comparison.text,
+            operand,
+        })
+    }
+
+    // instructions skipping the next one when the condition is false, or
+    // when it is true if `negated`
+    fn emit_condition(&mut self, condition: &Condition, negated: bool) -> Result<()> {
+        let x = condition.register as u16;
+        let comparison = match (condition.comparison.as_str(), negated) {
+            (comparison, false) => comparison,
+            ("==", true) => "!=",
+            ("!=", true) => "==",
+            ("key", true) => "-key",
+            ("-key", true) => "key",
+            ("<", true) => ">=",
+            (">", true) => "<=",
+            ("<=", true) => ">",
+            (_, true) => "<",
+        };
+
+        let operand = match &condition.operand {
+            Some(operand) => operand.clone(),
+            None => {
+                let opcode = if comparison == "key" { 0xe0a1 } else { 0xe09e };
+                return self.emit_opcode(opcode | x << 8);
+            }
+        };
+        let y = if self.is_register_token(&operand) {
+            Some(self.register_of(&operand)? as u16)
+        } else {
+            None
+        };
+        let value = match y {
+            Some(_) => 0,
+            None => self.integer(&operand, -0x80, 0xff)? & 0xff,
+        };
+
+        match comparison {
+            "==" | "!=" => {
+                let opcode = match (comparison, y) {
+                    ("==", Some(y)) => 0x9000 | y << 4,
+                    ("==", None) => 0x4000 | value,
+                    (_, Some(y)) => 0x5000 | y << 4,
+                    (_, None) => 0x3000 | value,
+                };
+                self.emit_opcode(opcode | x << 8)
+            }
+            _ => {
+                // vf := operand, then subtracts to compare through the borrow
+                let temp = self.aliases.get("compare-temp").copied().unwrap_or(0xf) as u16;
+                match y {
+                    Some(y) => self.emit_opcode(0x8000 | temp << 8 | y << 4)?,
+                    None => self.emit_opcode(0x6000 | temp << 8 | value)?,
+                }
+
+                let (subtraction, skip) = match comparison {
+                    ">" => (0x8005, 0x3f01),
+                    "<" => (0x8007, 0x3f01),
+                    ">=" => (0x8007, 0x4f01),
+                    _ => (0x8005, 0x4f01),
+                };
+                self.emit_opcode(subtraction | temp << 8 | x << 4)?;
+                self.emit_opcode(skip)
+            }
+        }
+    }
+
+    fn label(&mut self) -> Result<()> {
+        let name = self.name()?;
+
+        // code starting with main needs no jump to it
+        if name == "main"
+            && self.has_main_jump
+            && self.here == START_ADDRESS + 2
+            && self.rom.len() == 2
+        {
+            self.rom.clear();
+            self.here = START_ADDRESS;
+            self.has_main_jump = false;
+        }
+
+        self.define_label(name, self.here)
+    }
+
+    fn define_label(&mut self, name: String, address: usize) -> Result<()> {
+        if self.labels.insert(name.clone(), address).is_some() {
+            let token = self.last_token.clone();
+            return Err(self.error_at(token.as_ref(), ErrorKind::DuplicateName(name)));
+        }
+
+        Ok(())
+    }
+
+    fn macro_definition(&mut self) -> Result<()> {
+        let name = self.name()?;
+
+        let mut arguments = vec![];
+        loop {
+            let token = self.next()?;
+            if token.text == "{" {
+                break;
+            }
+            arguments.push(token.text);
+        }
+
+        let mut body = vec![];
+        let mut depth = 0;
+        loop {
+            let token = self.next()?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" if depth == 0 => break,
+                "}" => depth -= 1,
+                _ => {}
+            }
+            body.push(token);
+        }
+
+        self.macros.insert(
+            name,
+            Macro {
+                arguments,
+                body,
+                calls: 0,
+            },
+        );
+        Ok(())
+    }
+
+    // replaces the call with the body of the macro, its arguments substituted
+    fn macro_call(&mut self, token: &Token) -> Result<()> {
+        if token.depth >= MAX_MACRO_DEPTH {
+            let kind = ErrorKind::MacroTooDeep(token.text.clone(), MAX_MACRO_DEPTH);
+            return Err(error(token, kind));
+        }
+
+        let count = self.macros[&token.text].arguments.len();
+        let mut values = HashMap::new();
+        for index in 0..count {
+            let value = self.next()?;
+            values.insert(self.macros[&token.text].arguments[index].clone(), value);
+        }
+
+        let definition = self.macros.get_mut(&token.text).unwrap();
+        let calls = definition.calls;
+        definition.calls += 1;
+
+        for body_token in definition.body.iter().rev() {
+            let mut body_token = match values.get(&body_token.text) {
+                Some(value) => value.clone(),
+                None => body_token.clone(),
+            };
+            if body_token.text == "CALLS" {
+                body_token.text = calls.to_string();
+            }
+            body_token.depth = token.depth + 1;
+
+            self.tokens.push_front(body_token);
+        }
+
+        Ok(())
+    }
+
+    // value of `{ expression }`
+    fn calc_block(&mut self) -> Result<f64> {
+        self.expect("{")?;
+        let value = self.calc()?;
+        self.expect("}")?;
+        Ok(value)
+    }
+
+    // operators share the same precedence and are evaluated right to left
+    fn calc(&mut self) -> Result<f64> {
+        let token = self.next()?;
+        let unary: Option<fn(f64) -> f64> = match token.text.as_str() {
+            "-" => Some(|x| -x),
+            "~" => Some(|x| !(x as i64) as f64),
+            "!" => Some(|x| (x == 0.0) as i64 as f64),
+            "sin" => Some(f64::sin),
+            "cos" => Some(f64::cos),
+            "tan" => Some(f64::tan),
+            "exp" => Some(f64::exp),
+            "log" => Some(f64::ln),
+            "abs" => Some(f64::abs),
+            "sqrt" => Some(f64::sqrt),
+            "sign" => Some(f64::signum),
+            "ceil" => Some(f64::ceil),
+            "floor" => Some(f64::floor),
+            _ => None,
+        };
+        if let Some(unary) = unary {
+            return Ok(unary(self.calc()?));
+        }
+        if token.text == "@" {
+            let address = self.calc()? as usize;
+            let byte = address
+                .checked_sub(START_ADDRESS)
+                .and_then(|offset| self.rom.get(offset));
+            return Ok(byte.copied().unwrap_or_default() as f64);
+        }
+
+        let left = match token.text.as_str() {
+            "(" => {
+                let value = self.calc()?;
+                self.expect(")")?;
+                value
+            }
+            "HERE" => self.here as f64,
+            "PI" => std::f64::consts::PI,
+            "E" => std::f64::consts::E,
+            _ => self.value(&token)?,
+        };
+
+        let operator = match self.tokens.front() {
+            Some(operator) => operator.text.clone(),
+            None => return Ok(left),
+        };
+        let binary: fn(f64, f64) -> f64 = match operator.as_str() {
+            "+" => |x, y| x + y,
+            "-" => |x, y| x - y,
+            "*" => |x, y| x * y,
+            "/" => |x, y| x / y,
+            "%" => |x, y| x % y,
+            "&" => |x, y| (x as i64 & y as i64) as f64,
+            "|" => |x, y| (x as i64 | y as i64) as f64,
+            "^" => |x, y| (x as i64 ^ y as i64) as f64,
+            "<<" => |x, y| ((x as i64) << (y as i64 & 0x3f)) as f64,
+            ">>" => |x, y| ((x as i64) >> (y as i64 & 0x3f)) as f64,
+            "pow" => f64::powf,
+            "min" => f64::min,
+            "max" => f64::max,
+            "<" => |x, y| (x < y) as i64 as f64,
+            "<=" => |x, y| (x <= y) as i64 as f64,
+            "==" => |x, y| (x == y) as i64 as f64,
+            "!=" => |x, y| (x != y) as i64 as f64,
+            ">=" => |x, y| (x >= y) as i64 as f64,
+            ">" => |x, y| (x > y) as i64 as f64,
+            _ => return Ok(left),
+        };
+
+        self.next()?;
+        Ok(binary(left, self.calc()?))
+    }
+
+    // an address operand, which may refer to a label defined later
+    fn address(&mut self, token: &Token, kind: AddressKind, opcode: u16) -> Result<()> {
+        let address = self.here;
+        self.emit_opcode(opcode)?;
+        if kind == AddressKind::Unpack {
+            self.emit_opcode(0x6100)?;
+        }
+
+        if self.is_known(token) || validate_name(token).is_err() {
+            let value = self.value(token)?.floor() as i64;
+            return self.patch_address(address, kind, value, token);
+        }
+
+        self.fixups.push(Fixup {
+            address,
+            kind,
+            token: token.clone(),
+        });
+        Ok(())
+    }
+
+    // stores `value` in the instructions emitted at `address`
+    fn patch_address(
+        &mut self,
+        address: usize,
+        kind: AddressKind,
+        value: i64,
+        token: &Token,
+    ) -> Result<()> {
+        let max = if kind == AddressKind::Long {
+            0xffff
+        } else {
+            0xfff
+        };
+        let value = check_range(token, value, 0, max)? as u16;
+
+        match kind {
+            AddressKind::Short => self.patch(address, self.word(address) | value),
+            AddressKind::Long => self.patch(address, value),
+            AddressKind::Unpack => {
+                self.patch(address, self.word(address) | value >> 8);
+                self.patch(address + 2, self.word(address + 2) | value & 0xff);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_known(&self, token: &Token) -> bool {
+        number(&token.text).is_some()
+            || self.constants.contains_key(&token.text)
+            || self.labels.contains_key(&token.text)
+    }
+
+    fn value(&self, token: &Token) -> Result<f64> {
+        if let Some(value) = number(&token.text) {
+            return Ok(value);
+        }
+
+        match (
+            self.constants.get(&token.text),
+            self.labels.get(&token.text),
+        ) {
+            (Some(value), _) => Ok(*value),
+            (_, Some(address)) => Ok(*address as f64),
+            _ => Err(error(token, ErrorKind::UndefinedName(token.text.clone()))),
+        }
+    }
+
+    fn integer(&self, token: &Token, min: i64, max: i64) -> Result<u16> {
+        let value = self.value(token)?.floor() as i64;
+        check_range(token, value, min, max).map(|value| (value & 0xffff) as u16)
+    }
+
+    fn register_opcode(&mut self, opcode: u16) -> Result<()> {
+        let x = self.register()? as u16;
+        self.emit_opcode(opcode | x << 8)
+    }
+
+    fn register(&mut self) -> Result<u8> {
+        let token = self.next()?;
+        self.register_of(&token)
+    }
+
+    fn is_register_token(&self, token: &Token) -> bool {
+        self.aliases.contains_key(&token.text) || is_register(&token.text)
+    }
+
+    fn register_of(&self, token: &Token) -> Result<u8> {
+        if let Some(register) = self.aliases.get(&token.text) {
+            return Ok(*register);
+        }
+
+        match is_register(&token.text) {
+            true => Ok(u8::from_str_radix(&token.text[1..], 16).unwrap_or_default()),
+            false => Err(error(token, expected("a register", token))),
+        }
+    }
+
+    // a new name for a label, constant, alias or macro
+    fn name(&mut self) -> Result<String> {
+        let token = self.next()?;
+        validate_name(&token)?;
+        Ok(token.text)
+    }
+
+    fn expect(&mut self, text: &'static str) -> Result<()> {
+        let token = self.next()?;
+        if token.text != text {
+            let kind = ErrorKind::Expected(text, token.text.clone());
+            return Err(error(&token, kind));
+        }
+
+        Ok(())
+    }
+
+    fn peek_is(&self, text: &str) -> bool {
+        self.tokens.front().is_some_and(|token| token.text == text)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        match self.tokens.pop_front() {
+            Some(token) => {
+                self.last_token = Some(token.clone());
+                Ok(token)
+            }
+            None => {
+                let token = self.last_token.clone();
+                Err(self.error_at(token.as_ref(), ErrorKind::UnexpectedEnd))
+            }
+        }
+    }
+
+    fn emit_opcode(&mut self, opcode: u16) -> Result<()> {
+        self.emit(&opcode.to_be_bytes())
+    }
+
+    fn emit(&mut self, bytes: &[u8]) -> Result<()> {
+        let start = self.here - START_ADDRESS;
+        let end = start + bytes.len();
+        if end > memory::SIZE - START_ADDRESS {
+            let token = self.last_token.clone();
+            return Err(self.error_at(token.as_ref(), ErrorKind::RomTooLarge));
+        }
+
+        if self.rom.len() < end {
+            self.rom.resize(end, 0);
+        }
+        self.rom[start..end].copy_from_slice(bytes);
+        self.here += bytes.len();
+        Ok(())
+    }
+
+    fn word(&self, address: usize) -> u16 {
+        let offset = address - START_ADDRESS;
+        u16::from_be_bytes([self.rom[offset], self.rom[offset + 1]])
+    }
+
+    fn patch(&mut self, address: usize, word: u16) {
+        let offset = address - START_ADDRESS;
+        self.rom[offset..offset + 2].copy_from_slice(&word.to_be_bytes());
+    }
+
+    // points the jump at `address` to the current one
+    fn patch_jump(&mut self, address: usize, token: &Token) -> Result<()> {
+        check_range(token, self.here as i64, 0, 0xfff)?;
+        self.patch(address, 0x1000 | self.here as u16);
+        Ok(())
+    }
+
+    fn error_at(&self, token: Option<&Token>, kind: ErrorKind) -> CompilerError {
+        match token {
+            Some(token) => error(token, kind),
+            None => CompilerError {
+                line: 1,
+                column: 1,
+                kind,
+            },
+        }
+    }
+}
+
+fn is_register(text: &str) -> bool {
+    text.len() == 2
+        && (text.starts_with('v') || text.starts_with('V'))
+        && text[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn validate_name(token: &Token) -> Result<()> {
+    let text = token.text.as_str();
+    if is_register(text)
+        || text == "i"
+        || KEYWORDS.split_whitespace().any(|keyword| keyword == text)
+        || number(text).is_some()
+    {
+        return Err(error(token, ErrorKind::InvalidName(token.text.clone())));
+    }
+
+    Ok(())
+}
+
+fn check_range(token: &Token, value: i64, min: i64, max: i64) -> Result<i64> {
+    if value < min || value > max {
+        return Err(error(token, ErrorKind::OutOfRange { value, min, max }));
+    }
+
+    Ok(value)
+}
+
+fn expected(what: &'static str, token: &Token) -> ErrorKind {
+    ErrorKind::Expected(what, token.text.clone())
+}
+
+fn error(token: &Token, kind: ErrorKind) -> CompilerError {
+    CompilerError {
+        line: token.line,
+        column: token.column,
+        kind,
+    }
+}
@@ -0,0 +1,36 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ErrorKind {
+    #[error("Unexpected end of source")]
+    UnexpectedEnd,
+    #[error("Expected {0}, found `{1}`")]
+    Expected(&'static str, String),
+    #[error("Undefined name `{0}`")]
+    UndefinedName(String),
+    #[error("Name `{0}` already defined")]
+    DuplicateName(String),
+    #[error("`{0}` cannot be used as a name")]
+    InvalidName(String),
+    #[error("Value {value} out of range {min}..={max}")]
+    OutOfRange { value: i64, min: i64, max: i64 },
+    #[error("`{0}` without a matching `{1}`")]
+    Unmatched(&'static str, &'static str),
+    #[error("`{0}` is never closed")]
+    Unclosed(&'static str),
+    #[error("The program has no `main` label")]
+    MissingMain,
+    #[error("The program does not fit in memory")]
+    RomTooLarge,
+    #[error("Macro `{0}` is called more than {1} levels deep")]
+    MacroTooDeep(String, usize),
+}
+
+/// Error in the source, lines and columns start at 1.
+#[derive(Debug, Error)]
+#[error("{line}:{column}: {kind}")]
+pub struct CompilerError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ErrorKind,
+}
@@ -0,0 +1,58 @@
+use std::collections::VecDeque;
+
+/// Word of the source separated by whitespace, `#` starts a comment.
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub text: String,
+    pub line: usize,
+    pub column: usize,
+    // macro calls the token was expanded from, 0 in the source
+    pub depth: usize,
+}
+
+pub fn tokenize(source: &str) -> VecDeque<Token> {
+    let mut tokens = VecDeque::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let code = line.find('#').map_or(line, |comment| &line[..comment]);
+        let mut start = None;
+
+        for (position, c) in code.char_indices().chain(Some((code.len(), ' '))) {
+            match (c.is_whitespace(), start) {
+                (false, None) => start = Some(position),
+                (true, Some(word_start)) => {
+                    tokens.push_back(Token {
+                        text: code[word_start..position].to_string(),
+                        line: index + 1,
+                        column: code[..word_start].chars().count() + 1,
+                        depth: 0,
+                    });
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    tokens
+}
+
+/// Value of a number literal: decimal, `0x` hexadecimal or `0b` binary.
+pub fn number(text: &str) -> Option<f64> {
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(digits) => (-1.0, digits),
+        None => (1.0, text),
+    };
+
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()? as f64
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()? as f64
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+
+    Some(sign * value)
+}
@@ -0,0 +1,12 @@
+mod compiler;
+mod error;
+mod lexer;
+
+pub use error::{CompilerError, ErrorKind};
+
+use compiler::Compiler;
+
+/// Compiles Octo source into a rom loaded at `0x200`.
+pub fn compile(source: &str) -> Result<Vec<u8>, CompilerError> {
+    Compiler::new(lexer::tokenize(source)).compile()
+}
@@ -0,0 +1,279 @@
+//! Octo sources compiled into roms, one test per family of statements, and
+//! the position of the errors.
+
+use octo::{compile, CompilerError, ErrorKind};
+
+fn rom(source: &str) -> Vec<u8> {
+    compile(source).unwrap_or_else(|error| panic!("{}", error))
+}
+
+fn words(words: &[u16]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_be_bytes()).collect()
+}
+
+fn error(source: &str) -> CompilerError {
+    compile(source).unwrap_err()
+}
+
+#[test]
+fn main_is_jumped_to_unless_it_comes_first() {
+    assert_eq!(rom(": main clear"), words(&[0x00e0]));
+    assert_eq!(
+        rom("clear # before main\n: main return"),
+        words(&[0x1204, 0x00e0, 0x00ee])
+    );
+}
+
+#[test]
+fn instructions_without_registers() {
+    let source = "\
+: main
+  clear return ; scroll-right scroll-left exit lores hires audio
+  scroll-down 1 scroll-up 15 plane 3
+";
+
+    assert_eq!(
+        rom(source),
+        words(&[
+            0x00e0, 0x00ee, 0x00ee, 0x00fb, 0x00fc, 0x00fd, 0x00fe, 0x00ff, 0xf002, 0x00c1, 0x00df,
+            0xf301
+        ])
+    );
+}
+
+#[test]
+fn register_assignments() {
+    let source = "\
+: main
+  v1 := 0x12 v2 := v3 v4 := key v5 := delay v6 := random 0x0f
+  v7 += 2 v8 -= 1 v9 += va vb -= vc vd =- ve
+  v0 |= v1 v0 &= v1 v0 ^= v1 v0 >>= v1 v0 <<= v1
+  VF := -1
+";
+
+    assert_eq!(
+        rom(source),
+        words(&[
+            0x6112, 0x8230, 0xf40a, 0xf507, 0xc60f, 0x7702, 0x78ff, 0x89a4, 0x8bc5, 0x8de7, 0x8011,
+            0x8012, 0x8013, 0x8016, 0x801e, 0x6fff
+        ])
+    );
+}
+
+#[test]
+fn index_assignments() {
+    let source = "\
+: main
+  i := hex v1 i := bighex v2 i := long 0x1234 i := 0x345 i += v3
+  i := data
+: data
+";
+
+    assert_eq!(
+        rom(source),
+        words(&[0xf129, 0xf230, 0xf000, 0x1234, 0xa345, 0xf31e, 0xa20e])
+    );
+}
+
+#[test]
+fn register_and_address_instructions() {
+    let source = "\
+: main
+  bcd v1 save v2 load v3 save v1 - v4 load v5 - v2 saveflags v6 loadflags v7
+  sprite v1 v2 5 jump 0x300 jump0 0x400 native 0x123
+  delay := v8 buzzer := v9 pitch := va
+";
+
+    assert_eq!(
+        rom(source),
+        words(&[
+            0xf133, 0xf255, 0xf365, 0x5142, 0x5523, 0xf675, 0xf785, 0xd125, 0x1300, 0xb400, 0x0123,
+            0xf815, 0xf918, 0xfa3a
+        ])
+    );
+}
+
+#[test]
+fn directives() {
+    let source = "\
+:const SPEED 3
+:alias x v4
+:calc DOUBLE { 1 + SPEED * 2 }
+: main
+  x := SPEED
+  x += DOUBLE
+  :unpack 0xa data
+  :next target
+  v0 := 0
+  target
+  :call sub
+  :byte 0xff
+  :byte { DOUBLE - 10 }
+  :pointer data
+: sub return
+: data 1 2
+";
+
+    assert_eq!(
+        rom(source),
+        [
+            0x64, 0x03, 0x74, 0x07, 0x60, 0xa2, 0x61, 0x14, 0x60, 0x00, 0x22, 0x09, 0x22, 0x12,
+            0xff, 0xfd, 0x02, 0x14, 0x00, 0xee, 0x01, 0x02
+        ]
+    );
+
+    let rom = rom(": main jump 0x300\n:org 0x300 clear");
+    assert_eq!(rom.len(), 0x102);
+    assert_eq!(rom[..2], words(&[0x1300])[..]);
+    assert_eq!(rom[0x100..], words(&[0x00e0])[..]);
+}
+
+#[test]
+fn macros_substitute_their_arguments() {
+    let source = "\
+:macro twice register { register += 1 register += 1 }
+:macro counted { v0 := CALLS }
+: main
+  twice v3 counted counted twice v4
+";
+
+    assert_eq!(
+        rom(source),
+        words(&[0x7301, 0x7301, 0x6000, 0x6001, 0x7401, 0x7401])
+    );
+}
+
+#[test]
+fn recursive_macros_are_rejected() {
+    // 64 levels of macros calling the next one are fine
+    let mut source = String::from(":macro m0 { clear }\n");
+    for level in 1..64 {
+        source += &format!(":macro m{} {{ m{} }}\n", level, level - 1);
+    }
+    source += ": main m63";
+    assert_eq!(rom(&source), words(&[0x00e0]));
+
+    let cases = [
+        ": main\n:macro m { m }\n  m",
+        ": main\n:macro m { clear m m }\n  m",
+        // the macro name passed as its own argument
+        ": main\n:macro m x { x x }\n  m m",
+    ];
+    for source in &cases {
+        let error = error(source);
+        assert!(
+            matches!(error.kind, ErrorKind::MacroTooDeep(ref name, 64) if name == "m"),
+            "{:?}: {}",
+            source,
+            error
+        );
+    }
+
+    assert_eq!(
+        error(": main\n:macro m { m }\n  m").to_string(),
+        "2:12: Macro `m` is called more than 64 levels deep"
+    );
+}
+
+#[test]
+fn conditions_skip_the_next_instruction() {
+    let source = "\
+: main
+  if v0 == 1 then v1 := 2
+  if v0 != v2 then clear
+  if v3 key then clear
+  if v3 -key then clear
+  if v1 > 5 then clear
+";
+
+    assert_eq!(
+        rom(source),
+        words(&[
+            0x4001, 0x6102, 0x5020, 0x00e0, 0xe3a1, 0x00e0, 0xe39e, 0x00e0, 0x6f05, 0x8f15, 0x3f01,
+            0x00e0
+        ])
+    );
+}
+
+#[test]
+fn blocks_jump_over_their_branches() {
+    let source = "\
+: main
+  if v0 == 0 begin
+    v1 := 1
+  else
+    v1 := 2
+  end
+";
+
+    assert_eq!(
+        rom(source),
+        words(&[0x3000, 0x1208, 0x6101, 0x120a, 0x6102])
+    );
+
+    let source = "\
+: main
+  loop
+    v0 += 1
+    while v0 != 10
+  again
+";
+
+    assert_eq!(rom(source), words(&[0x7001, 0x400a, 0x1208, 0x1200]));
+}
+
+#[test]
+fn scrolls_by_zero_rows_are_rejected() {
+    for source in &[": main\n  scroll-down 0", ": main\n  scroll-up   0"] {
+        let error = error(source);
+        assert!(matches!(
+            error.kind,
+            ErrorKind::OutOfRange {
+                value: 0,
+                min: 1,
+                max: 15
+            }
+        ));
+        assert_eq!((error.line, error.column), (2, 15));
+        assert_eq!(error.to_string(), "2:15: Value 0 out of range 1..=15");
+    }
+}
+
+#[test]
+fn errors_report_their_line_and_column() {
+    let cases: &[(&str, usize, usize)] = &[
+        (": main\n  v0 := 256", 2, 9),
+        (": main\n  jump nowhere", 2, 8),
+        (": main\n: main", 2, 3),
+        (": v1", 1, 3),
+        (": main\n  v0 :=", 2, 6),
+        (": main\n  i := hex", 2, 8),
+        (": main\n  v0 <- v1", 2, 6),
+        (": main\n  if v0 ~ 1 then clear", 2, 9),
+        (": main\n  else", 2, 3),
+        (": main\n  if v0 == 1 begin\n  clear", 2, 14),
+        (": main\n:org 0xffff clear", 2, 13),
+        ("clear", 1, 1),
+    ];
+
+    for (source, line, column) in cases {
+        let error = error(source);
+        assert_eq!(
+            (error.line, error.column),
+            (*line, *column),
+            "{:?}: {}",
+            source,
+            error
+        );
+    }
+
+    assert!(matches!(
+        error(": main\n  jump nowhere").kind,
+        ErrorKind::UndefinedName(ref name) if name == "nowhere"
+    ));
+    assert!(matches!(
+        error(": main\n  if v0 == 1 begin").kind,
+        ErrorKind::Unclosed("begin")
+    ));
+    assert!(matches!(error("clear").kind, ErrorKind::MissingMain));
+}
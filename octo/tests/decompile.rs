@@ -0,0 +1,33 @@
+//! Decompiling a rom into Octo source and compiling it gives back the same
+//! bytes, on every platform.
+
+use chip8_core::decompiler::Decompiler;
+use chip8_core::emulator::platform::Platform;
+use std::fs;
+use std::path::Path;
+
+const PLATFORMS: &[Platform] = &[Platform::Chip8, Platform::SuperChip, Platform::XoChip];
+
+#[test]
+fn bundled_roms_round_trip() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+    let mut roms = 0;
+
+    for entry in fs::read_dir(root).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|extension| extension == "ch8") {
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            let rom = fs::read(&path).unwrap();
+
+            for platform in PLATFORMS {
+                let source = Decompiler::new(*platform).decompile(&rom).to_string();
+                let compiled = octo::compile(&source)
+                    .unwrap_or_else(|error| panic!("{} on {:?}: {}", name, platform, error));
+                assert_eq!(compiled, rom, "{} on {:?}", name, platform);
+            }
+            roms += 1;
+        }
+    }
+
+    assert!(roms >= 6);
+}
@@ -1,9 +1,10 @@
-use core::emulator;
+use chip8_core::emulator;
+use chip8_core::emulator::audio::AudioPattern;
 
 pub struct Audio {}
 
 impl emulator::audio::Audio for Audio {
-    fn beep(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    fn beep(&mut self, _pattern: &AudioPattern) -> Result<(), Box<dyn std::error::Error>> {
         Ok(())
     }
 
@@ -1,4 +1,4 @@
-use core::emulator;
+use chip8_core::emulator;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
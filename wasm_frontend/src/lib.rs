@@ -4,18 +4,16 @@ mod utils;
 
 use crate::audio::Audio;
 use crate::key_mapper::KeyMapper;
-use core::emulator;
-use core::emulator::cpu::Cpu;
-use core::emulator::display::Display;
-use core::emulator::keyboard::KeyboardState;
-use core::emulator::memory::Memory;
+use chip8_core::emulator::chip8::Chip8;
+use chip8_core::emulator::display::Display;
+use chip8_core::emulator::keyboard::{KeyMapper as _, KeyboardState};
 use gloo_events::{EventListener, EventListenerOptions, EventListenerPhase};
 use gloo_timers::callback::Interval;
 use std::sync::Arc;
+use wasm_bindgen::__rt::std::sync::RwLock;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::Clamped;
 use wasm_bindgen::JsCast;
-use wasm_bindgen::__rt::std::sync::RwLock;
 use web_sys::{CanvasRenderingContext2d, Document, ImageData, KeyboardEvent, Window};
 
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
@@ -24,6 +22,18 @@ use web_sys::{CanvasRenderingContext2d, Document, ImageData, KeyboardEvent, Wind
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
+// one emulated frame every interval tick, at about 60Hz
+const FRAME_MILLIS: u32 = 1000 / 60;
+const INSTRUCTIONS_PER_FRAME: usize = 10;
+
+// colors of the pixels lit on no plane, on the first, on the second and on both
+const PALETTE: [[u8; 4]; 4] = [
+    [0x00, 0x00, 0x00, 0xFF],
+    [0xFF, 0xFF, 0xFF, 0xFF],
+    [0xAA, 0xAA, 0xAA, 0xFF],
+    [0x55, 0x55, 0x55, 0xFF],
+];
+
 #[wasm_bindgen]
 extern "C" {
     fn alert(s: &str);
@@ -51,7 +61,7 @@ pub fn run_emu(rom_bytes: &[u8]) {
         .dyn_into::<web_sys::CanvasRenderingContext2d>()
         .unwrap();
 
-    let keys: Arc<RwLock<Vec<String>>> = Arc::new(RwLock::new(vec![]));
+    let keys: Arc<RwLock<KeyboardState>> = Arc::new(RwLock::new(KeyboardState::default()));
 
     setup_key_down_listener(&document, keys.clone());
     setup_key_up_listener(&document, keys.clone());
@@ -59,74 +69,76 @@ pub fn run_emu(rom_bytes: &[u8]) {
     run(context, rom_bytes, keys.clone());
 }
 
-fn setup_key_down_listener(document: &Document, keys: Arc<RwLock<Vec<String>>>) {
+fn setup_key_down_listener(document: &Document, keys: Arc<RwLock<KeyboardState>>) {
     let on_key_down = EventListener::new_with_options(
-        &document,
+        document,
         "keydown",
         EventListenerOptions {
             phase: EventListenerPhase::Capture,
             passive: true,
         },
         move |event| {
-            let mut keys = keys.write().unwrap();
             let keyboard_event = event.clone().dyn_into::<KeyboardEvent>().unwrap();
-            let mut event_string = String::from("");
-            event_string.push_str(&keyboard_event.key());
-            keys.push(event_string);
+            let key = KeyMapper {}.map_key(keyboard_event.key());
+            if let Ok(key) = key {
+                keys.write().unwrap().press(key);
+            }
         },
     );
 
     on_key_down.forget();
 }
 
-fn setup_key_up_listener(document: &Document, keys: Arc<RwLock<Vec<String>>>) {
+fn setup_key_up_listener(document: &Document, keys: Arc<RwLock<KeyboardState>>) {
     let on_key_up = EventListener::new_with_options(
-        &document,
+        document,
         "keyup",
         EventListenerOptions {
             phase: EventListenerPhase::Capture,
             passive: true,
         },
         move |_event| {
-            let mut keys = keys.write().unwrap();
-            keys.clear()
+            *keys.write().unwrap() = KeyboardState::default();
         },
     );
 
     on_key_up.forget();
 }
 
-fn run(context: CanvasRenderingContext2d, rom_bytes: &[u8], keys: Arc<RwLock<Vec<String>>>) {
-    let mut memory = Memory::default();
-    memory.load_rom(rom_bytes);
-    let mut cpu = Cpu::new(memory);
-    let mut display = Display::default();
-
-    let mut audio = Audio {};
-
-    let i = Interval::new(1 / 100, move || {
-        let state = KeyboardState::new(keys.read().unwrap().clone(), &KeyMapper {});
-
-        cpu.cycle(&mut display, &mut audio, state).unwrap();
-
+fn run(context: CanvasRenderingContext2d, rom_bytes: &[u8], keys: Arc<RwLock<KeyboardState>>) {
+    let mut chip8 = match Chip8::builder()
+        .rom(rom_bytes)
+        .instructions_per_frame(INSTRUCTIONS_PER_FRAME)
+        .audio(Box::new(Audio {}))
+        .build()
+    {
+        Ok(chip8) => chip8,
+        Err(error) => {
+            alert(&error.to_string());
+            return;
+        }
+    };
+    let mut hires = chip8.display().is_hires();
+    // RGBA bytes of the display, reused by every frame
+    let mut buffer = Vec::with_capacity(Display::max_width() * Display::max_height() * 4);
+
+    let i = Interval::new(FRAME_MILLIS, move || {
+        chip8.set_keyboard_state(*keys.read().unwrap());
+        chip8.run_frame().unwrap();
+
+        let display = chip8.display();
+
+        if hires != display.is_hires() {
+            hires = display.is_hires();
+            scale_canvas(&context, display.width());
+        }
+
+        buffer.clear();
+        buffer.extend(display.rgba(PALETTE).flatten());
         let data = ImageData::new_with_u8_clamped_array_and_sh(
-            Clamped(
-                display
-                    .buffer()
-                    .iter()
-                    .map(|b| {
-                        if *b {
-                            vec![0xFF, 0xFF, 0xFF, 0xFF]
-                        } else {
-                            vec![0x00, 0x00, 0x00, 0xFF]
-                        }
-                    })
-                    .flatten()
-                    .collect::<Vec<u8>>()
-                    .as_mut_slice(),
-            ),
-            (emulator::display::Display::width()) as u32,
-            (emulator::display::Display::height()) as u32,
+            Clamped(buffer.as_mut_slice()),
+            display.width() as u32,
+            display.height() as u32,
         )
         .expect("Error converting display into js byte data");
 
@@ -137,3 +149,18 @@ fn run(context: CanvasRenderingContext2d, rom_bytes: &[u8], keys: Arc<RwLock<Vec
 
     i.forget();
 }
+
+// keeps the canvas at the same on screen size in both low and high resolution
+fn scale_canvas(context: &CanvasRenderingContext2d, width: usize) {
+    if let Some(canvas) = context.canvas() {
+        canvas
+            .set_attribute(
+                "style",
+                &format!(
+                    "transform: scale({}); transform-origin: top left",
+                    1024 / width
+                ),
+            )
+            .expect("Error scaling canvas");
+    }
+}